@@ -0,0 +1,185 @@
+use thiserror::Error;
+
+/// Number of satoshis in one bitcoin.
+pub const SATS_PER_BTC: u64 = 100_000_000;
+
+/// Maximum number of decimal places of a bitcoin amount.
+const BTC_DECIMALS: usize = 8;
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum FormatError {
+    #[error("Empty amount")]
+    Empty,
+    #[error("Negative amount")]
+    Negative,
+    #[error("Too many decimal places, at most 8 are allowed")]
+    TooPrecise,
+    #[error("Invalid amount: {0}")]
+    Invalid(String),
+    #[error("Amount too large")]
+    Overflow,
+    #[error("Amount in sats must be a whole number")]
+    FractionalSats,
+}
+
+/// Unit of an amount typed by the user.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AmountUnit {
+    Btc,
+    Sats,
+}
+
+/// Adds thousands separators to a string of digits.
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+/// Formats an amount in satoshis as a BTC string with 8 decimals and thousands separators.
+pub fn sats_to_btc_string(sats: u64) -> String {
+    let whole = group_thousands(&(sats / SATS_PER_BTC).to_string());
+    format!("{}.{:08}", whole, sats % SATS_PER_BTC)
+}
+
+/// Formats an amount in satoshis as a plain BTC decimal, without thousands separators or trailing zeros,
+/// e.g. for the amount of a BIP21 URI.
+pub fn sats_to_btc_decimal(sats: u64) -> String {
+    let fraction = format!("{:08}", sats % SATS_PER_BTC);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        (sats / SATS_PER_BTC).to_string()
+    } else {
+        format!("{}.{}", sats / SATS_PER_BTC, fraction)
+    }
+}
+
+/// Parses a BTC string into an amount in satoshis.
+/// Thousands separators are allowed, negative amounts and more than 8 decimals are rejected.
+pub fn btc_string_to_sats(s: &str) -> Result<u64, FormatError> {
+    let s = s.trim().replace(',', "");
+    if s.is_empty() {
+        return Err(FormatError::Empty);
+    }
+    if s.starts_with('-') {
+        return Err(FormatError::Negative);
+    }
+
+    let (whole, fraction) = s.split_once('.').unwrap_or((&s, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(FormatError::Invalid(s.clone()));
+    }
+    if !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        return Err(FormatError::Invalid(s.clone()));
+    }
+    // NOTE: trailing zeros don't add precision
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > BTC_DECIMALS {
+        return Err(FormatError::TooPrecise);
+    }
+
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| FormatError::Overflow)?
+    };
+    let fraction: u64 = format!("{:0<8}", fraction)
+        .parse()
+        .map_err(|_| FormatError::Invalid(s.clone()))?;
+
+    whole
+        .checked_mul(SATS_PER_BTC)
+        .and_then(|sats| sats.checked_add(fraction))
+        .ok_or(FormatError::Overflow)
+}
+
+/// Parses an amount in the given unit into satoshis.
+/// Thousands separators are allowed, BTC amounts take at most 8 decimals
+/// and sats amounts must be whole numbers.
+pub fn parse_amount(input: &str, unit: AmountUnit) -> Result<u64, FormatError> {
+    match unit {
+        AmountUnit::Btc => btc_string_to_sats(input),
+        AmountUnit::Sats => {
+            let s = input.trim().replace(',', "");
+            if s.is_empty() {
+                return Err(FormatError::Empty);
+            }
+            if s.starts_with('-') {
+                return Err(FormatError::Negative);
+            }
+
+            let (whole, fraction) = s.split_once('.').unwrap_or((&s, ""));
+            if whole.is_empty() || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+                return Err(FormatError::Invalid(s.clone()));
+            }
+            if fraction.chars().any(|c| c != '0') {
+                return Err(FormatError::FractionalSats);
+            }
+            whole.parse().map_err(|_| FormatError::Overflow)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sats_to_btc_string() {
+        assert_eq!(sats_to_btc_string(0), "0.00000000");
+        assert_eq!(sats_to_btc_string(1), "0.00000001");
+        assert_eq!(sats_to_btc_string(SATS_PER_BTC), "1.00000000");
+        assert_eq!(sats_to_btc_string(21_000_000 * SATS_PER_BTC), "21,000,000.00000000");
+    }
+
+    #[test]
+    fn test_sats_to_btc_decimal() {
+        assert_eq!(sats_to_btc_decimal(0), "0");
+        assert_eq!(sats_to_btc_decimal(1), "0.00000001");
+        assert_eq!(sats_to_btc_decimal(50_000), "0.0005");
+        assert_eq!(sats_to_btc_decimal(21_000_000 * SATS_PER_BTC), "21000000");
+    }
+
+    #[test]
+    fn test_btc_string_to_sats() {
+        assert_eq!(btc_string_to_sats("0.00000001"), Ok(1));
+        assert_eq!(btc_string_to_sats("1"), Ok(SATS_PER_BTC));
+        assert_eq!(btc_string_to_sats("1.0"), Ok(SATS_PER_BTC));
+        assert_eq!(btc_string_to_sats(".5"), Ok(SATS_PER_BTC / 2));
+        assert_eq!(btc_string_to_sats("21,000,000.00000000"), Ok(21_000_000 * SATS_PER_BTC));
+    }
+
+    #[test]
+    fn test_btc_string_to_sats_invalid() {
+        assert_eq!(btc_string_to_sats("0.000000001"), Err(FormatError::TooPrecise));
+        assert_eq!(btc_string_to_sats("-1"), Err(FormatError::Negative));
+        assert_eq!(btc_string_to_sats(""), Err(FormatError::Empty));
+        assert_eq!(btc_string_to_sats("."), Err(FormatError::Invalid(".".to_string())));
+        assert_eq!(btc_string_to_sats("1.2.3"), Err(FormatError::Invalid("1.2.3".to_string())));
+        assert_eq!(btc_string_to_sats("abc"), Err(FormatError::Invalid("abc".to_string())));
+        assert_eq!(btc_string_to_sats("999999999999"), Err(FormatError::Overflow));
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("0.001", AmountUnit::Btc), Ok(100_000));
+        assert_eq!(parse_amount("0.00100000", AmountUnit::Btc), Ok(100_000));
+        assert_eq!(parse_amount("100000", AmountUnit::Sats), Ok(100_000));
+        assert_eq!(parse_amount("100,000", AmountUnit::Sats), Ok(100_000));
+        assert_eq!(parse_amount("100000.0", AmountUnit::Sats), Ok(100_000));
+    }
+
+    #[test]
+    fn test_parse_amount_invalid() {
+        assert_eq!(parse_amount("0.123456789", AmountUnit::Btc), Err(FormatError::TooPrecise));
+        assert_eq!(parse_amount("100.5", AmountUnit::Sats), Err(FormatError::FractionalSats));
+        assert_eq!(parse_amount("abc", AmountUnit::Sats), Err(FormatError::Invalid("abc".to_string())));
+        assert_eq!(parse_amount("", AmountUnit::Btc), Err(FormatError::Empty));
+        assert_eq!(parse_amount("-1", AmountUnit::Sats), Err(FormatError::Negative));
+    }
+}
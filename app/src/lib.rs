@@ -2,8 +2,13 @@
 use leptos::*;
 use leptos_meta::*;
 use leptos_router::*;
+use views::{Balance, SendForm};
 
 pub mod error_template;
+pub mod format;
+#[cfg(target_arch = "wasm32")]
+pub mod storage;
+pub mod views;
 
 #[component]
 pub fn App() -> impl IntoView {
@@ -38,8 +43,14 @@ fn HomePage() -> impl IntoView {
     let (count, set_count) = create_signal(0);
     let on_click = move |_| set_count.update(|count| *count += 1);
 
+    // NOTE: the balance is filled in once the wallet is synced
+    let (balance, _set_balance) = create_signal(0u64);
+    let on_amount = move |sats: u64| logging::log!("Sending {} sats", sats);
+
     view! {
         <h1>"Welcome to Leptos!"</h1>
         <button type="button" class="py-[.688rem] px-4 inline-flex justify-center items-center gap-2 rounded-md border-2 border-gray-200 font-semibold text-blue-500 hover:text-white hover:bg-blue-500 hover:border-blue-500 focus:outline-none focus:ring-2 focus:ring-blue-500 focus:ring-offset-2 transition-all text-sm dark:border-gray-700 dark:hover:border-blue-500" on:click=on_click>"Click Me: " {count}</button>
+        <Balance sats=balance/>
+        <SendForm balance=balance on_amount=on_amount/>
     }
 }
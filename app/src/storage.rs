@@ -0,0 +1,78 @@
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+#[derive(Clone, Debug, Error)]
+pub enum StorageError {
+    #[error("localStorage is not available")]
+    Unavailable,
+    #[error("Error serializing to JSON: {0}")]
+    Serialize(String),
+    #[error("Error writing to localStorage: {0}")]
+    Write(String),
+}
+
+/// A store backed by the browser's localStorage, as JSON, keyed by the wallet's public descriptor.
+/// Values are replaced on persist, so changesets must be aggregated before persisting them.
+#[derive(Debug, Default)]
+pub struct LocalStorageStore;
+
+impl LocalStorageStore {
+    const KEY_PREFIX: &'static str = "bdk-wallet:";
+    const SYNC_PROGRESS_KEY_PREFIX: &'static str = "bdk-wallet-sync:";
+    const INVOICES_KEY_PREFIX: &'static str = "bdk-wallet-invoices:";
+
+    fn storage() -> Result<web_sys::Storage, StorageError> {
+        let window = web_sys::window().ok_or(StorageError::Unavailable)?;
+        match window.local_storage() {
+            Ok(Some(storage)) => Ok(storage),
+            _ => Err(StorageError::Unavailable),
+        }
+    }
+
+    fn get<T: DeserializeOwned>(key: &str) -> Option<T> {
+        let json = Self::storage().ok()?.get_item(key).ok()??;
+        serde_json::from_str(&json).ok()
+    }
+
+    fn set<T: Serialize>(key: &str, value: Option<&T>) -> Result<(), StorageError> {
+        let storage = Self::storage()?;
+        let result = match value {
+            Some(value) => {
+                let json = serde_json::to_string(value).map_err(|e| StorageError::Serialize(e.to_string()))?;
+                storage.set_item(key, &json)
+            }
+            None => storage.remove_item(key),
+        };
+        result.map_err(|e| StorageError::Write(format!("{:?}", e)))
+    }
+
+    /// Loads the aggregated changeset persisted for a descriptor.
+    pub fn load<T: DeserializeOwned>(&self, descriptor: &str) -> Option<T> {
+        Self::get(&format!("{}{}", Self::KEY_PREFIX, descriptor))
+    }
+
+    /// Persists the aggregated changeset of a descriptor, replacing the previous one.
+    pub fn persist<T: Serialize>(&self, descriptor: &str, changeset: &T) -> Result<(), StorageError> {
+        Self::set(&format!("{}{}", Self::KEY_PREFIX, descriptor), Some(changeset))
+    }
+
+    /// Loads the progress of an interrupted sync of a descriptor, if any.
+    pub fn load_sync_progress<T: DeserializeOwned>(&self, descriptor: &str) -> Option<T> {
+        Self::get(&format!("{}{}", Self::SYNC_PROGRESS_KEY_PREFIX, descriptor))
+    }
+
+    /// Saves the progress of an ongoing sync of a descriptor, `None` clearing it.
+    pub fn persist_sync_progress<T: Serialize>(&self, descriptor: &str, progress: Option<&T>) -> Result<(), StorageError> {
+        Self::set(&format!("{}{}", Self::SYNC_PROGRESS_KEY_PREFIX, descriptor), progress)
+    }
+
+    /// Loads the invoices created for a descriptor, if any.
+    pub fn load_invoices<T: DeserializeOwned>(&self, descriptor: &str) -> Option<T> {
+        Self::get(&format!("{}{}", Self::INVOICES_KEY_PREFIX, descriptor))
+    }
+
+    /// Saves the invoices created for a descriptor, replacing the previous ones.
+    pub fn persist_invoices<T: Serialize>(&self, descriptor: &str, invoices: &T) -> Result<(), StorageError> {
+        Self::set(&format!("{}{}", Self::INVOICES_KEY_PREFIX, descriptor), Some(invoices))
+    }
+}
@@ -0,0 +1,75 @@
+use crate::format::{parse_amount, sats_to_btc_string, AmountUnit};
+use leptos::*;
+
+/// Displays a balance in BTC, with 8 decimals and thousands separators.
+#[component]
+pub fn Balance(#[prop(into)] sats: Signal<u64>) -> impl IntoView {
+    view! {
+        <p class="text-2xl font-semibold text-gray-800 dark:text-gray-200">
+            {move || sats_to_btc_string(sats.get())} " BTC"
+        </p>
+    }
+}
+
+/// A form to enter the amount to send, in BTC or sats, checked against the balance.
+/// `on_amount` is called with the amount in sats once it parses and is within the balance.
+#[component]
+pub fn SendForm<F>(#[prop(into)] balance: Signal<u64>, on_amount: F) -> impl IntoView
+where
+    F: Fn(u64) + 'static,
+{
+    let (input, set_input) = create_signal(String::new());
+    let (unit, set_unit) = create_signal(AmountUnit::Btc);
+
+    let amount = move || {
+        let sats = parse_amount(input.get().as_str(), unit.get()).map_err(|e| e.to_string())?;
+        if sats > balance.get() {
+            return Err(format!("Amount exceeds the balance of {} BTC", sats_to_btc_string(balance.get())));
+        }
+        Ok(sats)
+    };
+    let on_submit = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        if let Ok(sats) = amount() {
+            on_amount(sats);
+        }
+    };
+
+    view! {
+        <form class="flex flex-col gap-2" on:submit=on_submit>
+            <div class="flex gap-2">
+                <input
+                    type="text"
+                    inputmode="decimal"
+                    placeholder="Amount"
+                    class="py-2 px-3 block w-full rounded-md border border-gray-200 text-sm dark:bg-slate-900 dark:border-gray-700 dark:text-gray-400"
+                    prop:value=input
+                    on:input=move |ev| set_input.set(event_target_value(&ev))
+                />
+                <select
+                    class="py-2 px-3 rounded-md border border-gray-200 text-sm dark:bg-slate-900 dark:border-gray-700 dark:text-gray-400"
+                    on:change=move |ev| {
+                        let unit = if event_target_value(&ev) == "sats" { AmountUnit::Sats } else { AmountUnit::Btc };
+                        set_unit.set(unit);
+                    }
+                >
+                    <option value="btc">"BTC"</option>
+                    <option value="sats">"sats"</option>
+                </select>
+            </div>
+            {move || match amount() {
+                // NOTE: no error for an empty input, the user hasn't typed anything yet
+                Err(_) if input.get().trim().is_empty() => None,
+                Err(e) => Some(view! { <p class="text-sm text-red-600">{e}</p> }),
+                Ok(sats) => Some(view! { <p class="text-sm text-gray-500">{sats_to_btc_string(sats)} " BTC"</p> }),
+            }}
+            <button
+                type="submit"
+                class="py-2 px-4 inline-flex justify-center items-center gap-2 rounded-md border-2 border-gray-200 font-semibold text-blue-500 hover:text-white hover:bg-blue-500 hover:border-blue-500 disabled:opacity-50 transition-all text-sm dark:border-gray-700 dark:hover:border-blue-500"
+                disabled=move || amount().is_err()
+            >
+                "Send"
+            </button>
+        </form>
+    }
+}
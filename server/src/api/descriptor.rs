@@ -0,0 +1,231 @@
+use anyhow::{bail, Result};
+use crate::api::script_type::ScriptType;
+use bdk::{
+    bitcoin::{secp256k1::Secp256k1, util::base58, Address, Network},
+    descriptor::{calc_checksum, IntoWalletDescriptor},
+    miniscript::{descriptor::{DescriptorPublicKey, Wildcard}, Descriptor, ForEachKey},
+};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Information about a parsed descriptor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DescriptorInfo {
+    pub valid: bool,
+    pub checksum: String,
+    pub script_type: String,
+    pub is_ranged: bool,
+    pub has_private_keys: bool,
+}
+
+/// Splits a descriptor into its body and its `#checksum`, if any.
+fn split_checksum(descriptor: &str) -> (&str, Option<&str>) {
+    match descriptor.trim().split_once('#') {
+        Some((body, checksum)) => (body, Some(checksum)),
+        None => (descriptor.trim(), None),
+    }
+}
+
+/// SLIP-0132 version bytes of single-sig extended public keys, with the canonical version bytes
+/// they convert to and the script type they imply.
+const SLIP132_VERSIONS: [([u8; 4], [u8; 4], Option<ScriptType>); 6] = [
+    // xpub and tpub
+    ([0x04, 0x88, 0xb2, 0x1e], [0x04, 0x88, 0xb2, 0x1e], None),
+    ([0x04, 0x35, 0x87, 0xcf], [0x04, 0x35, 0x87, 0xcf], None),
+    // ypub and upub
+    ([0x04, 0x9d, 0x7c, 0xb2], [0x04, 0x88, 0xb2, 0x1e], Some(ScriptType::NestedSegwit)),
+    ([0x04, 0x4a, 0x52, 0x62], [0x04, 0x35, 0x87, 0xcf], Some(ScriptType::NestedSegwit)),
+    // zpub and vpub
+    ([0x04, 0xb2, 0x47, 0x46], [0x04, 0x88, 0xb2, 0x1e], Some(ScriptType::NativeSegwit)),
+    ([0x04, 0x5f, 0x1c, 0xf6], [0x04, 0x35, 0x87, 0xcf], Some(ScriptType::NativeSegwit)),
+];
+
+/// Converts a SLIP-0132 `ypub`/`zpub` (or testnet `upub`/`vpub`) to the canonical `xpub`/`tpub`,
+/// with the script type it implies. Plain `xpub`/`tpub` keys imply no script type.
+/// Returns an error for unknown version bytes.
+pub fn normalize_slip132(key: &str) -> Result<(String, Option<ScriptType>)> {
+    let mut data = base58::from_check(key.trim())?;
+    if data.len() != 78 {
+        bail!("Invalid extended public key length: {} bytes", data.len());
+    }
+    let version: [u8; 4] = [data[0], data[1], data[2], data[3]];
+    match SLIP132_VERSIONS.iter().find(|(slip132, _, _)| *slip132 == version) {
+        Some((_, canonical, script_type)) => {
+            data[..4].copy_from_slice(canonical);
+            Ok((base58::check_encode_slice(&data), *script_type))
+        }
+        None => bail!("Unknown extended public key version bytes: {:02x?}", version),
+    }
+}
+
+/// Expands a multipath descriptor (`/<0;1>/*`) into its external and internal descriptors.
+/// Returns `None` if the descriptor isn't multipath, and an error for arities other than 2.
+pub fn expand_multipath(descriptor: &str) -> Result<Option<(String, String)>> {
+    // NOTE: the checksum is no longer valid once expanded
+    let (body, _) = split_checksum(descriptor);
+    if !body.contains('<') {
+        return Ok(None);
+    }
+
+    let mut external = String::with_capacity(body.len());
+    let mut internal = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find('<') {
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => bail!("Invalid multipath descriptor: unclosed '<'"),
+        };
+        let paths: Vec<&str> = rest[start + 1..end].split(';').collect();
+        if paths.len() != 2 {
+            bail!("Unsupported multipath descriptor: expected 2 paths, found {}", paths.len());
+        }
+        external.push_str(&rest[..start]);
+        external.push_str(paths[0]);
+        internal.push_str(&rest[..start]);
+        internal.push_str(paths[1]);
+        rest = &rest[end + 1..];
+    }
+    external.push_str(rest);
+    internal.push_str(rest);
+
+    Ok(Some((external, internal)))
+}
+
+/// Validates a descriptor for a network type, computing its canonical checksum.
+/// A missing checksum is accepted, but a mismatched one returns an error with the expected checksum.
+pub fn validate_descriptor(descriptor: &str, network: Network) -> Result<DescriptorInfo> {
+    let secp = Secp256k1::new();
+
+    let (body, found) = split_checksum(descriptor);
+    let checksum = calc_checksum(body)?;
+    if let Some(found) = found {
+        if found != checksum {
+            bail!("Invalid descriptor checksum: expected #{}, found #{}", checksum, found);
+        }
+    }
+
+    let (descriptor, keymap) = body.into_wallet_descriptor(&secp, network)?;
+
+    Ok(DescriptorInfo {
+        valid: true,
+        checksum,
+        script_type: format!("{:?}", descriptor.desc_type()),
+        is_ranged: descriptor.has_wildcard(),
+        has_private_keys: !keymap.is_empty(),
+    })
+}
+
+/// Checks that a descriptor doesn't derive hardened children at a wildcard (`/*'` or `/*h`) from an extended public key,
+/// as a watch-only wallet can't derive its addresses without the private key.
+pub fn check_hardened_wildcard(descriptor: &str) -> Result<()> {
+    let secp = Secp256k1::new();
+    let (descriptor, keymap) = Descriptor::<DescriptorPublicKey>::parse_descriptor(&secp, descriptor.trim())?;
+    let hardened_xpub = descriptor.for_any_key(|key| {
+        matches!(key, DescriptorPublicKey::XPub(xkey) if xkey.wildcard == Wildcard::Hardened) && !keymap.contains_key(key)
+    });
+    if hardened_xpub {
+        bail!("Hardened derivation requires private keys: the descriptor derives hardened children from an extended public key");
+    }
+    Ok(())
+}
+
+/// Looks for an address among the addresses of a descriptor, up to derivation index `max_index`,
+/// e.g. to check that an imported descriptor derives a known address before syncing.
+/// Returns the address's derivation index, or `None` if it isn't found.
+pub fn descriptor_contains_address(descriptor: &str, network: Network, address: &str, max_index: u32) -> Result<Option<u32>> {
+    let secp = Secp256k1::new();
+    let (descriptor, _) = descriptor.trim().into_wallet_descriptor(&secp, network)?;
+    let script_pubkey = Address::from_str(address.trim())?.script_pubkey();
+
+    // NOTE: a descriptor without a wildcard has a single address
+    let max_index = if descriptor.has_wildcard() { max_index } else { 0 };
+    Ok((0..=max_index).find(|index| descriptor.at_derivation_index(*index).script_pubkey() == script_pubkey))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTOR: &str = "tr(xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/*)";
+
+    #[test]
+    fn test_validate_descriptor() {
+        let info = validate_descriptor(&format!("{}#8e7pq23w", DESCRIPTOR), Network::Bitcoin).unwrap();
+        assert!(info.valid);
+        assert_eq!(info.checksum, "8e7pq23w");
+        assert_eq!(info.script_type, "Tr");
+        assert!(info.is_ranged);
+        assert!(!info.has_private_keys);
+    }
+
+    #[test]
+    fn test_validate_descriptor_wrong_checksum() {
+        let err = validate_descriptor(&format!("{}#qqqqqqqq", DESCRIPTOR), Network::Bitcoin).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid descriptor checksum: expected #8e7pq23w, found #qqqqqqqq"
+        );
+    }
+
+    #[test]
+    fn test_validate_descriptor_missing_checksum() {
+        let info = validate_descriptor(DESCRIPTOR, Network::Bitcoin).unwrap();
+        assert_eq!(info.checksum, "8e7pq23w");
+    }
+
+    #[test]
+    fn test_descriptor_contains_address() {
+        // NOTE: BIP86 test vectors
+        let found = descriptor_contains_address(DESCRIPTOR, Network::Bitcoin, "bc1p4qhjn9zdvkux4e44uhx8tc55attvtyu358kutcqkudyccelu0was9fqzwh", 20).unwrap();
+        assert_eq!(found, Some(1));
+
+        // the first change address isn't derived by the external descriptor
+        let unrelated = "bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7";
+        assert_eq!(descriptor_contains_address(DESCRIPTOR, Network::Bitcoin, unrelated, 20).unwrap(), None);
+        assert!(descriptor_contains_address(DESCRIPTOR, Network::Bitcoin, "not an address", 20).is_err());
+    }
+
+    #[test]
+    fn test_check_hardened_wildcard() {
+        assert!(check_hardened_wildcard(DESCRIPTOR).is_ok());
+        for hardened in ["/0/*')", "/0/*h)"] {
+            let err = check_hardened_wildcard(&DESCRIPTOR.replace("/0/*)", hardened)).unwrap_err();
+            assert!(err.to_string().starts_with("Hardened derivation requires private keys"));
+        }
+
+        // the private key can derive hardened children
+        let xprv = "tr(xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu/86'/0'/0'/0/*')";
+        assert!(check_hardened_wildcard(xprv).is_ok());
+    }
+
+    #[test]
+    fn test_expand_multipath() {
+        let multipath = "tr(xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/<0;1>/*)";
+        let (external, internal) = expand_multipath(multipath).unwrap().unwrap();
+        assert_eq!(external, DESCRIPTOR);
+        assert_eq!(internal, DESCRIPTOR.replace("/0/*", "/1/*"));
+
+        assert_eq!(expand_multipath(DESCRIPTOR).unwrap(), None);
+        assert!(expand_multipath(&multipath.replace("<0;1>", "<0;1;2>")).is_err());
+    }
+
+    #[test]
+    fn test_normalize_slip132() {
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let (xpub, script_type) = normalize_slip132(zpub).unwrap();
+        assert_eq!(xpub, "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V");
+        assert_eq!(script_type, Some(ScriptType::NativeSegwit));
+
+        let ypub = "ypub6Ww3ibxVfGzLrAH1PNcjyAWenMTbbAosGNB6VvmSEgytSER9azLDWCxoJwW7Ke7icmizBMXrzBx9979FfaHxHcrArf3zbeJJJUZPf663zsP";
+        let (xpub, script_type) = normalize_slip132(ypub).unwrap();
+        assert_eq!(xpub, "xpub6C6nQwHaWbSrzs5tZ1q7m5R9cPK9eYpNMFesiXsYrgc1P8bvLLAet9JfHjYXKjToD8cBRswJXXbbFpXgwsswVPAZzKMa1jUp2kVkGVUaJa7");
+        assert_eq!(script_type, Some(ScriptType::NestedSegwit));
+
+        let xpub = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        assert_eq!(normalize_slip132(xpub).unwrap(), (xpub.to_string(), None));
+
+        // an xprv has unknown version bytes for an extended public key
+        assert!(normalize_slip132("xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu").is_err());
+        assert!(normalize_slip132("not a key").is_err());
+    }
+}
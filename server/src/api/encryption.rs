@@ -0,0 +1,81 @@
+use anyhow::{anyhow, bail, Result};
+use bdk::bitcoin::hashes::hex::{FromHex, ToHex};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use sha2::Sha256;
+
+// NOTE: OWASP's recommendation for PBKDF2-HMAC-SHA256
+/// PBKDF2-HMAC-SHA256 iterations to derive the key from the password.
+const PBKDF2_ROUNDS: u32 = 600_000;
+
+/// Length of the random salt of the key derivation.
+const SALT_LEN: usize = 16;
+
+/// Length of a ChaCha20-Poly1305 nonce.
+const NONCE_LEN: usize = 12;
+
+/// Derives a ChaCha20-Poly1305 key from a password and a salt.
+fn derive_key(password: &str, salt: &[u8]) -> Key {
+    let mut key = Key::default();
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+/// Encrypts a mnemonic with a password, e.g. for the frontend to store it at rest.
+/// The key is derived from the password with PBKDF2 and a random salt, and the mnemonic is encrypted with ChaCha20-Poly1305.
+/// Returns the hex of the salt, the nonce and the ciphertext.
+pub fn encrypt_mnemonic(mnemonic: &str, password: &str) -> String {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(password, &salt));
+    // NOTE: encryption only fails for plaintexts larger than the cipher's limit, far above a mnemonic
+    let ciphertext = cipher.encrypt(&nonce, mnemonic.as_bytes()).expect("mnemonic is small enough to encrypt");
+
+    [salt.as_slice(), nonce.as_slice(), ciphertext.as_slice()].concat().to_hex()
+}
+
+/// Decrypts a mnemonic encrypted with `encrypt_mnemonic`.
+/// Returns an error if the password is wrong or the ciphertext was tampered with.
+pub fn decrypt_mnemonic(ciphertext: &str, password: &str) -> Result<String> {
+    let bytes = Vec::<u8>::from_hex(ciphertext.trim())?;
+    if bytes.len() < SALT_LEN + NONCE_LEN {
+        bail!("Encrypted mnemonic is too short");
+    }
+    let (salt, rest) = bytes.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(password, salt));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Failed to decrypt the mnemonic, wrong password or corrupted data"))?;
+    Ok(String::from_utf8(plaintext)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MNEMONIC_12: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_encrypt_mnemonic_round_trip() {
+        let encrypted = encrypt_mnemonic(MNEMONIC_12, "correct horse battery staple");
+        assert!(!encrypted.contains("abandon"));
+        assert_eq!(decrypt_mnemonic(&encrypted, "correct horse battery staple").unwrap(), MNEMONIC_12);
+
+        // a random salt and nonce every time
+        assert_ne!(encrypt_mnemonic(MNEMONIC_12, "correct horse battery staple"), encrypted);
+    }
+
+    #[test]
+    fn test_decrypt_mnemonic_wrong_password() {
+        let encrypted = encrypt_mnemonic(MNEMONIC_12, "correct horse battery staple");
+        assert!(decrypt_mnemonic(&encrypted, "wrong password").is_err());
+        assert!(decrypt_mnemonic("00", "correct horse battery staple").is_err());
+        assert!(decrypt_mnemonic("not hex", "correct horse battery staple").is_err());
+    }
+}
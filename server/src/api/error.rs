@@ -0,0 +1,61 @@
+use bdk_esplora::esplora_client;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum WalletError {
+    #[error("Unsupported feature: {0}")]
+    UnsupportedFeature(String),
+    /// Esplora couldn't be reached, worth retrying.
+    #[error("Couldn't connect to Esplora: {0}")]
+    EsploraConnection(String),
+    /// Esplora was reached but returned an error or an invalid response.
+    #[error("Esplora returned an error: {0}")]
+    EsploraResponse(String),
+    /// A mnemonic's words are all known but its BIP39 checksum doesn't match, e.g. two words were swapped.
+    #[error("Invalid mnemonic checksum")]
+    InvalidMnemonicChecksum,
+    /// A mnemonic has a word that isn't in the BIP39 word list, e.g. a typo.
+    #[error("Unknown mnemonic word: {0}")]
+    UnknownMnemonicWord(String),
+}
+
+impl From<esplora_client::Error> for WalletError {
+    fn from(error: esplora_client::Error) -> Self {
+        match &error {
+            esplora_client::Error::Reqwest(e) if e.is_connect() || e.is_timeout() || e.is_request() => {
+                WalletError::EsploraConnection(error.to_string())
+            }
+            esplora_client::Error::Io(_) => WalletError::EsploraConnection(error.to_string()),
+            _ => WalletError::EsploraResponse(error.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use esplora_client::Builder;
+    use crate::api::mock::MockEsplora;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_esplora_connection_error() {
+        // NOTE: bind then drop a listener so nothing listens on the port
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let client = Builder::new(&base_url).build_async().unwrap();
+        let error = WalletError::from(client.get_height().await.unwrap_err());
+        assert!(matches!(error, WalletError::EsploraConnection(_)));
+    }
+
+    #[tokio::test]
+    async fn test_esplora_response_error() {
+        let esplora = MockEsplora::respond_with("400 Bad Request", "bad request").await;
+
+        let client = Builder::new(esplora.base_url()).build_async().unwrap();
+        let error = WalletError::from(client.get_height().await.unwrap_err());
+        assert!(matches!(error, WalletError::EsploraResponse(_)));
+    }
+}
@@ -1,5 +1,282 @@
-use anyhow::Result;
-use bdk_esplora::esplora_client::{AsyncClient, Builder};
+use crate::api::fees::confirmation_probability_from_histogram;
+use anyhow::{anyhow, Result};
+use bdk::{
+    bitcoin::{consensus::encode::deserialize, hashes::{hex::{FromHex, ToHex}, sha256, Hash}, Address, BlockHash, Script, Transaction, TxMerkleNode, Txid},
+    chain::{keychain::LocalUpdate, BlockId, ConfirmationTimeAnchor},
+};
+use bdk_esplora::esplora_client::{self, AsyncClient, Builder};
+use futures::future::try_join_all;
+use reqwest::{header::{HeaderMap, HeaderValue, AUTHORIZATION}, Client};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    future::Future,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+/// Default number of requests per second to the Esplora API.
+const DEFAULT_RATE_LIMIT: u32 = 10;
+
+/// Default number of parallel requests to an Esplora server, e.g. when scanning a keychain.
+pub const DEFAULT_CONCURRENCY: usize = 5;
+
+/// How long fee estimates are cached, they change at most every block.
+const FEE_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// A token-bucket rate limiter.
+/// Requests over the limit wait for a token rather than fail.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Tokens added per second.
+    rate: f64,
+    /// Maximum number of tokens, i.e. the burst size.
+    capacity: f64,
+    /// Available tokens and the last time they were refilled.
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter allowing `requests_per_second` requests per second.
+    pub fn new(requests_per_second: u32) -> Self {
+        let rate = f64::from(requests_per_second.max(1));
+        Self {
+            rate,
+            capacity: rate,
+            state: Mutex::new((rate, Instant::now())),
+        }
+    }
+
+    /// Waits until a request can be made.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+                let (tokens, last) = *state;
+                let tokens = (tokens + now.duration_since(last).as_secs_f64() * self.rate).min(self.capacity);
+                if tokens >= 1.0 {
+                    *state = (tokens - 1.0, now);
+                    return;
+                }
+                *state = (tokens, now);
+                Duration::from_secs_f64((1.0 - tokens) / self.rate)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// The rate limiter shared across all Esplora calls.
+/// The limit in requests per second can be configured with the `ESPLORA_RATE_LIMIT` environment variable.
+pub fn rate_limiter() -> &'static RateLimiter {
+    static RATE_LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+    RATE_LIMITER.get_or_init(|| {
+        let requests_per_second = std::env::var("ESPLORA_RATE_LIMIT")
+            .ok()
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(DEFAULT_RATE_LIMIT);
+        RateLimiter::new(requests_per_second)
+    })
+}
+
+/// Number of confirmed transactions per page of Esplora's script history.
+const ESPLORA_PAGE_SIZE: usize = 25;
+
+/// An Esplora client acquiring a token of a rate limiter before each HTTP request it makes.
+/// A scan makes at least one request per SPK, so limiting each request rather than each scan
+/// is what actually throttles a sync.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitedClient<'a> {
+    client: &'a AsyncClient,
+    limiter: &'a RateLimiter,
+}
+
+// NOTE: the errors are esplora_client's, as returned by its own client
+#[allow(clippy::result_large_err)]
+impl<'a> RateLimitedClient<'a> {
+    pub fn new(client: &'a AsyncClient, limiter: &'a RateLimiter) -> Self {
+        Self { client, limiter }
+    }
+
+    async fn get_block_hash(&self, height: u32) -> Result<BlockHash, esplora_client::Error> {
+        self.limiter.acquire().await;
+        self.client.get_block_hash(height).await
+    }
+
+    async fn get_tip(&self) -> Result<BlockId, esplora_client::Error> {
+        self.limiter.acquire().await;
+        let height = self.client.get_height().await?;
+        self.limiter.acquire().await;
+        let hash = self.client.get_tip_hash().await?;
+        Ok(BlockId { height, hash })
+    }
+
+    /// All the transactions of a script, following Esplora's pages of confirmed transactions.
+    async fn scripthash_txs(&self, script: &Script) -> Result<Vec<esplora_client::Tx>, esplora_client::Error> {
+        self.limiter.acquire().await;
+        let mut txs = self.client.scripthash_txs(script, None).await?;
+        if txs.iter().filter(|tx| tx.status.confirmed).count() >= ESPLORA_PAGE_SIZE {
+            loop {
+                self.limiter.acquire().await;
+                let page = self.client.scripthash_txs(script, txs.last().map(|tx| tx.txid)).await?;
+                let page_len = page.len();
+                txs.extend(page);
+                if page_len < ESPLORA_PAGE_SIZE {
+                    break;
+                }
+            }
+        }
+        Ok(txs)
+    }
+
+    /// The chain update connecting `local_chain` to Esplora's tip, and the tip.
+    /// Local blocks are fetched again from the highest one down until one is still in the best chain.
+    async fn chain_update<K>(
+        &self,
+        local_chain: &BTreeMap<u32, BlockHash>,
+    ) -> Result<(LocalUpdate<K, ConfirmationTimeAnchor>, BlockId), esplora_client::Error> {
+        loop {
+            let mut update = LocalUpdate::default();
+            for (&height, &local_hash) in local_chain.iter().rev() {
+                let hash = self.get_block_hash(height).await?;
+                let _ = update.chain.insert_block(BlockId { height, hash }).expect("heights are distinct");
+                if hash == local_hash {
+                    break;
+                }
+            }
+            // NOTE: the tip conflicts with the fetched blocks if a block was mined meanwhile, so retry
+            let tip = self.get_tip().await?;
+            if update.chain.insert_block(tip).is_ok() {
+                return Ok((update, tip));
+            }
+        }
+    }
+
+    /// Scans the keychains' SPKs for their transactions, like `EsploraAsyncExt::scan`
+    /// but with each HTTP request rate limited.
+    /// A keychain's scan stops after `stop_gap` SPKs without transactions, with up to
+    /// `parallel_requests` requests in flight at once.
+    pub async fn scan<K: Ord + Clone>(
+        &self,
+        local_chain: &BTreeMap<u32, BlockHash>,
+        keychain_spks: BTreeMap<K, Vec<(u32, Script)>>,
+        stop_gap: usize,
+        parallel_requests: usize,
+    ) -> Result<LocalUpdate<K, ConfirmationTimeAnchor>, esplora_client::Error> {
+        let (mut update, tip) = self.chain_update(local_chain).await?;
+
+        for (keychain, spks) in keychain_spks {
+            let mut last_active_index = None;
+            let mut empty_scripts = 0;
+            for chunk in spks.chunks(parallel_requests.max(1)) {
+                let histories = try_join_all(chunk.iter().map(|(index, script)| async move {
+                    Ok::<_, esplora_client::Error>((*index, self.scripthash_txs(script).await?))
+                }))
+                .await?;
+                for (index, txs) in histories {
+                    if txs.is_empty() {
+                        empty_scripts += 1;
+                    } else {
+                        last_active_index = Some(index);
+                        empty_scripts = 0;
+                    }
+                    for tx in txs {
+                        let _ = update.graph.insert_tx(tx.to_tx());
+                        if let (Some(confirmation_time), Some(confirmation_height)) = (tx.status.block_time, tx.status.block_height) {
+                            let anchor = ConfirmationTimeAnchor {
+                                anchor_block: tip,
+                                confirmation_height,
+                                confirmation_time,
+                            };
+                            let _ = update.graph.insert_anchor(tx.txid, anchor);
+                        }
+                    }
+                }
+                if empty_scripts >= stop_gap {
+                    break;
+                }
+            }
+            if let Some(last_active_index) = last_active_index {
+                update.keychain.insert(keychain, last_active_index);
+            }
+        }
+
+        // NOTE: if the tip was reorged during the scan, connect the chain to the new tip instead
+        if self.get_block_hash(tip.height).await? != tip.hash {
+            update.chain = self.chain_update::<K>(local_chain).await?.0.chain;
+        }
+        Ok(update)
+    }
+}
+
+/// Number of parallel requests allowed per Esplora host,
+/// e.g. more for a self-hosted server and fewer for a rate-limited public one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConcurrencyConfig {
+    default: usize,
+    per_host: HashMap<String, usize>,
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_CONCURRENCY)
+    }
+}
+
+impl ConcurrencyConfig {
+    /// Creates a config allowing `default` parallel requests to any host.
+    pub fn new(default: usize) -> Self {
+        Self {
+            default: default.max(1),
+            per_host: HashMap::new(),
+        }
+    }
+
+    /// Sets the parallel requests allowed to a host, e.g. "mempool.space".
+    pub fn with_host(mut self, host: &str, concurrency: usize) -> Self {
+        self.per_host.insert(host.to_string(), concurrency.max(1));
+        self
+    }
+
+    /// Parses a config such as "10,mempool.space=4,localhost=20",
+    /// where a bare number sets the default. Invalid entries are ignored.
+    pub fn parse(config: &str) -> Self {
+        config
+            .split(',')
+            .map(str::trim)
+            .fold(Self::default(), |config, entry| match entry.split_once('=') {
+                Some((host, concurrency)) => match concurrency.trim().parse() {
+                    Ok(concurrency) => config.with_host(host.trim(), concurrency),
+                    Err(_) => config,
+                },
+                None => match entry.parse() {
+                    Ok(default) => Self { default: usize::max(default, 1), ..config },
+                    Err(_) => config,
+                },
+            })
+    }
+
+    /// The parallel requests allowed to the host of an Esplora base URL.
+    pub fn for_url(&self, base_url: &str) -> usize {
+        reqwest::Url::parse(base_url)
+            .ok()
+            .and_then(|url| url.host_str().and_then(|host| self.per_host.get(host).copied()))
+            .unwrap_or(self.default)
+    }
+}
+
+/// The concurrency config shared across all Esplora batch calls.
+/// It can be configured with the `ESPLORA_CONCURRENCY` environment variable, e.g. "10,localhost=20".
+pub fn concurrency_config() -> &'static ConcurrencyConfig {
+    static CONCURRENCY_CONFIG: OnceLock<ConcurrencyConfig> = OnceLock::new();
+    CONCURRENCY_CONFIG.get_or_init(|| {
+        std::env::var("ESPLORA_CONCURRENCY")
+            .map(|config| ConcurrencyConfig::parse(&config))
+            .unwrap_or_default()
+    })
+}
 
 /// Creates a client from a url.
 pub fn create_client(network: &str) -> Result<AsyncClient> {
@@ -11,9 +288,298 @@ pub fn create_client(network: &str) -> Result<AsyncClient> {
     Ok(Builder::new(url).build_async()?)
 }
 
+/// Headers for an Esplora behind auth, with an `Authorization: Bearer` header if a token is given.
+/// The header is marked sensitive so the token is never logged.
+pub fn auth_headers(auth_token: Option<&str>) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    if let Some(token) = auth_token {
+        let mut value = HeaderValue::from_str(&format!("Bearer {}", token))?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+    Ok(headers)
+}
+
+/// Creates a client from a url, sending the given headers with every request.
+pub fn create_client_with_headers(base_url: &str, headers: HeaderMap) -> Result<AsyncClient> {
+    let client = Client::builder().default_headers(headers).build()?;
+    Ok(AsyncClient::from_client(base_url.to_string(), client))
+}
+
+/// Amounts in sats funded to and spent from an address, both confirmed and in the mempool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressBalance {
+    pub funded: u64,
+    pub spent: u64,
+    pub balance: u64,
+}
+
+/// Computes the balance from an Esplora address or scripthash stats response,
+/// i.e. funded minus spent, both confirmed and in the mempool.
+pub fn parse_stats_balance(stats: &Value) -> Result<u64> {
+    parse_address_stats(stats).map(|stats| stats.balance)
+}
+
+/// Parses the funded, spent and balance amounts from an Esplora address or scripthash stats response.
+pub fn parse_address_stats(stats: &Value) -> Result<AddressBalance> {
+    let sum = |stats_key: &str, sum_key: &str| {
+        stats
+            .get(stats_key)
+            .and_then(|stats| stats.get(sum_key))
+            .and_then(Value::as_u64)
+            .ok_or_else(|| anyhow!("Missing {}.{} in stats response", stats_key, sum_key))
+    };
+    let funded = sum("chain_stats", "funded_txo_sum")? + sum("mempool_stats", "funded_txo_sum")?;
+    let spent = sum("chain_stats", "spent_txo_sum")? + sum("mempool_stats", "spent_txo_sum")?;
+    let balance = funded
+        .checked_sub(spent)
+        .ok_or_else(|| anyhow!("Spent {} is more than funded {}", spent, funded))?;
+    Ok(AddressBalance { funded, spent, balance })
+}
+
+/// Returns the funded, spent and balance amounts of any address by querying Esplora's address endpoint,
+/// independently of a wallet's keychains.
+pub async fn get_address_balance(address: &Address, base_url: &str, client: &Client) -> Result<AddressBalance> {
+    let url = format!("{}/address/{}", base_url, address);
+
+    rate_limiter().acquire().await;
+    let stats: Value = client.get(url).send().await?.error_for_status()?.json().await?;
+    parse_address_stats(&stats)
+}
+
+/// Returns the Esplora scripthash of a script, i.e. the hex SHA256 of the script.
+pub fn scripthash(script: &[u8]) -> String {
+    sha256::Hash::hash(script).to_hex()
+}
+
+/// Returns the balance of a raw script in hex, without needing a wallet or descriptor,
+/// by querying Esplora's scripthash endpoint.
+pub async fn get_scripthash_balance(script_hex: &str, base_url: &str, client: &Client) -> Result<u64> {
+    let script = Vec::<u8>::from_hex(script_hex).map_err(|e| anyhow!("Invalid script hex: {}", e))?;
+    let url = format!("{}/scripthash/{}", base_url, scripthash(&script));
+
+    rate_limiter().acquire().await;
+    let stats: Value = client.get(url).send().await?.error_for_status()?.json().await?;
+    parse_stats_balance(&stats)
+}
+
+/// Mempool status of an unconfirmed transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MempoolStatus {
+    pub txid: String,
+    pub in_mempool: bool,
+    /// Neither in the mempool nor confirmed, e.g. evicted or replaced.
+    pub dropped: bool,
+    /// Fee rate in sat/vB.
+    pub fee_rate: Option<f32>,
+    pub vsize: Option<u64>,
+}
+
+/// Parses the mempool status from an Esplora transaction response.
+pub fn parse_mempool_status(txid: &Txid, tx: &Value) -> Result<MempoolStatus> {
+    let field = |key: &str| tx.get(key).and_then(Value::as_u64).ok_or_else(|| anyhow!("Missing {} in transaction response", key));
+    let confirmed = tx
+        .get("status")
+        .and_then(|status| status.get("confirmed"))
+        .and_then(Value::as_bool)
+        .ok_or_else(|| anyhow!("Missing status.confirmed in transaction response"))?;
+    let vsize = field("weight")?.div_ceil(4);
+    let fee = field("fee")?;
+    Ok(MempoolStatus {
+        txid: txid.to_string(),
+        in_mempool: !confirmed,
+        dropped: false,
+        fee_rate: Some(fee as f32 / vsize as f32),
+        vsize: Some(vsize),
+    })
+}
+
+/// Returns the mempool status of a transaction from Esplora.
+/// A transaction Esplora doesn't know about is flagged as dropped.
+pub async fn get_mempool_status(txid: &Txid, base_url: &str, client: &Client) -> Result<MempoolStatus> {
+    let url = format!("{}/tx/{}", base_url, txid);
+
+    rate_limiter().acquire().await;
+    let response = client.get(url).send().await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(MempoolStatus {
+            txid: txid.to_string(),
+            in_mempool: false,
+            dropped: true,
+            fee_rate: None,
+            vsize: None,
+        });
+    }
+    let tx: Value = response.error_for_status()?.json().await?;
+    parse_mempool_status(txid, &tx)
+}
+
+/// Parses the fee histogram of `(fee_rate, vsize)` pairs from an Esplora mempool response.
+pub fn parse_fee_histogram(mempool: &Value) -> Result<Vec<(f32, u64)>> {
+    let histogram = mempool
+        .get("fee_histogram")
+        .ok_or_else(|| anyhow!("Missing fee_histogram in mempool response"))?;
+    Ok(serde_json::from_value(histogram.clone())?)
+}
+
+/// Returns the mempool fee histogram from Esplora.
+pub async fn get_fee_histogram(base_url: &str, client: &Client) -> Result<Vec<(f32, u64)>> {
+    let url = format!("{}/mempool", base_url);
+
+    rate_limiter().acquire().await;
+    let mempool: Value = client.get(url).send().await?.error_for_status()?.json().await?;
+    parse_fee_histogram(&mempool)
+}
+
+/// Roughly estimates the probability, from 0 to 1, that a transaction paying `fee_rate` sat/vB confirms
+/// within `within_blocks` blocks, from the current mempool's fee histogram.
+pub async fn confirmation_probability(base_url: &str, client: &Client, fee_rate: f32, within_blocks: u32) -> Result<f32> {
+    let histogram = get_fee_histogram(base_url, client).await?;
+    Ok(confirmation_probability_from_histogram(&histogram, fee_rate, within_blocks))
+}
+
+/// The merkle inclusion proof of a confirmed transaction, to verify it against its block header.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub block_height: u32,
+    /// The sibling hashes from the transaction up to the merkle root.
+    pub merkle: Vec<TxMerkleNode>,
+    /// The transaction's position in the block.
+    pub pos: usize,
+}
+
+/// Returns the merkle inclusion proof of a confirmed transaction from Esplora.
+/// An unconfirmed or unknown transaction has no proof and returns an error.
+pub async fn get_merkle_proof(txid: &Txid, base_url: &str, client: &Client) -> Result<MerkleProof> {
+    let url = format!("{}/tx/{}/merkle-proof", base_url, txid);
+
+    rate_limiter().acquire().await;
+    let response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let reason = response.text().await.unwrap_or_default();
+        return Err(anyhow!("No merkle proof for {} ({}): {}", txid, status, reason.trim()));
+    }
+    Ok(response.json().await?)
+}
+
+/// Broadcasts a fully signed raw transaction in hex to Esplora and returns its txid.
+/// The hex is validated first, and Esplora's reject reason is returned as the error if the transaction is rejected.
+pub async fn broadcast_raw_transaction(tx_hex: &str, base_url: &str, client: &Client) -> Result<Txid> {
+    let bytes = Vec::<u8>::from_hex(tx_hex.trim()).map_err(|e| anyhow!("Invalid transaction hex: {}", e))?;
+    let tx: Transaction = deserialize(&bytes).map_err(|e| anyhow!("Invalid transaction: {}", e))?;
+
+    rate_limiter().acquire().await;
+    let response = client.post(format!("{}/tx", base_url)).body(bytes.to_hex()).send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let reason = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Transaction rejected ({}): {}", status, reason.trim()));
+    }
+    Ok(tx.txid())
+}
+
+/// A cache of block timestamps by height, so that repeated lookups don't hit Esplora.
+#[derive(Debug, Default)]
+pub struct BlockTimeCache {
+    times: Mutex<HashMap<u32, u64>>,
+}
+
+impl BlockTimeCache {
+    /// Returns the cached timestamp of the block at `height`, or fetches and caches it.
+    pub async fn get_or_fetch<F, Fut>(&self, height: u32, fetch: F) -> Result<u64>
+    where
+        F: FnOnce(u32) -> Fut,
+        Fut: Future<Output = Result<u64>>,
+    {
+        if let Some(time) = self.times.lock().unwrap().get(&height) {
+            return Ok(*time);
+        }
+        let time = fetch(height).await?;
+        self.times.lock().unwrap().insert(height, time);
+        Ok(time)
+    }
+
+    /// Returns the timestamp of the block at `height`, fetching it from Esplora if not cached.
+    pub async fn get_block_time(&self, height: u32, client: &AsyncClient) -> Result<u64> {
+        self.get_or_fetch(height, |height| async move {
+            rate_limiter().acquire().await;
+            let hash = client.get_block_hash(height).await?;
+            rate_limiter().acquire().await;
+            let header = client.get_header_by_hash(&hash).await?;
+            Ok(u64::from(header.time))
+        })
+        .await
+    }
+
+    /// Returns the timestamps of the blocks at `heights`,
+    /// fetching each distinct height that isn't cached only once.
+    pub async fn get_block_times(&self, heights: &[u32], client: &AsyncClient) -> Result<HashMap<u32, u64>> {
+        let mut times = HashMap::new();
+        for height in heights.iter().copied().collect::<BTreeSet<u32>>() {
+            times.insert(height, self.get_block_time(height, client).await?);
+        }
+        Ok(times)
+    }
+}
+
+/// The block timestamps cache shared across all Esplora calls.
+pub fn block_time_cache() -> &'static BlockTimeCache {
+    static BLOCK_TIME_CACHE: OnceLock<BlockTimeCache> = OnceLock::new();
+    BLOCK_TIME_CACHE.get_or_init(BlockTimeCache::default)
+}
+
+/// Fee estimates in sat/vB by confirmation target, as returned by Esplora.
+type FeeEstimates = HashMap<String, f64>;
+
+/// A short-lived cache of fee estimates by Esplora URL,
+/// so that repeated fee queries (fee slider, send preview) don't hit Esplora.
+#[derive(Debug)]
+pub struct FeeCache {
+    ttl: Duration,
+    estimates: Mutex<HashMap<String, (Instant, FeeEstimates)>>,
+}
+
+impl FeeCache {
+    /// Creates a fee cache whose entries expire after `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            estimates: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the cached fee estimates of the Esplora server at `url`, or fetches and caches them if expired.
+    pub async fn get_or_fetch<F, Fut>(&self, url: &str, fetch: F) -> Result<HashMap<String, f64>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<HashMap<String, f64>>>,
+    {
+        if let Some((fetched_at, estimates)) = self.estimates.lock().unwrap().get(url) {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(estimates.clone());
+            }
+        }
+        let estimates = fetch().await?;
+        self.estimates
+            .lock()
+            .unwrap()
+            .insert(url.to_string(), (Instant::now(), estimates.clone()));
+        Ok(estimates)
+    }
+}
+
+/// The fee estimates cache shared across all Esplora calls.
+pub fn fee_cache() -> &'static FeeCache {
+    static FEE_CACHE: OnceLock<FeeCache> = OnceLock::new();
+    FEE_CACHE.get_or_init(|| FeeCache::new(FEE_CACHE_TTL))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api::mock::MockEsplora;
+    use bdk::bitcoin::{consensus::encode::serialize, BlockHeader};
     use std::any::TypeId;
 
     fn is_derivationpath<T: ?Sized + 'static>(_s: &T) -> bool {
@@ -35,4 +601,285 @@ mod tests {
     fn test_create_client_panic() {
         create_client("foo").unwrap();
     }
+
+    #[tokio::test]
+    async fn test_rate_limiter_burst() {
+        let rate_limiter = RateLimiter::new(20);
+
+        // the first 20 requests are a burst, the next 10 wait for 0.5s worth of tokens
+        let start = Instant::now();
+        for _ in 0..30 {
+            rate_limiter.acquire().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(450));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_scan() {
+        // NOTE: mocked Esplora chain with no transactions
+        let tip_hash = "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5";
+        let esplora = MockEsplora::start(move |request| {
+            let path = request.split_whitespace().nth(1).unwrap_or_default();
+            match path {
+                "/blocks/tip/height" => ("200 OK", "100".to_string()),
+                "/blocks/tip/hash" | "/block-height/100" => ("200 OK", tip_hash.to_string()),
+                _ if path.starts_with("/scripthash/") => ("200 OK", "[]".to_string()),
+                _ => ("404 Not Found", String::new()),
+            }
+        })
+        .await;
+        let client = Builder::new(esplora.base_url()).build_async().unwrap();
+        let spks: Vec<(u32, Script)> = (0..20).map(|index| (index, Script::new_op_return(&[index as u8]))).collect();
+        let rate_limiter = RateLimiter::new(10);
+
+        let start = Instant::now();
+        let update = RateLimitedClient::new(&client, &rate_limiter)
+            .scan(&BTreeMap::new(), BTreeMap::from([((), spks)]), 20, 5)
+            .await
+            .unwrap();
+        assert!(update.keychain.is_empty());
+        // the tip's height and hash, one request per SPK and the tip check after the scan
+        assert_eq!(esplora.requests().len(), 23);
+        // each request took a token: the first 10 are a burst, the next 13 wait for 1.3s worth of tokens
+        assert!(start.elapsed() >= Duration::from_millis(1_250));
+    }
+
+    #[test]
+    fn test_parse_stats_balance() {
+        // NOTE: mocked scripthash stats response
+        let stats = serde_json::json!({
+            "scripthash": "50d2063d75bfa981d9487d789c76bde0b7147f803b0a88005664f15f4b07427d",
+            "chain_stats": { "funded_txo_count": 2, "funded_txo_sum": 150_000, "spent_txo_count": 1, "spent_txo_sum": 100_000, "tx_count": 3 },
+            "mempool_stats": { "funded_txo_count": 1, "funded_txo_sum": 20_000, "spent_txo_count": 0, "spent_txo_sum": 0, "tx_count": 1 }
+        });
+        assert_eq!(parse_stats_balance(&stats).unwrap(), 70_000);
+        assert!(parse_stats_balance(&serde_json::json!({})).is_err());
+    }
+
+    #[test]
+    fn test_parse_fee_histogram() {
+        // NOTE: mocked mempool response
+        let mempool = serde_json::json!({
+            "count": 3_000,
+            "vsize": 1_800_000,
+            "total_fee": 9_000_000,
+            "fee_histogram": [[53.1, 102_131], [20.0, 700_000], [1.0, 997_869]]
+        });
+        let histogram = parse_fee_histogram(&mempool).unwrap();
+        assert_eq!(histogram, vec![(53.1, 102_131), (20.0, 700_000), (1.0, 997_869)]);
+        assert!(confirmation_probability_from_histogram(&histogram, 60.0, 1) > 0.9);
+        assert!(parse_fee_histogram(&serde_json::json!({})).is_err());
+    }
+
+    #[test]
+    fn test_scripthash() {
+        // NOTE: the SHA256 of the script, not reversed
+        let script = Vec::<u8>::from_hex("76a914ebaa30e2a1f1a0bf2cdfc6cdde2a18ceda84bd1a88ac").unwrap();
+        assert_eq!(scripthash(&script), "50d2063d75bfa981d9487d789c76bde0b7147f803b0a88005664f15f4b07427d");
+    }
+
+    #[tokio::test]
+    async fn test_block_time_cache() {
+        let cache = BlockTimeCache::default();
+        let fetches = Mutex::new(0);
+        // NOTE: mocked Esplora block lookup
+        let fetch = |height: u32| {
+            *fetches.lock().unwrap() += 1;
+            async move {
+                match height {
+                    1_000 => Ok(1_231_006_505),
+                    _ => Err(anyhow!("Block not found")),
+                }
+            }
+        };
+
+        assert_eq!(cache.get_or_fetch(1_000, fetch).await.unwrap(), 1_231_006_505);
+        assert_eq!(cache.get_or_fetch(1_000, fetch).await.unwrap(), 1_231_006_505);
+        assert_eq!(*fetches.lock().unwrap(), 1);
+
+        assert!(cache.get_or_fetch(2_000, fetch).await.is_err());
+        assert_eq!(*fetches.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_block_time_cache_hit() {
+        // NOTE: a height no other test looks up, as the cache is shared
+        let height = 424_242;
+        let hash = BlockHash::hash(b"block 424242");
+        let header = BlockHeader {
+            version: 1,
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 1_231_006_505,
+            bits: 0x1d00ffff,
+            nonce: 0,
+        };
+        let esplora = MockEsplora::start(move |request| {
+            if request.starts_with(&format!("GET /block-height/{} ", height)) {
+                ("200 OK", hash.to_string())
+            } else if request.starts_with(&format!("GET /block/{}/header ", hash)) {
+                ("200 OK", serialize(&header).to_hex())
+            } else {
+                ("404 Not Found", String::new())
+            }
+        })
+        .await;
+        let client = Builder::new(esplora.base_url()).build_async().unwrap();
+
+        assert_eq!(block_time_cache().get_block_time(height, &client).await.unwrap(), 1_231_006_505);
+        assert_eq!(esplora.requests().len(), 2);
+
+        // the second lookup is a cache hit, even of the same height in a batch
+        let times = block_time_cache().get_block_times(&[height, height], &client).await.unwrap();
+        assert_eq!(times[&height], 1_231_006_505);
+        assert_eq!(esplora.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_create_client_with_headers() {
+        let headers = auth_headers(Some("secret")).unwrap();
+        assert!(headers[AUTHORIZATION].is_sensitive());
+        assert!(!format!("{:?}", headers).contains("secret"));
+        assert!(auth_headers(None).unwrap().is_empty());
+
+        let esplora = MockEsplora::respond_with("200 OK", "100").await;
+        let client = create_client_with_headers(esplora.base_url(), headers).unwrap();
+        assert_eq!(client.get_height().await.unwrap(), 100);
+        let request = esplora.requests()[0].to_lowercase();
+        assert!(request.starts_with("get /blocks/tip/height"));
+        assert!(request.contains("authorization: bearer secret"));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_raw_transaction() {
+        use bdk::bitcoin::consensus::encode::serialize_hex;
+
+        let tx = Transaction {
+            version: 2,
+            lock_time: bdk::bitcoin::PackedLockTime(0),
+            // NOTE: an input is needed, a transaction without inputs is ambiguous with the segwit marker
+            input: vec![bdk::bitcoin::TxIn::default()],
+            output: vec![bdk::bitcoin::TxOut { value: 1_000, script_pubkey: bdk::bitcoin::Script::new() }],
+        };
+        let tx_hex = serialize_hex(&tx);
+        let client = Client::new();
+
+        let esplora = MockEsplora::respond_with("200 OK", "").await;
+        assert_eq!(broadcast_raw_transaction(&tx_hex, esplora.base_url(), &client).await.unwrap(), tx.txid());
+        assert!(esplora.requests()[0].ends_with(&tx_hex));
+
+        let esplora = MockEsplora::respond_with("400 Bad Request", "min-relay-fee-not-met (code 66)").await;
+        let err = broadcast_raw_transaction(&tx_hex, esplora.base_url(), &client).await.unwrap_err();
+        assert!(err.to_string().contains("min-relay-fee-not-met"));
+
+        // invalid hex never reaches Esplora
+        assert!(broadcast_raw_transaction("zz", "http://127.0.0.1:1", &client).await.is_err());
+        assert!(broadcast_raw_transaction("00", "http://127.0.0.1:1", &client).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_address_balance() {
+        use std::str::FromStr;
+
+        // NOTE: mocked address stats response
+        let body = serde_json::json!({
+            "address": "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c",
+            "chain_stats": { "funded_txo_count": 2, "funded_txo_sum": 80_000, "spent_txo_count": 1, "spent_txo_sum": 50_000, "tx_count": 3 },
+            "mempool_stats": { "funded_txo_count": 0, "funded_txo_sum": 0, "spent_txo_count": 0, "spent_txo_sum": 0, "tx_count": 0 }
+        })
+        .to_string();
+        let esplora = MockEsplora::respond_with("200 OK", body).await;
+
+        let address = Address::from_str("tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c").unwrap();
+        let balance = get_address_balance(&address, esplora.base_url(), &Client::new()).await.unwrap();
+        assert_eq!(balance, AddressBalance { funded: 80_000, spent: 50_000, balance: 30_000 });
+        assert!(esplora.requests()[0].starts_with("GET /address/tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c "));
+    }
+
+    #[tokio::test]
+    async fn test_fee_cache() {
+        let cache = FeeCache::new(Duration::from_millis(100));
+        let fetches = Mutex::new(0);
+        // NOTE: mocked Esplora fee estimates
+        let fetch = || {
+            *fetches.lock().unwrap() += 1;
+            async move { Ok(HashMap::from([("1".to_string(), 20.0)])) }
+        };
+        let url = "https://mempool.space/api";
+
+        assert_eq!(cache.get_or_fetch(url, fetch).await.unwrap()["1"], 20.0);
+        assert_eq!(cache.get_or_fetch(url, fetch).await.unwrap()["1"], 20.0);
+        assert_eq!(*fetches.lock().unwrap(), 1);
+
+        // a different server isn't cached
+        cache.get_or_fetch("https://blockstream.info/api", fetch).await.unwrap();
+        assert_eq!(*fetches.lock().unwrap(), 2);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        cache.get_or_fetch(url, fetch).await.unwrap();
+        assert_eq!(*fetches.lock().unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_get_mempool_status() {
+        let txid = Txid::all_zeros();
+        let client = Client::new();
+
+        // NOTE: mocked Esplora transaction response
+        let body = serde_json::json!({
+            "txid": txid.to_string(),
+            "weight": 562,
+            "fee": 1_410,
+            "status": { "confirmed": false }
+        })
+        .to_string();
+        let esplora = MockEsplora::respond_with("200 OK", body).await;
+        let status = get_mempool_status(&txid, esplora.base_url(), &client).await.unwrap();
+        assert!(status.in_mempool);
+        assert!(!status.dropped);
+        assert_eq!(status.vsize, Some(141));
+        assert_eq!(status.fee_rate, Some(10.0));
+
+        let esplora = MockEsplora::respond_with("404 Not Found", "Transaction not found").await;
+        let status = get_mempool_status(&txid, esplora.base_url(), &client).await.unwrap();
+        assert!(!status.in_mempool);
+        assert!(status.dropped);
+    }
+
+    #[tokio::test]
+    async fn test_get_merkle_proof() {
+        use std::str::FromStr;
+
+        let txid = Txid::all_zeros();
+        let client = Client::new();
+
+        // NOTE: mocked Esplora merkle proof response
+        let sibling = "f9fc751cb7dc372406a9f8d738d5e6f8f63bab71986a39cf36ee70ee17036d07";
+        let body = serde_json::json!({
+            "block_height": 123_456,
+            "merkle": [sibling, txid.to_string()],
+            "pos": 2
+        })
+        .to_string();
+        let esplora = MockEsplora::respond_with("200 OK", body).await;
+        let proof = get_merkle_proof(&txid, esplora.base_url(), &client).await.unwrap();
+        assert_eq!(proof.block_height, 123_456);
+        assert_eq!(proof.merkle, vec![TxMerkleNode::from_str(sibling).unwrap(), TxMerkleNode::from_hash(txid.as_hash())]);
+        assert_eq!(proof.pos, 2);
+
+        let esplora = MockEsplora::respond_with("400 Bad Request", "Transaction not found or is unconfirmed").await;
+        let err = get_merkle_proof(&txid, esplora.base_url(), &client).await.unwrap_err();
+        assert!(err.to_string().contains("Transaction not found or is unconfirmed"));
+    }
+
+    #[test]
+    fn test_concurrency_config() {
+        let config = ConcurrencyConfig::parse("10, localhost=20, mempool.space=2, invalid=x");
+        assert_eq!(config.for_url("http://localhost:3002"), 20);
+        assert_eq!(config.for_url("https://mempool.space/api"), 2);
+        assert_eq!(config.for_url("https://blockstream.info/api"), 10);
+        assert_eq!(ConcurrencyConfig::default().for_url("https://mempool.space/api"), DEFAULT_CONCURRENCY);
+        // at least one request at a time
+        assert_eq!(ConcurrencyConfig::parse("0").for_url("https://mempool.space/api"), 1);
+    }
 }
@@ -0,0 +1,193 @@
+use crate::api::script_type::ScriptType;
+use serde::{Deserialize, Serialize};
+
+// NOTE: hardcoded to BIP86 taproot key-path spends
+/// Weight of the version, locktime, input/output counts, and segwit marker and flag.
+pub const TX_OVERHEAD_WEIGHT: u64 = 42;
+/// Weight of a P2TR key-path input.
+pub const P2TR_INPUT_WEIGHT: u64 = 230;
+/// Weight of a P2TR output.
+pub const P2TR_OUTPUT_WEIGHT: u64 = 172;
+
+// NOTE: segwit v0 single-key spends
+/// Weight of a P2WPKH input, with a 72-byte signature.
+pub const P2WPKH_INPUT_WEIGHT: u64 = 272;
+/// Weight of a P2WPKH output.
+pub const P2WPKH_OUTPUT_WEIGHT: u64 = 124;
+/// Weight of a P2SH-P2WPKH input, with a 72-byte signature.
+pub const P2SH_P2WPKH_INPUT_WEIGHT: u64 = 364;
+/// Weight of a P2SH output.
+pub const P2SH_OUTPUT_WEIGHT: u64 = 128;
+
+/// Converts a weight to virtual bytes, rounding up.
+pub fn weight_to_vbytes(weight: u64) -> u64 {
+    weight.div_ceil(4)
+}
+
+/// Weight of a single-key input of the given script type.
+pub fn input_weight(script_type: ScriptType) -> u64 {
+    match script_type {
+        ScriptType::Taproot => P2TR_INPUT_WEIGHT,
+        ScriptType::NativeSegwit => P2WPKH_INPUT_WEIGHT,
+        ScriptType::NestedSegwit => P2SH_P2WPKH_INPUT_WEIGHT,
+    }
+}
+
+/// Weight of an output of the given script type.
+pub fn output_weight(script_type: ScriptType) -> u64 {
+    match script_type {
+        ScriptType::Taproot => P2TR_OUTPUT_WEIGHT,
+        ScriptType::NativeSegwit => P2WPKH_OUTPUT_WEIGHT,
+        ScriptType::NestedSegwit => P2SH_OUTPUT_WEIGHT,
+    }
+}
+
+/// Estimates the vsize of a transaction spending `num_inputs` inputs to `num_outputs` outputs,
+/// all of the given script type, without selecting coins, e.g. for an instant fee preview.
+pub fn estimate_vsize(script_type: ScriptType, num_inputs: usize, num_outputs: usize) -> usize {
+    let weight = TX_OVERHEAD_WEIGHT
+        + num_inputs as u64 * input_weight(script_type)
+        + num_outputs as u64 * output_weight(script_type);
+    weight_to_vbytes(weight) as usize
+}
+
+/// Computes the fee rate in sat/vB to be included within `target_vsize_depth` vbytes from the top of the mempool,
+/// from a fee histogram of `(fee_rate, vsize)` pairs as reported by a node, e.g. one block's worth is 1,000,000 vB.
+/// If the whole mempool is shallower than the target, its lowest fee rate is enough, and an empty mempool gives 0.
+pub fn fee_rate_from_histogram(mut histogram: Vec<(f32, u64)>, target_vsize_depth: u64) -> f32 {
+    histogram.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut depth: u64 = 0;
+    for (fee_rate, vsize) in &histogram {
+        depth += vsize;
+        if depth >= target_vsize_depth {
+            return *fee_rate;
+        }
+    }
+    histogram.last().map_or(0.0, |(fee_rate, _)| *fee_rate)
+}
+
+/// Virtual size of a block's worth of transactions.
+pub const BLOCK_VSIZE: u64 = 1_000_000;
+
+/// Roughly estimates the probability, from 0 to 1, that a transaction paying `fee_rate` sat/vB confirms
+/// within `within_blocks` blocks, given a mempool fee histogram of `(fee_rate, vsize)` pairs.
+/// Only transactions paying a higher fee rate compete for the block space, so the probability falls
+/// as their vsize fills the blocks. New transactions arriving in the meantime are ignored.
+pub fn confirmation_probability_from_histogram(histogram: &[(f32, u64)], fee_rate: f32, within_blocks: u32) -> f32 {
+    let capacity = within_blocks as u64 * BLOCK_VSIZE;
+    if capacity == 0 {
+        return 0.0;
+    }
+    let competing: u64 = histogram
+        .iter()
+        .filter(|(competing_fee_rate, _)| *competing_fee_rate > fee_rate)
+        .map(|(_, vsize)| vsize)
+        .sum();
+    (1.0 - competing as f32 / capacity as f32).clamp(0.0, 1.0)
+}
+
+/// Estimated costs of consolidating UTXOs now versus spending them separately later.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsolidationEstimate {
+    /// Fee to consolidate all UTXOs now, plus spending the consolidated UTXO later.
+    pub consolidation_cost_sat: u64,
+    /// Marginal fee of spending all UTXOs as separate inputs later.
+    pub separate_cost_sat: u64,
+    /// Fee saved by consolidating now, negative if consolidating costs more.
+    pub savings_sat: i64,
+}
+
+/// Estimates the fee saved by consolidating `num_utxos` UTXOs now at `fee_rate`
+/// versus spending them as separate inputs later at `future_fee_rate`.
+/// Fee rates are in sat/vB.
+pub fn estimate_consolidation_savings(
+    num_utxos: usize,
+    fee_rate: f32,
+    future_fee_rate: f32,
+) -> ConsolidationEstimate {
+    let num_utxos = num_utxos as u64;
+    let input_vbytes = weight_to_vbytes(P2TR_INPUT_WEIGHT) as f32;
+
+    // consolidate into a single output now, and spend that single input later
+    let consolidation_vbytes = weight_to_vbytes(
+        TX_OVERHEAD_WEIGHT + num_utxos * P2TR_INPUT_WEIGHT + P2TR_OUTPUT_WEIGHT,
+    );
+    let consolidation_cost =
+        (consolidation_vbytes as f32 * fee_rate + input_vbytes * future_fee_rate).ceil() as u64;
+
+    // spend every UTXO as its own input later
+    let separate_cost = (num_utxos as f32 * input_vbytes * future_fee_rate).ceil() as u64;
+
+    ConsolidationEstimate {
+        consolidation_cost_sat: consolidation_cost,
+        separate_cost_sat: separate_cost,
+        savings_sat: separate_cost as i64 - consolidation_cost as i64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weight_to_vbytes() {
+        assert_eq!(weight_to_vbytes(P2TR_INPUT_WEIGHT), 58);
+        assert_eq!(weight_to_vbytes(P2TR_OUTPUT_WEIGHT), 43);
+    }
+
+    #[test]
+    fn test_estimate_consolidation_savings() {
+        // cheap now, expensive later
+        let estimate = estimate_consolidation_savings(5, 1.0, 50.0);
+        assert!(estimate.savings_sat > 0);
+        assert_eq!(
+            estimate.savings_sat,
+            estimate.separate_cost_sat as i64 - estimate.consolidation_cost_sat as i64
+        );
+
+        // a single UTXO can't be consolidated any further
+        let estimate = estimate_consolidation_savings(1, 1.0, 50.0);
+        assert!(estimate.savings_sat < 0);
+    }
+
+    #[test]
+    fn test_fee_rate_from_histogram() {
+        // NOTE: unsorted on purpose
+        let histogram = vec![(5.0, 400_000), (20.0, 300_000), (10.0, 500_000), (1.0, 2_000_000)];
+
+        // the top of the mempool
+        assert_eq!(fee_rate_from_histogram(histogram.clone(), 100_000), 20.0);
+        // about a block deep
+        assert_eq!(fee_rate_from_histogram(histogram.clone(), 1_000_000), 5.0);
+        // deeper than the mempool
+        assert_eq!(fee_rate_from_histogram(histogram, 10_000_000), 1.0);
+        assert_eq!(fee_rate_from_histogram(Vec::new(), 1_000_000), 0.0);
+    }
+
+    #[test]
+    fn test_confirmation_probability_from_histogram() {
+        let histogram = vec![(50.0, 200_000), (20.0, 600_000), (10.0, 1_500_000), (1.0, 3_000_000)];
+
+        // a high fee outbids almost the whole mempool
+        assert!(confirmation_probability_from_histogram(&histogram, 40.0, 1) >= 0.8);
+        // a low fee is stuck behind more than a block of higher fees
+        assert_eq!(confirmation_probability_from_histogram(&histogram, 5.0, 1), 0.0);
+        // but gets a chance over more blocks
+        assert!(confirmation_probability_from_histogram(&histogram, 5.0, 6) > 0.5);
+        assert_eq!(confirmation_probability_from_histogram(&[], 1.0, 1), 1.0);
+        assert_eq!(confirmation_probability_from_histogram(&histogram, 40.0, 0), 0.0);
+    }
+
+    #[test]
+    fn test_estimate_vsize() {
+        // NOTE: typical 1-input/2-output transactions are 141 vB for P2WPKH and 154 vB for P2TR
+        let p2wpkh = estimate_vsize(ScriptType::NativeSegwit, 1, 2);
+        assert!(p2wpkh.abs_diff(141) <= 2, "unexpected P2WPKH vsize {}", p2wpkh);
+        let p2tr = estimate_vsize(ScriptType::Taproot, 1, 2);
+        assert!(p2tr.abs_diff(154) <= 2, "unexpected P2TR vsize {}", p2tr);
+
+        // more inputs and outputs only grow the estimate
+        assert!(estimate_vsize(ScriptType::Taproot, 2, 2) > p2tr);
+        assert!(estimate_vsize(ScriptType::NestedSegwit, 1, 2) > p2wpkh);
+    }
+}
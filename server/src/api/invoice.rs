@@ -0,0 +1,184 @@
+use anyhow::{anyhow, Result};
+use app::format::sats_to_btc_decimal;
+use bdk::{bitcoin::Address, wallet::AddressIndex, Wallet};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, str::FromStr};
+
+/// A request for payment of `amount` sats to a dedicated receive address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invoice {
+    pub address: String,
+    pub amount: u64,
+    pub label: String,
+    /// BIP21 payment URI.
+    pub uri: String,
+}
+
+/// Payment status of an invoice, with the amount received so far in sats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InvoiceStatus {
+    Unpaid,
+    Partial(u64),
+    Paid(u64),
+}
+
+/// Percent-encodes a BIP21 query parameter value.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Returns a BIP21 URI requesting `amount` sats to `address`, with an optional label.
+pub fn bip21_uri(address: &str, amount: u64, label: Option<&str>) -> String {
+    let mut uri = format!("bitcoin:{}?amount={}", address, sats_to_btc_decimal(amount));
+    if let Some(label) = label.filter(|label| !label.is_empty()) {
+        uri.push_str("&label=");
+        uri.push_str(&percent_encode(label));
+    }
+    uri
+}
+
+/// The invoices created for a wallet, by address.
+/// It's loaded from and persisted to a `WalletStore` along with the wallet.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvoiceBook {
+    invoices: HashMap<String, Invoice>,
+}
+
+impl InvoiceBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an invoice for `amount` sats on a freshly revealed receive address, so that each invoice is paid to its own address.
+    pub fn create_invoice(&mut self, wallet: &mut Wallet, amount: u64, label: &str) -> Invoice {
+        let address = wallet.get_address(AddressIndex::New).address.to_string();
+        let invoice = Invoice {
+            uri: bip21_uri(&address, amount, Some(label)),
+            address: address.clone(),
+            amount,
+            label: label.to_string(),
+        };
+        self.invoices.insert(address, invoice.clone());
+        invoice
+    }
+
+    /// Returns the invoice for an address, if any.
+    pub fn get(&self, address: &str) -> Option<&Invoice> {
+        self.invoices.get(address)
+    }
+
+    /// Checks how much of the invoice for `address` was paid,
+    /// summing the outputs of the wallet's transactions, confirmed or not, that pay to it.
+    pub fn check_invoice(&self, wallet: &Wallet, address: &str) -> Result<InvoiceStatus> {
+        let invoice = self.get(address).ok_or_else(|| anyhow!("No invoice for address {}", address))?;
+        let script = Address::from_str(&invoice.address)?.script_pubkey();
+
+        let received: u64 = wallet
+            .transactions()
+            .flat_map(|tx| tx.node.tx.output.iter())
+            .filter(|output| output.script_pubkey == script)
+            .map(|output| output.value)
+            .sum();
+        Ok(match received {
+            0 => InvoiceStatus::Unpaid,
+            received if received < invoice.amount => InvoiceStatus::Partial(received),
+            received => InvoiceStatus::Paid(received),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        script_type::ScriptType,
+        store::{MemoryStore, WalletStore},
+        wallet::create_wallet_with_script_type,
+    };
+    use app::format::SATS_PER_BTC;
+    use bdk::{
+        bitcoin::{hashes::Hash, BlockHash, PackedLockTime, Transaction, TxOut},
+        chain::{BlockId, ConfirmationTime},
+    };
+
+    const MNEMONIC_24: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+    const DERIVATION_PATH_EXTERNAL: &str = "m/86'/0'/0'/0";
+    const DERIVATION_PATH_INTERNAL: &str = "m/86'/0'/0'/1";
+
+    /// Inserts a fake confirmed transaction paying `value` to `address`.
+    fn pay(wallet: &mut Wallet, address: &str, value: u64) {
+        let tx = Transaction {
+            version: 1,
+            lock_time: PackedLockTime(0),
+            input: vec![],
+            output: vec![TxOut {
+                value,
+                script_pubkey: Address::from_str(address).unwrap().script_pubkey(),
+            }],
+        };
+        wallet
+            .insert_checkpoint(BlockId {
+                height: 1_000,
+                hash: BlockHash::all_zeros(),
+            })
+            .unwrap();
+        wallet
+            .insert_tx(tx, ConfirmationTime::Confirmed { height: 1_000, time: 100 })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_bip21_uri() {
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        assert_eq!(bip21_uri(address, 50_000, None), format!("bitcoin:{}?amount=0.0005", address));
+        assert_eq!(bip21_uri(address, SATS_PER_BTC, Some("")), format!("bitcoin:{}?amount=1", address));
+        assert_eq!(
+            bip21_uri(address, 123_456_789, Some("Coffee & cake")),
+            format!("bitcoin:{}?amount=1.23456789&label=Coffee%20%26%20cake", address)
+        );
+    }
+
+    #[test]
+    fn test_invoice_paid() {
+        let mut wallet = create_wallet_with_script_type(MNEMONIC_24, "regtest", ScriptType::Taproot, DERIVATION_PATH_EXTERNAL, DERIVATION_PATH_INTERNAL).unwrap();
+        let mut invoices = InvoiceBook::new();
+
+        let invoice = invoices.create_invoice(&mut wallet, 50_000, "coffee");
+        assert!(invoice.uri.starts_with(&format!("bitcoin:{}?amount=0.0005", invoice.address)));
+        assert!(invoice.uri.ends_with("&label=coffee"));
+        // each invoice gets its own address
+        assert_ne!(invoices.create_invoice(&mut wallet, 1_000, "tip").address, invoice.address);
+
+        assert_eq!(invoices.check_invoice(&wallet, &invoice.address).unwrap(), InvoiceStatus::Unpaid);
+        pay(&mut wallet, &invoice.address, 20_000);
+        assert_eq!(invoices.check_invoice(&wallet, &invoice.address).unwrap(), InvoiceStatus::Partial(20_000));
+        pay(&mut wallet, &invoice.address, 30_000);
+        assert_eq!(invoices.check_invoice(&wallet, &invoice.address).unwrap(), InvoiceStatus::Paid(50_000));
+
+        assert!(invoices.check_invoice(&wallet, "bcrt1qunknown").is_err());
+    }
+
+    #[test]
+    fn test_invoices_persisted() {
+        let store = MemoryStore::new();
+        let mut wallet = create_wallet_with_script_type(MNEMONIC_24, "regtest", ScriptType::Taproot, DERIVATION_PATH_EXTERNAL, DERIVATION_PATH_INTERNAL).unwrap();
+        let descriptor = wallet.get_descriptor_for_keychain(bdk::KeychainKind::External).to_string();
+        assert!(store.load_invoices(&descriptor).is_none());
+
+        let mut invoices = store.load_invoices(&descriptor).unwrap_or_default();
+        let invoice = invoices.create_invoice(&mut wallet, 50_000, "coffee");
+        store.persist_invoices(&descriptor, &invoices).unwrap();
+
+        // a later request loads the invoice back to check it
+        let invoices = store.load_invoices(&descriptor).unwrap();
+        assert_eq!(invoices.get(&invoice.address), Some(&invoice));
+        pay(&mut wallet, &invoice.address, 50_000);
+        assert_eq!(invoices.check_invoice(&wallet, &invoice.address).unwrap(), InvoiceStatus::Paid(50_000));
+    }
+}
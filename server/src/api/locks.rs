@@ -0,0 +1,163 @@
+use bdk::bitcoin::OutPoint;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+/// How long a UTXO stays reserved if the transaction spending it is never broadcast.
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// A registry of UTXOs reserved by built but not yet broadcast transactions.
+/// Locks are released once the transaction's broadcast is attempted, or expire after a timeout.
+#[derive(Debug)]
+pub struct UtxoLocks {
+    timeout: Duration,
+    /// Locked UTXOs and when they were locked.
+    locked: Mutex<HashMap<OutPoint, Instant>>,
+}
+
+impl UtxoLocks {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            locked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reserves UTXOs if none of them is reserved already, checking and reserving them at once,
+    /// so that concurrent transactions can't reserve the same UTXO.
+    /// The UTXOs are released when the returned guard is dropped, unless it is kept.
+    pub fn try_lock(&self, outpoints: impl IntoIterator<Item = OutPoint>) -> Option<UtxoLockGuard<'_>> {
+        let outpoints: Vec<OutPoint> = outpoints.into_iter().collect();
+        let now = Instant::now();
+        let mut locked = self.locked.lock().unwrap();
+        locked.retain(|_, locked_at| locked_at.elapsed() < self.timeout);
+        if outpoints.iter().any(|outpoint| locked.contains_key(outpoint)) {
+            return None;
+        }
+        locked.extend(outpoints.iter().map(|outpoint| (*outpoint, now)));
+        Some(UtxoLockGuard {
+            locks: self,
+            outpoints,
+            locked_at: now,
+        })
+    }
+
+    /// Releases UTXOs, e.g. once the transaction spending them is broadcast.
+    pub fn release(&self, outpoints: impl IntoIterator<Item = OutPoint>) {
+        let mut locked = self.locked.lock().unwrap();
+        for outpoint in outpoints {
+            locked.remove(&outpoint);
+        }
+    }
+
+    /// The currently reserved UTXOs, dropping the expired ones.
+    pub fn locked(&self) -> Vec<OutPoint> {
+        let mut locked = self.locked.lock().unwrap();
+        locked.retain(|_, locked_at| locked_at.elapsed() < self.timeout);
+        locked.keys().copied().collect()
+    }
+}
+
+/// UTXOs reserved by `UtxoLocks::try_lock`, released when dropped,
+/// e.g. when building or signing the transaction spending them fails.
+#[must_use]
+#[derive(Debug)]
+pub struct UtxoLockGuard<'a> {
+    locks: &'a UtxoLocks,
+    outpoints: Vec<OutPoint>,
+    locked_at: Instant,
+}
+
+impl UtxoLockGuard<'_> {
+    /// The reserved UTXOs.
+    pub fn outpoints(&self) -> &[OutPoint] {
+        &self.outpoints
+    }
+
+    /// Keeps the UTXOs reserved after the guard, until released on broadcast or until the lock times out.
+    pub fn keep(mut self) {
+        self.outpoints.clear();
+    }
+}
+
+impl Drop for UtxoLockGuard<'_> {
+    fn drop(&mut self) {
+        let mut locked = self.locks.locked.lock().unwrap();
+        for outpoint in &self.outpoints {
+            // NOTE: an expired lock may have been taken over since
+            if locked.get(outpoint) == Some(&self.locked_at) {
+                locked.remove(outpoint);
+            }
+        }
+    }
+}
+
+impl Default for UtxoLocks {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOCK_TIMEOUT)
+    }
+}
+
+/// The UTXO locks shared across all requests.
+pub fn utxo_locks() -> &'static UtxoLocks {
+    static UTXO_LOCKS: OnceLock<UtxoLocks> = OnceLock::new();
+    UTXO_LOCKS.get_or_init(UtxoLocks::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk::bitcoin::{hashes::Hash, Txid};
+
+    #[test]
+    fn test_utxo_locks_expire() {
+        let outpoint = OutPoint::new(Txid::all_zeros(), 0);
+
+        let locks = UtxoLocks::new(Duration::from_secs(60));
+        locks.try_lock([outpoint]).unwrap().keep();
+        assert_eq!(locks.locked(), vec![outpoint]);
+        locks.release([outpoint]);
+        assert!(locks.locked().is_empty());
+
+        let locks = UtxoLocks::new(Duration::ZERO);
+        locks.try_lock([outpoint]).unwrap().keep();
+        assert!(locks.locked().is_empty());
+    }
+
+    #[test]
+    fn test_utxo_lock_guard() {
+        let first = OutPoint::new(Txid::all_zeros(), 0);
+        let second = OutPoint::new(Txid::all_zeros(), 1);
+        let locks = UtxoLocks::default();
+
+        // a reserved UTXO can't be reserved again, even along with a free one
+        let guard = locks.try_lock([first]).unwrap();
+        assert_eq!(guard.outpoints(), &[first]);
+        assert!(locks.try_lock([second, first]).is_none());
+        assert!(locks.locked().iter().all(|outpoint| *outpoint != second));
+
+        // dropping the guard releases it
+        drop(guard);
+        assert!(locks.locked().is_empty());
+        let guard = locks.try_lock([first, second]).unwrap();
+        guard.keep();
+        assert_eq!(locks.locked().len(), 2);
+    }
+
+    #[test]
+    fn test_utxo_locks_concurrent() {
+        let outpoint = OutPoint::new(Txid::all_zeros(), 0);
+        let locks = UtxoLocks::default();
+
+        // only one of the concurrent reservations of the same UTXO succeeds
+        let reserved = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| locks.try_lock([outpoint]).map(UtxoLockGuard::keep).is_some()))
+                .collect();
+            threads.into_iter().map(|thread| thread.join().unwrap()).filter(|reserved| *reserved).count()
+        });
+        assert_eq!(reserved, 1);
+    }
+}
@@ -0,0 +1,102 @@
+//! A local Esplora stand-in for tests, answering HTTP requests with canned responses
+//! and recording the requests it received.
+
+use std::sync::{Arc, Mutex};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+/// A canned response: the HTTP status, e.g. "200 OK", and the body.
+pub type Response = (&'static str, String);
+
+type Responder = Arc<dyn Fn(&str) -> Response + Send + Sync>;
+
+/// A local HTTP server standing in for Esplora, stopped when dropped.
+pub struct MockEsplora {
+    base_url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+    server: JoinHandle<()>,
+}
+
+impl MockEsplora {
+    /// Starts a stand-in answering every request with `respond(request)`,
+    /// where `request` is the raw HTTP request.
+    pub async fn start(respond: impl Fn(&str) -> Response + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let respond: Responder = Arc::new(respond);
+
+        let recorded = requests.clone();
+        let server = tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(serve(socket, respond.clone(), recorded.clone()));
+            }
+        });
+        Self { base_url, requests, server }
+    }
+
+    /// Starts a stand-in answering every request with the same status and body.
+    pub async fn respond_with(status: &'static str, body: impl Into<String>) -> Self {
+        let body = body.into();
+        Self::start(move |_| (status, body.clone())).await
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// The raw HTTP requests received so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockEsplora {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// Answers the single request of a connection, closing it afterwards.
+async fn serve(mut socket: TcpStream, respond: Responder, recorded: Arc<Mutex<Vec<String>>>) {
+    let mut request = Vec::new();
+    let mut buf = [0; 4096];
+    while !is_complete(&request) {
+        let n = socket.read(&mut buf).await.unwrap();
+        if n == 0 {
+            return;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let request = String::from_utf8_lossy(&request).to_string();
+    let (status, body) = respond(&request);
+    recorded.lock().unwrap().push(request);
+    let response = format!(
+        "HTTP/1.1 {}\r\nconnection: close\r\ncontent-length: {}\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    socket.write_all(response.as_bytes()).await.unwrap();
+    let _ = socket.shutdown().await;
+}
+
+/// Whether the headers and the body announced by them were fully read.
+fn is_complete(request: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(request);
+    let Some(headers_end) = text.find("\r\n\r\n") else {
+        return false;
+    };
+    let content_length = text[..headers_end]
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    request.len() >= headers_end + 4 + content_length
+}
@@ -1,2 +1,14 @@
+pub mod descriptor;
+pub mod encryption;
+pub mod error;
 pub mod esplora;
+pub mod fees;
+pub mod invoice;
+pub mod locks;
+#[cfg(test)]
+pub(crate) mod mock;
+pub mod price;
+pub mod psbt;
+pub mod script_type;
+pub mod store;
 pub mod wallet;
@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use app::format::SATS_PER_BTC;
+
+// NOTE: defaults to mempool.space, returns `{ "time": ..., "USD": ..., "EUR": ... }`
+const DEFAULT_PRICE_SOURCE_URL: &str = "https://mempool.space/api/v1/prices";
+
+/// How long a fetched price is cached for.
+const PRICE_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Cached prices keyed by price source URL and currency.
+type PriceCache = Mutex<HashMap<(String, String), (f64, Instant)>>;
+
+fn price_cache() -> &'static PriceCache {
+    static PRICE_CACHE: OnceLock<PriceCache> = OnceLock::new();
+    PRICE_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The price source URL.
+/// It can be configured with the `PRICE_SOURCE_URL` environment variable.
+pub fn price_source_url() -> String {
+    std::env::var("PRICE_SOURCE_URL").unwrap_or_else(|_| DEFAULT_PRICE_SOURCE_URL.to_string())
+}
+
+/// Parses the price of one bitcoin in a currency from a price source response.
+pub fn parse_price(response: &Value, currency: &str) -> Result<f64> {
+    response
+        .get(currency.to_uppercase())
+        .and_then(Value::as_f64)
+        .ok_or_else(|| anyhow!("Price not found for currency: {}", currency))
+}
+
+/// Converts an amount in satoshis to a fiat value given the price of one bitcoin.
+pub fn sats_to_fiat(sats: u64, price: f64) -> f64 {
+    sats as f64 / SATS_PER_BTC as f64 * price
+}
+
+/// Fetches the price of one bitcoin in a currency, cached for a short TTL.
+pub async fn get_price(currency: &str, client: &Client) -> Result<f64> {
+    let url = price_source_url();
+    let key = (url.clone(), currency.to_uppercase());
+    if let Some((price, fetched_at)) = price_cache().lock().unwrap().get(&key) {
+        if fetched_at.elapsed() < PRICE_CACHE_TTL {
+            return Ok(*price);
+        }
+    }
+
+    let response: Value = client.get(&url).send().await?.error_for_status()?.json().await?;
+    let price = parse_price(&response, currency)?;
+    price_cache().lock().unwrap().insert(key, (price, Instant::now()));
+    Ok(price)
+}
+
+/// Returns the fiat value of an amount in satoshis.
+pub async fn get_fiat_value(sats: u64, currency: &str, client: &Client) -> Result<f64> {
+    let price = get_price(currency, client).await?;
+    Ok(sats_to_fiat(sats, price))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_price() {
+        // NOTE: mocked price source response
+        let response = serde_json::json!({ "time": 1_700_000_000, "USD": 30_000.0, "EUR": 28_000 });
+        assert_eq!(parse_price(&response, "usd").unwrap(), 30_000.0);
+        assert_eq!(parse_price(&response, "EUR").unwrap(), 28_000.0);
+        assert!(parse_price(&response, "JPY").is_err());
+    }
+
+    #[test]
+    fn test_sats_to_fiat() {
+        assert_eq!(sats_to_fiat(SATS_PER_BTC, 30_000.0), 30_000.0);
+        assert_eq!(sats_to_fiat(50_000, 30_000.0), 15.0);
+        assert_eq!(sats_to_fiat(0, 30_000.0), 0.0);
+    }
+}
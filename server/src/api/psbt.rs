@@ -0,0 +1,479 @@
+use anyhow::{bail, Result};
+use bdk::{
+    bitcoin::{
+        consensus::encode::{deserialize, serialize_hex},
+        hashes::hex::FromHex,
+        psbt::PartiallySignedTransaction,
+        secp256k1::Secp256k1,
+        Address, Network, Script,
+    },
+    miniscript::psbt::PsbtExt,
+    KeychainKind, Wallet,
+};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Serialization format of a PSBT.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PsbtFormat {
+    #[default]
+    Base64,
+    Hex,
+}
+
+impl PsbtFormat {
+    /// Parses a PSBT format, "base64" or "hex".
+    pub fn parse(format: &str) -> Result<Self> {
+        match format {
+            "base64" => Ok(PsbtFormat::Base64),
+            "hex" => Ok(PsbtFormat::Hex),
+            &_ => bail!("Invalid PSBT format: {}", format),
+        }
+    }
+}
+
+/// Serializes a PSBT in the given format.
+pub fn serialize_psbt(psbt: &PartiallySignedTransaction, format: PsbtFormat) -> String {
+    match format {
+        PsbtFormat::Base64 => psbt.to_string(),
+        PsbtFormat::Hex => serialize_hex(psbt),
+    }
+}
+
+/// Parses a PSBT in either base64 or hex.
+pub fn parse_psbt(psbt: &str) -> Result<PartiallySignedTransaction> {
+    let psbt = psbt.trim();
+    // NOTE: hex PSBTs start with the magic bytes "psbt\xff", base64 ones with "cHNid"
+    if psbt.starts_with("70736274ff") {
+        Ok(deserialize(&Vec::<u8>::from_hex(psbt)?)?)
+    } else {
+        Ok(PartiallySignedTransaction::from_str(psbt)?)
+    }
+}
+
+/// A combined PSBT, and whether it can now be finalized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CombinedPsbt {
+    pub psbt: String,
+    pub finalizable: bool,
+}
+
+/// Whether all of the PSBT's inputs have enough signatures to be finalized.
+pub fn is_finalizable(psbt: &PartiallySignedTransaction) -> bool {
+    let secp = Secp256k1::verification_only();
+    psbt.clone().finalize(&secp).is_ok()
+}
+
+/// Combines base64 or hex PSBTs of the same unsigned transaction, e.g. from different multisig signers.
+/// The combined PSBT is serialized in the given format.
+/// Returns an error if the PSBTs describe different transactions.
+pub fn combine_psbts(psbts: Vec<String>, format: PsbtFormat) -> Result<CombinedPsbt> {
+    let mut psbts = psbts.iter().map(|psbt| parse_psbt(psbt));
+    let mut combined = match psbts.next() {
+        Some(psbt) => psbt?,
+        None => bail!("No PSBTs to combine"),
+    };
+    for psbt in psbts {
+        let psbt = psbt?;
+        if psbt.unsigned_tx.txid() != combined.unsigned_tx.txid() {
+            bail!(
+                "PSBTs describe different transactions: {} and {}",
+                combined.unsigned_tx.txid(),
+                psbt.unsigned_tx.txid()
+            );
+        }
+        combined.combine(psbt)?;
+    }
+
+    Ok(CombinedPsbt {
+        finalizable: is_finalizable(&combined),
+        psbt: serialize_psbt(&combined, format),
+    })
+}
+
+/// An input of a PSBT, for display.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PsbtInputSummary {
+    pub outpoint: String,
+    /// `None` if the PSBT doesn't include the previous output.
+    pub amount: Option<u64>,
+    pub address: Option<String>,
+    pub is_mine: bool,
+}
+
+/// An output of a PSBT, for display.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PsbtOutputSummary {
+    pub amount: u64,
+    pub address: Option<String>,
+    /// Guessed from the output being owned by the wallet.
+    pub is_change: bool,
+}
+
+/// A human-readable summary of what a PSBT spends and pays.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PsbtSummary {
+    pub txid: String,
+    pub inputs: Vec<PsbtInputSummary>,
+    pub outputs: Vec<PsbtOutputSummary>,
+    /// `None` if any input amount is unknown.
+    pub fee: Option<u64>,
+    pub has_own_inputs: bool,
+}
+
+/// Returns a readable JSON dump of a PSBT's global, input and output maps, for debugging e.g. multisig signing,
+/// with counts of signatures and key derivations and the presence of UTXOs and final scripts.
+pub fn psbt_debug_json(psbt: &str) -> Result<String> {
+    let psbt = parse_psbt(psbt)?;
+
+    let inputs: Vec<serde_json::Value> = psbt
+        .inputs
+        .iter()
+        .map(|input| {
+            serde_json::json!({
+                "partial_sigs": input.partial_sigs.len(),
+                "tap_key_sig": input.tap_key_sig.is_some(),
+                "tap_script_sigs": input.tap_script_sigs.len(),
+                "signatures": input.partial_sigs.len() + usize::from(input.tap_key_sig.is_some()) + input.tap_script_sigs.len(),
+                "bip32_derivations": input.bip32_derivation.len(),
+                "tap_key_origins": input.tap_key_origins.len(),
+                "witness_utxo": input.witness_utxo.is_some(),
+                "non_witness_utxo": input.non_witness_utxo.is_some(),
+                "sighash_type": input.sighash_type.map(|sighash_type| sighash_type.to_string()),
+                "final_script_sig": input.final_script_sig.is_some(),
+                "final_script_witness": input.final_script_witness.is_some(),
+                "finalized": input.final_script_sig.is_some() || input.final_script_witness.is_some(),
+            })
+        })
+        .collect();
+    let outputs: Vec<serde_json::Value> = psbt
+        .outputs
+        .iter()
+        .map(|output| {
+            serde_json::json!({
+                "bip32_derivations": output.bip32_derivation.len(),
+                "tap_key_origins": output.tap_key_origins.len(),
+                "tap_internal_key": output.tap_internal_key.is_some(),
+                "redeem_script": output.redeem_script.is_some(),
+                "witness_script": output.witness_script.is_some(),
+            })
+        })
+        .collect();
+
+    let debug = serde_json::json!({
+        "global": {
+            "txid": psbt.unsigned_tx.txid().to_string(),
+            "version": psbt.version,
+            "xpubs": psbt.xpub.len(),
+            "proprietary": psbt.proprietary.len(),
+            "unknown": psbt.unknown.len(),
+        },
+        "inputs": inputs,
+        "outputs": outputs,
+    });
+    Ok(serde_json::to_string_pretty(&debug)?)
+}
+
+fn script_address(script: &Script, network: Network) -> Option<String> {
+    Address::from_script(script, network).ok().map(|address| address.to_string())
+}
+
+/// Returns the index of the PSBT's change output, i.e. the output paying to the wallet's internal keychain.
+/// Returns `None` if there's no change, e.g. when draining the wallet.
+pub fn identify_change_output(wallet: &Wallet, psbt: &PartiallySignedTransaction) -> Option<usize> {
+    psbt.unsigned_tx.output.iter().position(|txout| {
+        wallet.is_mine(&txout.script_pubkey)
+            && matches!(wallet.spk_index().index_of_spk(&txout.script_pubkey), Some((KeychainKind::Internal, _)))
+    })
+}
+
+/// A privacy issue of a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrivacyWarning {
+    /// An output pays an address with history, linking the transactions.
+    AddressReuse,
+    /// A recipient amount is a round number, which tells it apart from the change.
+    RoundAmount,
+    /// The change is larger than any recipient amount, a common change detection heuristic.
+    LargerChange,
+    /// The inputs have different script types, which fingerprints the wallets involved.
+    MixedInputTypes,
+}
+
+/// A heuristic privacy report of a transaction: a score out of 100, lowered by each warning.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivacyReport {
+    pub score: u8,
+    pub warnings: Vec<PrivacyWarning>,
+}
+
+/// Amounts that are a multiple of this many sats (0.001 BTC) are considered round.
+const ROUND_AMOUNT_SATS: u64 = 100_000;
+
+/// Score lost per privacy warning.
+const PRIVACY_WARNING_PENALTY: u8 = 25;
+
+/// The script type of an input's spent output, to detect mixed input types.
+fn input_script_type(script: &Script) -> &'static str {
+    if script.is_v1_p2tr() {
+        "p2tr"
+    } else if script.is_v0_p2wpkh() {
+        "p2wpkh"
+    } else if script.is_v0_p2wsh() {
+        "p2wsh"
+    } else if script.is_p2sh() {
+        "p2sh"
+    } else if script.is_p2pkh() {
+        "p2pkh"
+    } else {
+        "other"
+    }
+}
+
+/// Computes a heuristic privacy report of a proposed transaction, flagging address reuse,
+/// round recipient amounts, change larger than the recipients and mixed input script types.
+pub fn transaction_privacy_score(wallet: &Wallet, psbt: &PartiallySignedTransaction) -> PrivacyReport {
+    let tx = &psbt.unsigned_tx;
+    let change_index = identify_change_output(wallet, psbt);
+    let recipients: Vec<u64> = tx
+        .output
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| Some(*index) != change_index)
+        .map(|(_, output)| output.value)
+        .collect();
+    let input_scripts: Vec<&Script> = psbt
+        .inputs
+        .iter()
+        .filter_map(|input| input.witness_utxo.as_ref().map(|txout| &txout.script_pubkey))
+        .collect();
+
+    let mut warnings = Vec::new();
+    let has_history = |script: &Script| {
+        input_scripts.contains(&script)
+            || wallet
+                .transactions()
+                .any(|previous| previous.node.tx.output.iter().any(|output| &output.script_pubkey == script))
+    };
+    if tx.output.iter().any(|output| has_history(&output.script_pubkey)) {
+        warnings.push(PrivacyWarning::AddressReuse);
+    }
+    if recipients.iter().any(|value| value % ROUND_AMOUNT_SATS == 0) {
+        warnings.push(PrivacyWarning::RoundAmount);
+    }
+    if let Some(change_index) = change_index {
+        if recipients.iter().all(|value| tx.output[change_index].value > *value) {
+            warnings.push(PrivacyWarning::LargerChange);
+        }
+    }
+    let first_type = input_scripts.first().map(|script| input_script_type(script));
+    if input_scripts.iter().any(|script| Some(input_script_type(script)) != first_type) {
+        warnings.push(PrivacyWarning::MixedInputTypes);
+    }
+
+    PrivacyReport {
+        score: 100 - PRIVACY_WARNING_PENALTY * warnings.len() as u8,
+        warnings,
+    }
+}
+
+/// Decodes a base64 PSBT into a summary of its inputs, outputs and fee.
+/// Ownership of inputs and outputs is checked against `wallet`.
+pub fn describe_psbt(wallet: &Wallet, psbt_base64: &str, network: Network) -> Result<PsbtSummary> {
+    let psbt = parse_psbt(psbt_base64)?;
+    let tx = &psbt.unsigned_tx;
+
+    let inputs = tx
+        .input
+        .iter()
+        .zip(psbt.inputs.iter())
+        .map(|(txin, input)| {
+            let outpoint = txin.previous_output;
+            let prevout = match (&input.witness_utxo, &input.non_witness_utxo) {
+                (Some(txout), _) => Some(txout.clone()),
+                (None, Some(prev_tx)) => prev_tx.output.get(outpoint.vout as usize).cloned(),
+                (None, None) => None,
+            };
+            PsbtInputSummary {
+                outpoint: outpoint.to_string(),
+                amount: prevout.as_ref().map(|txout| txout.value),
+                address: prevout.as_ref().and_then(|txout| script_address(&txout.script_pubkey, network)),
+                is_mine: prevout.as_ref().is_some_and(|txout| wallet.is_mine(&txout.script_pubkey)),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let change_index = identify_change_output(wallet, &psbt);
+    let outputs = tx
+        .output
+        .iter()
+        .enumerate()
+        .map(|(index, txout)| PsbtOutputSummary {
+            amount: txout.value,
+            address: script_address(&txout.script_pubkey, network),
+            is_change: change_index == Some(index),
+        })
+        .collect::<Vec<_>>();
+
+    let input_total = inputs.iter().map(|input| input.amount).sum::<Option<u64>>();
+    let output_total = outputs.iter().map(|output| output.amount).sum::<u64>();
+    let fee = input_total.and_then(|total| total.checked_sub(output_total));
+
+    Ok(PsbtSummary {
+        txid: tx.txid().to_string(),
+        has_own_inputs: inputs.iter().any(|input| input.is_mine),
+        inputs,
+        outputs,
+        fee,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::wallet::{build_drain_transaction, build_transaction, Fee, TxOptions, tests::get_funded_wallet_with_change};
+    use bdk::{bitcoin::consensus::encode::serialize, wallet::AddressIndex, SignOptions};
+
+    const MNEMONIC_24: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+    const DERIVATION_PATH_EXTERNAL: &str = "m/86'/0'/0'/0";
+    const DERIVATION_PATH_INTERNAL: &str = "m/86'/0'/0'/1";
+    const ADDRESS: &str = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+    #[test]
+    fn test_combine_psbts() {
+        let (mut wallet, _txid) = get_funded_wallet_with_change(MNEMONIC_24, DERIVATION_PATH_EXTERNAL, DERIVATION_PATH_INTERNAL);
+        let unsigned = build_drain_transaction(&mut wallet, ADDRESS, Fee::Rate(1.0), None).unwrap();
+
+        // one signer returns a signed but not finalized PSBT, the other an unsigned one
+        let mut signed = unsigned.clone();
+        let sign_options = SignOptions {
+            try_finalize: false,
+            ..SignOptions::default()
+        };
+        wallet.sign(&mut signed, sign_options).unwrap();
+        assert!(!is_finalizable(&unsigned));
+
+        let combined = combine_psbts(vec![unsigned.to_string(), signed.to_string()], PsbtFormat::Base64).unwrap();
+        assert!(combined.finalizable);
+        let combined = PartiallySignedTransaction::from_str(&combined.psbt).unwrap();
+        assert_eq!(combined.unsigned_tx, unsigned.unsigned_tx);
+        assert!(combined.inputs[0].tap_key_sig.is_some());
+    }
+
+    #[test]
+    fn test_combine_psbts_different_transactions() {
+        let (mut wallet, _txid) = get_funded_wallet_with_change(MNEMONIC_24, DERIVATION_PATH_EXTERNAL, DERIVATION_PATH_INTERNAL);
+        let psbt_a = build_drain_transaction(&mut wallet, ADDRESS, Fee::Rate(1.0), None).unwrap();
+        let psbt_b = build_drain_transaction(&mut wallet, ADDRESS, Fee::Rate(2.0), None).unwrap();
+
+        assert!(combine_psbts(vec![psbt_a.to_string(), psbt_b.to_string()], PsbtFormat::Base64).is_err());
+        assert!(combine_psbts(vec![], PsbtFormat::Base64).is_err());
+    }
+
+    #[test]
+    fn test_describe_psbt() {
+        let (mut wallet, _txid) = get_funded_wallet_with_change(MNEMONIC_24, DERIVATION_PATH_EXTERNAL, DERIVATION_PATH_INTERNAL);
+        let options = TxOptions::for_network(Network::Regtest);
+        let mut psbt = build_transaction(&mut wallet, ADDRESS, Some(20_000), Fee::Rate(1.0), None, &options).unwrap();
+        wallet.sign(&mut psbt, options.sign_options()).unwrap();
+
+        let summary = describe_psbt(&wallet, &psbt.to_string(), Network::Testnet).unwrap();
+        assert_eq!(summary.txid, psbt.unsigned_tx.txid().to_string());
+        assert!(summary.has_own_inputs);
+        assert_eq!(summary.inputs.len(), 1);
+        assert_eq!(summary.inputs[0].amount, Some(50_000));
+
+        let recipient = summary.outputs.iter().find(|output| !output.is_change).unwrap();
+        assert_eq!(recipient.address.as_deref(), Some(ADDRESS));
+        assert_eq!(recipient.amount, 20_000);
+        let change = summary.outputs.iter().find(|output| output.is_change).unwrap();
+        assert_eq!(summary.fee, Some(50_000 - recipient.amount - change.amount));
+        assert!(summary.fee.unwrap() > 0);
+
+        assert!(describe_psbt(&wallet, "not a psbt", Network::Testnet).is_err());
+    }
+
+    #[test]
+    fn test_psbt_formats_round_trip() {
+        let (mut wallet, _txid) = get_funded_wallet_with_change(MNEMONIC_24, DERIVATION_PATH_EXTERNAL, DERIVATION_PATH_INTERNAL);
+        let psbt = build_drain_transaction(&mut wallet, ADDRESS, Fee::Rate(1.0), None).unwrap();
+
+        let base64 = serialize_psbt(&psbt, PsbtFormat::Base64);
+        let hex = serialize_psbt(&psbt, PsbtFormat::Hex);
+        assert!(base64.starts_with("cHNid"));
+        assert!(hex.starts_with("70736274ff"));
+
+        let from_base64 = parse_psbt(&base64).unwrap();
+        let from_hex = parse_psbt(&hex).unwrap();
+        assert_eq!(serialize(&from_base64), serialize(&psbt));
+        assert_eq!(serialize(&from_hex), serialize(&psbt));
+
+        assert_eq!(PsbtFormat::default(), PsbtFormat::Base64);
+        assert_eq!(PsbtFormat::parse("hex").unwrap(), PsbtFormat::Hex);
+        assert!(PsbtFormat::parse("binary").is_err());
+    }
+
+    #[test]
+    fn test_identify_change_output() {
+        let (mut wallet, _) = get_funded_wallet_with_change(MNEMONIC_24, DERIVATION_PATH_EXTERNAL, DERIVATION_PATH_INTERNAL);
+        let options = TxOptions::for_network(Network::Regtest);
+
+        let psbt = build_transaction(&mut wallet, ADDRESS, Some(20_000), Fee::Rate(1.0), None, &options).unwrap();
+        let change_index = identify_change_output(&wallet, &psbt).unwrap();
+        assert_ne!(psbt.unsigned_tx.output[change_index].script_pubkey, Address::from_str(ADDRESS).unwrap().script_pubkey());
+        assert_eq!(psbt.unsigned_tx.output[1 - change_index].value, 20_000);
+
+        // draining the wallet leaves no change
+        let psbt = build_drain_transaction(&mut wallet, ADDRESS, Fee::Rate(1.0), None).unwrap();
+        assert_eq!(identify_change_output(&wallet, &psbt), None);
+    }
+
+    #[test]
+    fn test_psbt_debug_json() {
+        let (mut wallet, _txid) = get_funded_wallet_with_change(MNEMONIC_24, DERIVATION_PATH_EXTERNAL, DERIVATION_PATH_INTERNAL);
+        let unsigned = build_drain_transaction(&mut wallet, ADDRESS, Fee::Rate(1.0), None).unwrap();
+
+        let mut signed = unsigned.clone();
+        let sign_options = SignOptions {
+            try_finalize: false,
+            ..SignOptions::default()
+        };
+        wallet.sign(&mut signed, sign_options).unwrap();
+
+        let debug: serde_json::Value = serde_json::from_str(&psbt_debug_json(&unsigned.to_string()).unwrap()).unwrap();
+        assert_eq!(debug["global"]["txid"], unsigned.unsigned_tx.txid().to_string());
+        assert_eq!(debug["inputs"][0]["signatures"], 0);
+        assert_eq!(debug["inputs"][0]["witness_utxo"], true);
+        assert_eq!(debug["inputs"][0]["tap_key_origins"], 1);
+
+        let debug: serde_json::Value = serde_json::from_str(&psbt_debug_json(&signed.to_string()).unwrap()).unwrap();
+        assert_eq!(debug["inputs"][0]["signatures"], 1);
+        assert_eq!(debug["inputs"][0]["tap_key_sig"], true);
+        assert_eq!(debug["inputs"][0]["finalized"], false);
+
+        wallet.sign(&mut signed, SignOptions::default()).unwrap();
+        let debug: serde_json::Value = serde_json::from_str(&psbt_debug_json(&signed.to_string()).unwrap()).unwrap();
+        assert_eq!(debug["inputs"][0]["finalized"], true);
+    }
+
+    #[test]
+    fn test_transaction_privacy_score() {
+        let (mut wallet, _txid) = get_funded_wallet_with_change(MNEMONIC_24, DERIVATION_PATH_EXTERNAL, DERIVATION_PATH_INTERNAL);
+        let options = TxOptions::for_network(Network::Regtest);
+
+        // 20,000 sats to a fresh address, with a larger change
+        let psbt = build_transaction(&mut wallet, ADDRESS, Some(20_000), Fee::Rate(1.0), None, &options).unwrap();
+        let report = transaction_privacy_score(&wallet, &psbt);
+        assert_eq!(report.warnings, vec![PrivacyWarning::LargerChange]);
+        assert_eq!(report.score, 75);
+
+        // back to the funded address, which already has history
+        let reused = wallet.get_address(AddressIndex::Peek(0)).address.to_string();
+        let psbt = build_transaction(&mut wallet, &reused, Some(40_000), Fee::Rate(1.0), None, &options).unwrap();
+        let report = transaction_privacy_score(&wallet, &psbt);
+        assert!(report.warnings.contains(&PrivacyWarning::AddressReuse));
+        assert!(!report.warnings.contains(&PrivacyWarning::LargerChange));
+        assert!(!report.warnings.contains(&PrivacyWarning::MixedInputTypes));
+        assert!(report.score < 100);
+    }
+}
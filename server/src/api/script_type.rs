@@ -0,0 +1,79 @@
+use anyhow::{bail, Result};
+use bdk::{bitcoin::Network, miniscript::descriptor::DescriptorType, KeychainKind, Wallet};
+use serde::{Deserialize, Serialize};
+
+// NOTE: hardcoded to BIP86
+pub(crate) const DEFAULT_DERIVATION_PATH_EXTERNAL: &str = "m/86'/0'/0'/0";
+pub(crate) const DEFAULT_DERIVATION_PATH_INTERNAL: &str = "m/86'/0'/0'/1";
+
+// NOTE: BIP84 for native segwit
+pub(crate) const SEGWIT_DERIVATION_PATH_EXTERNAL: &str = "m/84'/0'/0'/0";
+pub(crate) const SEGWIT_DERIVATION_PATH_INTERNAL: &str = "m/84'/0'/0'/1";
+
+// NOTE: BIP49 for nested segwit
+pub(crate) const NESTED_SEGWIT_DERIVATION_PATH_EXTERNAL: &str = "m/49'/0'/0'/0";
+pub(crate) const NESTED_SEGWIT_DERIVATION_PATH_INTERNAL: &str = "m/49'/0'/0'/1";
+
+/// Script type of the wallet's descriptors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScriptType {
+    Taproot,
+    NativeSegwit,
+    NestedSegwit,
+}
+
+impl ScriptType {
+    /// Parses a script type, "taproot", "segwit" or "nested-segwit".
+    pub fn parse(script_type: &str) -> Result<Self> {
+        match script_type {
+            "taproot" | "p2tr" => Ok(ScriptType::Taproot),
+            "segwit" | "p2wpkh" => Ok(ScriptType::NativeSegwit),
+            "nested-segwit" | "p2sh-p2wpkh" => Ok(ScriptType::NestedSegwit),
+            &_ => bail!("Invalid script type: {}", script_type),
+        }
+    }
+
+    /// The script type of a wallet's external descriptor, e.g. taproot for a `tr()` descriptor.
+    pub fn of_wallet<D>(wallet: &Wallet<D>) -> Result<Self> {
+        match wallet.get_descriptor_for_keychain(KeychainKind::External).desc_type() {
+            DescriptorType::Tr => Ok(ScriptType::Taproot),
+            DescriptorType::Wpkh => Ok(ScriptType::NativeSegwit),
+            DescriptorType::ShWpkh => Ok(ScriptType::NestedSegwit),
+            desc_type => bail!("Unsupported descriptor type: {:?}", desc_type),
+        }
+    }
+
+    /// The default external and internal derivation paths.
+    pub fn derivation_paths(&self) -> (&'static str, &'static str) {
+        match self {
+            ScriptType::Taproot => (DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL),
+            ScriptType::NativeSegwit => (SEGWIT_DERIVATION_PATH_EXTERNAL, SEGWIT_DERIVATION_PATH_INTERNAL),
+            ScriptType::NestedSegwit => (NESTED_SEGWIT_DERIVATION_PATH_EXTERNAL, NESTED_SEGWIT_DERIVATION_PATH_INTERNAL),
+        }
+    }
+
+    /// The BIP43 purpose of the script type's derivation paths.
+    pub fn purpose(&self) -> u32 {
+        match self {
+            ScriptType::Taproot => 86,
+            ScriptType::NativeSegwit => 84,
+            ScriptType::NestedSegwit => 49,
+        }
+    }
+
+    /// The expected address prefix on a network.
+    pub fn address_prefix(&self, network: Network) -> String {
+        let is_mainnet = network == Network::Bitcoin;
+        let hrp = match network {
+            Network::Bitcoin => "bc",
+            Network::Testnet | Network::Signet => "tb",
+            Network::Regtest => "bcrt",
+        };
+        match self {
+            ScriptType::Taproot => format!("{}1p", hrp),
+            ScriptType::NativeSegwit => format!("{}1q", hrp),
+            // NOTE: P2SH addresses are base58, with no bech32 human readable part
+            ScriptType::NestedSegwit => if is_mainnet { "3" } else { "2" }.to_string(),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use crate::api::invoice::InvoiceBook;
+use anyhow::Result;
+use bdk::{
+    chain::{Append, PersistBackend},
+    KeychainKind,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+/// The changes to a wallet's state that are persisted.
+pub use bdk::wallet::ChangeSet;
+
+/// How far an interrupted sync got: the index each keychain's scan resumes from.
+pub type SyncProgress = BTreeMap<KeychainKind, u32>;
+
+/// Storage for wallet changesets, keyed by the wallet's public descriptor.
+pub trait WalletStore {
+    /// Loads all changesets persisted for a descriptor, aggregated into one.
+    fn load(&self, descriptor: &str) -> Option<ChangeSet>;
+
+    /// Persists a changeset for a descriptor, on top of the ones already persisted.
+    fn persist(&self, descriptor: &str, changeset: ChangeSet) -> Result<()>;
+
+    /// Loads the progress of an interrupted sync of a descriptor, if any.
+    /// Stores that don't track sync progress never have any, so a sync starts over.
+    fn load_sync_progress(&self, _descriptor: &str) -> Option<SyncProgress> {
+        None
+    }
+
+    /// Saves the progress of an ongoing sync of a descriptor, replacing the previous one.
+    /// `None` clears it once the sync completes.
+    fn persist_sync_progress(&self, _descriptor: &str, _progress: Option<SyncProgress>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Loads the invoices created for a descriptor, if any.
+    /// Stores that don't track invoices never have any.
+    fn load_invoices(&self, _descriptor: &str) -> Option<InvoiceBook> {
+        None
+    }
+
+    /// Saves the invoices created for a descriptor, replacing the previous ones.
+    fn persist_invoices(&self, _descriptor: &str, _invoices: &InvoiceBook) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// An in-memory store, lost when dropped.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    changesets: Mutex<HashMap<String, ChangeSet>>,
+    sync_progress: Mutex<HashMap<String, SyncProgress>>,
+    invoices: Mutex<HashMap<String, InvoiceBook>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl WalletStore for MemoryStore {
+    fn load(&self, descriptor: &str) -> Option<ChangeSet> {
+        self.changesets.lock().unwrap().get(descriptor).cloned()
+    }
+
+    fn persist(&self, descriptor: &str, changeset: ChangeSet) -> Result<()> {
+        self.changesets
+            .lock()
+            .unwrap()
+            .entry(descriptor.to_string())
+            .or_default()
+            .append(changeset);
+        Ok(())
+    }
+
+    fn load_sync_progress(&self, descriptor: &str) -> Option<SyncProgress> {
+        self.sync_progress.lock().unwrap().get(descriptor).cloned()
+    }
+
+    fn persist_sync_progress(&self, descriptor: &str, progress: Option<SyncProgress>) -> Result<()> {
+        let mut sync_progress = self.sync_progress.lock().unwrap();
+        match progress {
+            Some(progress) => sync_progress.insert(descriptor.to_string(), progress),
+            None => sync_progress.remove(descriptor),
+        };
+        Ok(())
+    }
+
+    fn load_invoices(&self, descriptor: &str) -> Option<InvoiceBook> {
+        self.invoices.lock().unwrap().get(descriptor).cloned()
+    }
+
+    fn persist_invoices(&self, descriptor: &str, invoices: &InvoiceBook) -> Result<()> {
+        self.invoices.lock().unwrap().insert(descriptor.to_string(), invoices.clone());
+        Ok(())
+    }
+}
+
+/// Adapts a `WalletStore` to BDK's persistence backend for a single wallet.
+pub struct StoreBackend<'a> {
+    store: &'a dyn WalletStore,
+    descriptor: String,
+}
+
+impl<'a> StoreBackend<'a> {
+    pub fn new(store: &'a dyn WalletStore, descriptor: String) -> Self {
+        Self { store, descriptor }
+    }
+}
+
+impl PersistBackend<ChangeSet> for StoreBackend<'_> {
+    type WriteError = anyhow::Error;
+    type LoadError = anyhow::Error;
+
+    fn write_changes(&mut self, changeset: &ChangeSet) -> Result<()> {
+        self.store.persist(&self.descriptor, changeset.clone())
+    }
+
+    fn load_from_persistence(&mut self) -> Result<ChangeSet> {
+        Ok(self.store.load(&self.descriptor).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::wallet::{create_wallet, load_or_create_wallet};
+    use bdk::wallet::AddressIndex;
+
+    const MNEMONIC_24: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+    const DERIVATION_PATH_EXTERNAL: &str = "m/86'/0'/0'/0";
+    const DERIVATION_PATH_INTERNAL: &str = "m/86'/0'/0'/1";
+
+    #[test]
+    fn test_memory_store_persists_changesets() {
+        let store = MemoryStore::new();
+        assert!(store.load("tr(...)").is_none());
+
+        {
+            let mut wallet = create_wallet(MNEMONIC_24, "regtest", DERIVATION_PATH_EXTERNAL, DERIVATION_PATH_INTERNAL, &store).unwrap();
+            assert_eq!(wallet.get_address(AddressIndex::New).index, 0);
+            assert_eq!(wallet.get_address(AddressIndex::New).index, 1);
+            // NOTE: revealing an address commits it right away, nothing is left staged
+            assert!(!wallet.commit().unwrap());
+        }
+
+        // the revealed addresses are loaded from the store
+        let mut wallet = create_wallet(MNEMONIC_24, "regtest", DERIVATION_PATH_EXTERNAL, DERIVATION_PATH_INTERNAL, &store).unwrap();
+        assert_eq!(wallet.get_address(AddressIndex::New).index, 2);
+
+        // the default taproot wallet is the same wallet
+        let mut wallet = load_or_create_wallet(MNEMONIC_24, "regtest", &store).unwrap();
+        assert_eq!(wallet.get_address(AddressIndex::New).index, 3);
+    }
+}
@@ -1,31 +1,114 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use bdk::{
     Wallet,
-    bitcoin::{Network, util::bip32::DerivationPath, secp256k1::Secp256k1, psbt::PartiallySignedTransaction, Transaction, Address},
-    keys::bip39::{Mnemonic, Language},
+    bitcoin::{Network, consensus::encode::deserialize, hashes::hex::{FromHex, ToHex}, util::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey, KeySource}, secp256k1::Secp256k1, psbt::PartiallySignedTransaction, Transaction, Address, LockTime, Txid, PrivateKey, OutPoint, TxOut, BlockHash, Script, Sequence},
+    keys::{bip39::{Mnemonic, Language, WordCount}, DerivableKey, GeneratableKey, GeneratedKey, KeyMap},
+    miniscript::{ForEachKey, Tap},
     descriptor,
-    descriptor::IntoWalletDescriptor, LocalUtxo, wallet::{AddressIndex, AddressInfo}, FeeRate, SignOptions,
+    descriptor::{IntoWalletDescriptor, ExtendedDescriptor, policy::SatisfiableItem}, LocalUtxo, wallet::{AddressIndex, AddressInfo, Balance, tx_builder::TxOrdering, Update}, FeeRate, SignOptions,
+    chain::{BlockId, ConfirmationTime},
+    KeychainKind,
 };
-use bdk_esplora::{esplora_client::{AsyncClient, Builder}, EsploraAsyncExt};
+use bdk_esplora::esplora_client::{AsyncClient, Builder};
 use leptos::{server, ServerFnError};
-use std::{str::FromStr, u32, collections::HashMap};
+use crate::api::script_type::ScriptType;
+use crate::api::{descriptor::{check_hardened_wildcard, expand_multipath, normalize_slip132}, error::WalletError, esplora::{auth_headers, block_time_cache, broadcast_raw_transaction, concurrency_config, create_client_with_headers, fee_cache, get_address_balance, get_mempool_status, get_merkle_proof, rate_limiter, MempoolStatus, RateLimitedClient}, fees::{estimate_consolidation_savings, estimate_vsize, fee_rate_from_histogram, input_weight, weight_to_vbytes}, locks::{utxo_locks, UtxoLockGuard, UtxoLocks}, price::get_fiat_value, psbt::{combine_psbts, describe_psbt, identify_change_output, parse_psbt, PsbtFormat}, store::{StoreBackend, SyncProgress, WalletStore}};
+use std::{str::FromStr, collections::{BTreeMap, HashMap, HashSet}, fmt::Display, future::Future, ops::Range, sync::OnceLock, time::{Duration, Instant}};
 use serde::{Serialize, Deserialize};
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
 use serde_json::to_string;
 
-// NOTE: hardcoded to BIP86
-const DEFAULT_DERIVATION_PATH_EXTERNAL: &str = "m/86'/0'/0'/0";
-const DEFAULT_DERIVATION_PATH_INTERNAL: &str = "m/86'/0'/0'/1";
-
 // NOTE: hardcoded to mempool.space
 const DEFAULT_ESPLORA_BASE_URL_MAINNET: &str = "https://mempool.space/api";
 const DEFAULT_ESPLORA_BASE_URL_TESTNET: &str = "https://mempool.space/testnet/api";
 
+// NOTE: Bitcoin Core's default minimum relay fee rate in sat/vB
+const MIN_RELAY_FEE_RATE: f32 = 1.0;
+
+// NOTE: BIP125 signals RBF with an input sequence below 0xfffffffe
+const MAX_RBF_SEQUENCE: u32 = 0xfffffffd;
+
+// NOTE: Bitcoin Core's maximum standard transaction weight
+const MAX_STANDARD_TX_WEIGHT: usize = 400_000;
+
+// NOTE: coinbase outputs can only be spent after 100 confirmations
+const COINBASE_MATURITY: u32 = 100;
+
+// NOTE: coin selections retried when concurrent transactions reserve the selected UTXOs first
+const MAX_LOCK_ATTEMPTS: usize = 3;
+
+// NOTE: fallback used when broadcasting fails, hardcoded to blockstream.info
+const FALLBACK_ESPLORA_BASE_URL_MAINNET: &str = "https://blockstream.info/api";
+const FALLBACK_ESPLORA_BASE_URL_TESTNET: &str = "https://blockstream.info/testnet/api";
+
 #[derive(Debug)]
 enum AddressType {
     Receive,
     Change
 }
 
+/// Validates that an address has the expected prefix for a network and script type.
+pub fn validate_address_prefix(address: &Address, network: Network, script_type: ScriptType) -> Result<()> {
+    let prefix = script_type.address_prefix(network);
+    let address = address.to_string();
+    if !address.starts_with(&prefix) {
+        bail!("Address {} doesn't match the expected prefix {}", address, prefix);
+    }
+    Ok(())
+}
+
+/// Largest non-hardened BIP32 child index.
+const MAX_NON_HARDENED_INDEX: usize = (1 << 31) - 1;
+
+/// Converts an address index to a BIP32 non-hardened child index,
+/// erroring instead of truncating when it is out of range.
+pub fn validate_address_index(index: usize) -> Result<u32> {
+    if index > MAX_NON_HARDENED_INDEX {
+        bail!("Address index {} is out of range, the maximum is {}", index, MAX_NON_HARDENED_INDEX);
+    }
+    Ok(index as u32)
+}
+
+/// Derives a keychain's address at `index` directly from its descriptor,
+/// unlike BDK's `AddressIndex::Peek`, which derives every address up to the index.
+pub fn peek_address<D>(wallet: &Wallet<D>, keychain: KeychainKind, index: u32) -> Result<AddressInfo> {
+    validate_address_index(index as usize)?;
+    let address = wallet
+        .get_descriptor_for_keychain(keychain)
+        .at_derivation_index(index)
+        .address(wallet.network())?;
+    Ok(AddressInfo { index, address, keychain })
+}
+
+/// Parses an address index mode, "peek" at a given `index`, or the "new" or "last_unused" address.
+/// Only "peek" takes an index, "new" and "last_unused" depend on the wallet's synced history.
+pub fn parse_address_index(index_mode: &str, index: Option<usize>) -> Result<AddressIndex> {
+    match (index_mode, index) {
+        ("peek", Some(index)) => Ok(AddressIndex::Peek(validate_address_index(index)?)),
+        ("peek", None) => bail!("Address index mode peek requires an index"),
+        ("new", None) => Ok(AddressIndex::New),
+        ("last_unused", None) => Ok(AddressIndex::LastUnused),
+        ("new" | "last_unused", Some(_)) => bail!("Address index mode {} doesn't take an index", index_mode),
+        _ => bail!("Invalid address index mode {}, must be peek, new or last_unused", index_mode),
+    }
+}
+
+/// Parses an address, checking that it belongs to the given network.
+pub fn parse_address_for_network(address: &str, network: Network) -> Result<Address> {
+    let address = Address::from_str(address.trim())?;
+    if !address.is_valid_for_network(network) {
+        bail!("Address {} is not valid for {}", address, network);
+    }
+    Ok(address)
+}
+
+/// Returns the dust threshold in sats of an address's script type, the minimum amount it can be sent,
+/// e.g. 546 sats for p2pkh, 294 sats for p2wpkh and 330 sats for p2tr.
+pub fn dust_limit_for_address(address: &str, network: &str) -> Result<u64> {
+    let address = parse_address_for_network(address, parse_network(network)?)?;
+    Ok(address.script_pubkey().dust_value().to_sat())
+}
+
 /// Hack to get around the fact that BDK's AddressInfo doesn't implement Serialize.
 #[derive(Debug, Serialize, Deserialize)]
 struct AddressInfoDef {
@@ -43,356 +126,5491 @@ impl AddressInfoDef {
     }
 }
 
-/// Creates a wallet from a mnemonic, a network type, and an internal and external derivation paths.
-pub fn create_wallet(
+/// Hack to get around the fact that BDK's LocalUtxo doesn't serialize its script and full derivation path,
+/// which integrations need to build their own PSBTs.
+/// The LocalUtxo's own fields are kept as they were serialized.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtxoDef {
+    #[serde(flatten)]
+    pub utxo: LocalUtxo,
+    pub script_pubkey_hex: String,
+    /// The full derivation path from the master key, e.g. `m/86'/0'/0'/0/0`.
+    pub derivation_path: Option<String>,
+}
+impl UtxoDef {
+    fn from(wallet: &Wallet, utxo: LocalUtxo) -> Self {
+        Self {
+            script_pubkey_hex: utxo.txout.script_pubkey.to_hex(),
+            derivation_path: full_derivation_path(wallet, utxo.keychain, utxo.derivation_index).map(|path| path.to_string()),
+            utxo,
+        }
+    }
+}
+
+/// The full derivation path of a keychain's address at `index`, from the wallet's descriptor key origin.
+fn full_derivation_path(wallet: &Wallet, keychain: KeychainKind, index: u32) -> Option<DerivationPath> {
+    let mut path = None;
+    wallet.get_descriptor_for_keychain(keychain).for_each_key(|key| {
+        path = Some(key.full_derivation_path());
+        false
+    });
+    path?.child(ChildNumber::from_normal_idx(index).ok()?).into()
+}
+
+/// The wallet's chain tip after a sync.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainTip {
+    pub height: u32,
+    pub hash: String,
+}
+
+/// Parses a network type.
+/// "bitcoin" and "mainnet" are aliases, as are "test" and "testnet".
+pub fn parse_network(network: &str) -> Result<Network> {
+    match network {
+        "mainnet" | "bitcoin" => Ok(Network::Bitcoin),
+        "testnet" | "test" => Ok(Network::Testnet),
+        "signet" => Ok(Network::Signet),
+        "regtest" => Ok(Network::Regtest),
+        &_ => bail!("Invalid network: {}", network),
+    }
+}
+
+/// Parses the value of the `DEFAULT_SCRIPT_TYPE` environment variable, taproot when unset or invalid.
+pub fn parse_default_script_type(value: Option<&str>) -> ScriptType {
+    match value {
+        Some(script_type) => ScriptType::parse(script_type).unwrap_or_else(|e| {
+            log::warn!("{}, defaulting to taproot", e);
+            ScriptType::Taproot
+        }),
+        None => ScriptType::Taproot,
+    }
+}
+
+/// The deployment's default script type, taproot unless set with the `DEFAULT_SCRIPT_TYPE` environment variable,
+/// e.g. "segwit" for native segwit addresses by default.
+pub fn default_script_type() -> ScriptType {
+    static DEFAULT_SCRIPT_TYPE: OnceLock<ScriptType> = OnceLock::new();
+    *DEFAULT_SCRIPT_TYPE.get_or_init(|| parse_default_script_type(std::env::var("DEFAULT_SCRIPT_TYPE").ok().as_deref()))
+}
+
+/// Creates a wallet from a mnemonic, a network type, and an internal and external derivation paths,
+/// loading its previous state from a store and persisting new changes to it on commit.
+pub fn create_wallet<'a>(
     mnemonic: &str,
     network: &str,
     derivation_path_external: &str,
     derivation_path_internal: &str,
-) -> Result<Wallet> {
-    let secp = Secp256k1::new();
+    store: &'a dyn WalletStore,
+) -> Result<Wallet<StoreBackend<'a>>> {
+    create_stored_wallet(
+        mnemonic,
+        network,
+        ScriptType::Taproot,
+        derivation_path_external,
+        derivation_path_internal,
+        store,
+    )
+}
+
+/// Loads a wallet from a store, or creates it if the store has none, from a mnemonic and a network type,
+/// with the deployment's default script type and its default derivation paths.
+pub fn load_or_create_wallet<'a>(mnemonic: &str, network: &str, store: &'a dyn WalletStore) -> Result<Wallet<StoreBackend<'a>>> {
+    let script_type = default_script_type();
+    let (derivation_path_external, derivation_path_internal) = script_type.derivation_paths();
+    create_stored_wallet(mnemonic, network, script_type, derivation_path_external, derivation_path_internal, store)
+}
 
+/// Creates a wallet from a mnemonic, a network type, a script type, and an internal and external derivation paths,
+/// persisted in a store.
+fn create_stored_wallet<'a>(
+    mnemonic: &str,
+    network: &str,
+    script_type: ScriptType,
+    derivation_path_external: &str,
+    derivation_path_internal: &str,
+    store: &'a dyn WalletStore,
+) -> Result<Wallet<StoreBackend<'a>>> {
     let mnemonic = Mnemonic::parse_in(Language::English, mnemonic)?;
-    let network = match network {
-        "mainnet" => Network::Bitcoin,
-        "testnet" => Network::Testnet,
-        "signet" => Network::Signet,
-        "regtest" => Network::Regtest,
-        &_ => Network::Testnet, // NOTE: a good default
-    };
+    let network = parse_network(network)?;
+    let xprv = ExtendedPrivKey::new_master(network, &mnemonic.to_seed(""))?;
 
-    // generate derivation paths
-    let external_path = DerivationPath::from_str(derivation_path_external).unwrap();
-    let internal_path = DerivationPath::from_str(derivation_path_internal).unwrap();
-
-    // generate external and internal descriptor from mnemonic
-    let (external_descriptor, _ext_keymap) =
-        match descriptor!(tr((mnemonic.clone(), external_path))) // NOTE: taproot is hardcoded tr
-            .unwrap()
-            .into_wallet_descriptor(&secp, network)
-        {
-            Ok((extended_descriptor, keymap)) => (extended_descriptor, keymap),
-            Err(e) => panic!("Invalid external derivation path: {}", e),
-        };
-    let (internal_descriptor, _int_keymap) =
-        match descriptor!(tr((mnemonic.clone(), internal_path))) // NOTE: taproot is hardcoded tr
-            .unwrap()
-            .into_wallet_descriptor(&secp, network)
-        {
-            Ok((extended_descriptor, keymap)) => (extended_descriptor, keymap),
-            Err(e) => panic!("Invalid internal derivation path: {}", e),
-        };
+    let (external, internal) = wallet_descriptors(xprv, network, script_type, derivation_path_external, derivation_path_internal)?;
+    // NOTE: keyed by the public descriptor, so no private keys end up in the store
+    let backend = StoreBackend::new(store, external.0.to_string());
+    Wallet::new(external, Some(internal), backend, network).map_err(|e| anyhow!("Error loading wallet: {}", e))
+}
 
-    Ok(Wallet::new_no_persist(external_descriptor, Some(internal_descriptor), network)?)
+/// Creates a wallet from a mnemonic and a network type,
+/// with the deployment's default script type and its default derivation paths.
+pub fn create_default_wallet(mnemonic: &str, network: &str) -> Result<Wallet> {
+    create_wallet_with_default_paths(mnemonic, network, default_script_type())
 }
 
-/// Sync a wallet with the Esplora client.
-pub async fn sync_wallet(wallet: &mut Wallet, client: &AsyncClient) -> Result<bool> {
-    let local_chain = wallet.checkpoints();
-
-    let keychain_spks = wallet.spks_of_all_keychains().into_iter().collect();
-    let update = client
-        .scan(
-            local_chain,
-            keychain_spks,
-            [],
-            [],
-            5, // stop gap
-            5, // parallel requests
-        )
-        .await?;
-    wallet.apply_update(update)?;
-    Ok(wallet.commit()?)
+/// Creates a wallet from a mnemonic, a network type and a script type, with the script type's default derivation paths.
+fn create_wallet_with_default_paths(mnemonic: &str, network: &str, script_type: ScriptType) -> Result<Wallet> {
+    let (derivation_path_external, derivation_path_internal) = script_type.derivation_paths();
+    create_wallet_with_script_type(mnemonic, network, script_type, derivation_path_external, derivation_path_internal)
 }
 
-/// Get the fee estimates from the Esplora server.
-/// The default block is 1, which is the next block.
-pub async fn get_fee_estimates(client: &AsyncClient, block: Option<usize>) -> Result<f32> {
-    let fee_estimates: HashMap<String, f64> = client.get_fee_estimates().await?;
+/// Creates a wallet from a mnemonic, a network type, a script type, and an internal and external derivation paths.
+pub fn create_wallet_with_script_type(
+    mnemonic: &str,
+    network: &str,
+    script_type: ScriptType,
+    derivation_path_external: &str,
+    derivation_path_internal: &str,
+) -> Result<Wallet> {
+    let mnemonic = Mnemonic::parse_in(Language::English, mnemonic)?;
+    let network = parse_network(network)?;
+    let xprv = ExtendedPrivKey::new_master(network, &mnemonic.to_seed(""))?;
 
-    // NOTE: if block is not specified, use the next block
-    let fee_estimate = match block {
-        Some(block) => fee_estimates.get(&block.to_string()).unwrap(),
-        None => fee_estimates.get("1").unwrap(),
-    };
-    Ok(*fee_estimate as f32)
+    create_wallet_from_xprv(xprv, network, script_type, derivation_path_external, derivation_path_internal)
 }
 
-/// Create a Signed Transaction from a wallet using all available coins to send to a given address.
-/// Estimate the fee using the Esplora client.
-/// Tries to use fee rate such that it will be included in the next block.
-/// By default, the transaction is marked as RBF.
-pub async fn create_signed_transaction(
-    wallet: &mut Wallet,
-    address: &str,
-    client: &AsyncClient,
-) -> Result<PartiallySignedTransaction> {
-    let fee_rate = get_fee_estimates(client, None).await.unwrap();
-    let address = Address::from_str(address)?;
+/// External and internal derivation paths of an account for the default script type,
+/// e.g. `m/86'/0'/1'/0` and `m/86'/0'/1'/1` for taproot.
+pub fn account_derivation_paths(account: u32) -> (String, String) {
+    let purpose = default_script_type().purpose();
+    (format!("m/{}'/0'/{}'/0", purpose, account), format!("m/{}'/0'/{}'/1", purpose, account))
+}
 
-    // create a drain transaction
-    let mut tx_builder = wallet.build_tx();
-    tx_builder
-        // Spend all outputs in this wallet.
-        .drain_wallet()
-        // Send the excess (which is all the coins minus the fee) to this address.
-        .drain_to(address.script_pubkey())
-        .fee_rate(FeeRate::from_sat_per_vb(fee_rate))
-        .enable_rbf();
+/// Sums the values of a keychain's UTXOs whose derivation index falls within `start..start + count`,
+/// e.g. to show the balance of a page of addresses.
+pub fn get_balance_range(wallet: &Wallet, keychain: KeychainKind, start: u32, count: u32) -> u64 {
+    let end = start.saturating_add(count);
+    wallet
+        .list_unspent()
+        .filter(|utxo| utxo.keychain == keychain && (start..end).contains(&utxo.derivation_index))
+        .map(|utxo| utxo.txout.value)
+        .sum()
+}
 
-    let (mut psbt, _) = match tx_builder.finish() {
-        Ok(psbt) => psbt,
-        Err(e) => panic!("Error creating transaction: {}", e),
-    };
-    match wallet.sign(&mut psbt, SignOptions::default()) {
-        Ok(finalized) => finalized,
-        Err(e) => panic!("Error signing transaction: {}", e),
-    };
-    Ok(psbt)
+/// Sums the total balances of several wallets.
+pub fn total_balance(wallets: &[Wallet]) -> u64 {
+    wallets.iter().map(|wallet| wallet.get_balance().total()).sum()
 }
 
-/// Broadcast a signed transaction to the network using the given Esplora client.
-pub async fn broadcast_signed_transaction(psbt: PartiallySignedTransaction, client: &AsyncClient) -> Result<Transaction> {
-    let tx = psbt.extract_tx();
-    let _ = client.broadcast(&tx).await;
-    Ok(tx)
+/// Creates and syncs a wallet per account of a mnemonic, and returns their combined balance.
+/// The accounts are synced in parallel.
+pub async fn aggregate_balance<C>(mnemonic: &str, network: &str, accounts: Vec<u32>, client: &C) -> Result<u64>
+where
+    C: Scanner + Clone + Send + Sync + 'static,
+{
+    let mut syncs = tokio::task::JoinSet::new();
+    for account in accounts {
+        let (derivation_path_external, derivation_path_internal) = account_derivation_paths(account);
+        let mut wallet = create_wallet_with_script_type(mnemonic, network, default_script_type(), &derivation_path_external, &derivation_path_internal)?;
+        let client = client.clone();
+        syncs.spawn(async move {
+            sync_wallet(&mut wallet, &client, SyncConfig::default()).await?;
+            Ok::<Wallet, anyhow::Error>(wallet)
+        });
+    }
+
+    let mut wallets = Vec::new();
+    while let Some(wallet) = syncs.join_next().await {
+        wallets.push(wallet??);
+    }
+    Ok(total_balance(&wallets))
 }
 
-/// Returns a JSON string of the wallet's utxos.
-#[server(GetUtxo, "/api", "GetJson", "utxo")] // GetJson is a GET and will be cached
-pub async fn get_utxo(mnemonic: String, network: String) -> Result<String, ServerFnError>{
-    // Create the Esplora async client
-    let base_url = if network == "bitcoin" { DEFAULT_ESPLORA_BASE_URL_MAINNET } else { DEFAULT_ESPLORA_BASE_URL_TESTNET };
-    let esplora_client = Builder::new(base_url).build_async()?;
+/// Creates a wallet from raw seed bytes, a network type, a script type, and an internal and external derivation paths.
+/// The seed must be between 16 and 64 bytes long.
+pub fn create_wallet_from_seed(
+    seed: &[u8],
+    network: &str,
+    script_type: ScriptType,
+    derivation_path_external: &str,
+    derivation_path_internal: &str,
+) -> Result<Wallet> {
+    if !(16..=64).contains(&seed.len()) {
+        bail!("Invalid seed length: {} bytes, expected between 16 and 64", seed.len());
+    }
+    let network = parse_network(network)?;
+    let xprv = ExtendedPrivKey::new_master(network, seed)?;
 
-    // Create the wallet
-    let mut wallet = create_wallet(mnemonic.as_str(), network.as_str(),
-            DEFAULT_DERIVATION_PATH_EXTERNAL,
-            DEFAULT_DERIVATION_PATH_INTERNAL,
-            ).unwrap();
+    create_wallet_from_xprv(xprv, network, script_type, derivation_path_external, derivation_path_internal)
+}
 
-    // Sync Wallet
-    let _ = sync_wallet(&mut wallet, &esplora_client).await;
+/// How a wallet is derived from its mnemonic, for display.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DerivationInfo {
+    pub script_type: ScriptType,
+    pub external_path: String,
+    pub internal_path: String,
+    pub network: String,
+    /// Fingerprint of the master key, as in key origins `[fingerprint/path]`.
+    pub master_fingerprint: String,
+}
 
-    // Get UTXOs
-    let utxo = wallet.list_unspent().collect::<Vec<LocalUtxo>>();
+/// Derivation info of a wallet created from a mnemonic, a network type, and a script type.
+/// The master fingerprint lets users check the wallet matches another wallet's setup.
+pub fn get_derivation_info(mnemonic: &str, network: &str, script_type: ScriptType) -> Result<DerivationInfo> {
+    let mnemonic = Mnemonic::parse_in(Language::English, mnemonic)?;
+    let network = parse_network(network)?;
+    let xprv = ExtendedPrivKey::new_master(network, &mnemonic.to_seed(""))?;
+    let (external_path, internal_path) = script_type.derivation_paths();
 
-    // Serialize to JSON
-    let json = to_string(&utxo)?;
-    Ok(json)
+    Ok(DerivationInfo {
+        script_type,
+        external_path: external_path.to_string(),
+        internal_path: internal_path.to_string(),
+        network: network.to_string(),
+        master_fingerprint: xprv.fingerprint(&Secp256k1::new()).to_string(),
+    })
 }
 
-/// Returns a JSON string of the wallet's balance.
-#[server(GetBalance, "/api", "GetJson", "balance")] // GetJson is a GET and will be cached
-pub async fn get_balance(mnemonic: String, network: String) -> Result<String, ServerFnError> {
-    // Create the Esplora async client
-    let base_url = if network == "bitcoin" { DEFAULT_ESPLORA_BASE_URL_MAINNET } else { DEFAULT_ESPLORA_BASE_URL_TESTNET };
-    let esplora_client = Builder::new(base_url).build_async()?;
+/// Whether a phrase is a valid BIP39 mnemonic in a language, checking its checksum without building a wallet,
+/// e.g. to verify a re-typed backup. A wrong checksum and an unknown word are distinct errors,
+/// so the user can be told what's wrong, while any other malformed phrase, e.g. of a wrong length, isn't valid.
+pub fn validate_mnemonic(phrase: &str, language: Language) -> Result<bool> {
+    match Mnemonic::parse_in(language, phrase) {
+        Ok(_) => Ok(true),
+        Err(bdk::keys::bip39::Error::InvalidChecksum) => Err(WalletError::InvalidMnemonicChecksum.into()),
+        Err(bdk::keys::bip39::Error::UnknownWord(index)) => {
+            let word = phrase.split_whitespace().nth(index).unwrap_or_default();
+            Err(WalletError::UnknownMnemonicWord(word.to_string()).into())
+        }
+        Err(_) => Ok(false),
+    }
+}
 
-    // Create the wallet
-    let mut wallet = create_wallet(mnemonic.as_str(), network.as_str(),
-            DEFAULT_DERIVATION_PATH_EXTERNAL,
-            DEFAULT_DERIVATION_PATH_INTERNAL,
-            ).unwrap();
+/// Returns the hex master fingerprint of a mnemonic, as displayed by hardware wallets.
+/// The fingerprint doesn't depend on the network.
+pub fn get_master_fingerprint(mnemonic: &str) -> Result<String> {
+    let mnemonic = Mnemonic::parse_in(Language::English, mnemonic)?;
+    let xprv = ExtendedPrivKey::new_master(Network::Bitcoin, &mnemonic.to_seed(""))?;
+    Ok(xprv.fingerprint(&Secp256k1::new()).to_string())
+}
 
-    // Sync Wallet
-    let _ = sync_wallet(&mut wallet, &esplora_client).await;
+/// Creates a wallet from a master extended private key.
+fn create_wallet_from_xprv(
+    xprv: ExtendedPrivKey,
+    network: Network,
+    script_type: ScriptType,
+    derivation_path_external: &str,
+    derivation_path_internal: &str,
+) -> Result<Wallet> {
+    let (external, internal) = wallet_descriptors(xprv, network, script_type, derivation_path_external, derivation_path_internal)?;
 
-    // Get Balance
-    let balance = wallet.get_balance();
+    // NOTE: the keymaps are needed for the wallet to be able to sign
+    Ok(Wallet::new_no_persist(external, Some(internal), network)?)
+}
 
-    // Serialize to JSON
-    let json = to_string(&balance)?;
-    Ok(json)
+/// Splits a derivation path from the master key at its last hardened step into the account key there,
+/// its `[fingerprint/path]` key origin, and the remaining unhardened path.
+fn split_key_origin(xprv: ExtendedPrivKey, path: &DerivationPath) -> Result<(ExtendedPrivKey, KeySource, DerivationPath)> {
+    let secp = Secp256k1::new();
+    let steps: &[ChildNumber] = path.as_ref();
+    let hardened = steps.len() - steps.iter().rev().take_while(|step| step.is_normal()).count();
+    let origin_path = DerivationPath::from(&steps[..hardened]);
+    let account_xprv = xprv.derive_priv(&secp, &origin_path)?;
+    Ok((account_xprv, (xprv.fingerprint(&secp), origin_path), DerivationPath::from(&steps[hardened..])))
 }
 
-/// Returns a JSON string of the wallet's address for a given address type and index.
-/// Address type can be "receive" or "change".
-#[server(GetAddress, "/api", "GetJson", "address")] // GetJson is a GET and will be cached
-pub async fn get_address(mnemonic: String, network: String, address_type: String, index: usize) -> Result<String, ServerFnError> {
-    // Address wrangling
-    let address_type = address_type.as_str();
-    let address_type: AddressType = match address_type {
-        "receive" => AddressType::Receive,
-        "change" => AddressType::Change,
-        &_ => AddressType::Receive, // NOTE: a good default
+/// The external and internal descriptors, with their keymaps, derived from a master extended private key.
+fn wallet_descriptors(
+    xprv: ExtendedPrivKey,
+    network: Network,
+    script_type: ScriptType,
+    derivation_path_external: &str,
+    derivation_path_internal: &str,
+) -> Result<((ExtendedDescriptor, KeyMap), (ExtendedDescriptor, KeyMap))> {
+    let secp = Secp256k1::new();
+
+    // generate derivation paths
+    let external_path = DerivationPath::from_str(derivation_path_external)
+        .map_err(|e| anyhow!("Invalid external derivation path {}: {}", derivation_path_external, e))?;
+    let internal_path = DerivationPath::from_str(derivation_path_internal)
+        .map_err(|e| anyhow!("Invalid internal derivation path {}: {}", derivation_path_internal, e))?;
+
+    // generate external and internal descriptor from the account key, with an explicit key origin
+    // NOTE: other wallets need the `[fingerprint/path]` origin to sign PSBTs for exported descriptors
+    let descriptor = |path: DerivationPath| -> Result<_> {
+        let (account_xprv, origin, path) = split_key_origin(xprv, &path)?;
+        Ok(match script_type {
+            ScriptType::Taproot => descriptor!(tr(account_xprv.into_descriptor_key(Some(origin), path)?))?,
+            ScriptType::NativeSegwit => descriptor!(wpkh(account_xprv.into_descriptor_key(Some(origin), path)?))?,
+            ScriptType::NestedSegwit => descriptor!(sh(wpkh(account_xprv.into_descriptor_key(Some(origin), path)?)))?,
+        })
     };
-    let address_index: AddressIndex = AddressIndex::Peek(index as u32);
+    let (external_descriptor, external_keymap) = descriptor(external_path)?
+        .into_wallet_descriptor(&secp, network)
+        .map_err(|e| anyhow!("Invalid external derivation path: {}", e))?;
+    let (internal_descriptor, internal_keymap) = descriptor(internal_path)?
+        .into_wallet_descriptor(&secp, network)
+        .map_err(|e| anyhow!("Invalid internal derivation path: {}", e))?;
 
-    // Create the wallet
-    let mut wallet = create_wallet(mnemonic.as_str(), network.as_str(),
-            DEFAULT_DERIVATION_PATH_EXTERNAL,
-            DEFAULT_DERIVATION_PATH_INTERNAL,
-            ).unwrap();
+    Ok((
+        (external_descriptor, external_keymap),
+        (internal_descriptor, internal_keymap),
+    ))
+}
 
-    // Get the address
-    let address = match address_type {
-        AddressType::Receive => wallet.get_address(address_index),
-        AddressType::Change => wallet.get_internal_address(address_index),
+/// Creates a wallet from an external and internal descriptor, and a network type.
+/// A multipath descriptor (`/<0;1>/*`) is expanded into both the external and internal descriptors,
+/// in which case no change descriptor should be given.
+/// Without a change descriptor the wallet is receive-only and change goes to the external keychain.
+/// A watch-only descriptor with a hardened wildcard is rejected, as its addresses can't be derived.
+pub fn create_wallet_from_descriptor(
+    descriptor: &str,
+    change_descriptor: Option<&str>,
+    network: &str,
+) -> Result<Wallet> {
+    let network = parse_network(network)?;
+
+    let (external_descriptor, internal_descriptor) = match expand_multipath(descriptor)? {
+        Some(_) if change_descriptor.is_some() => {
+            bail!("A change descriptor can't be given with a multipath descriptor")
+        }
+        Some((external, internal)) => (external, Some(internal)),
+        None => (descriptor.to_string(), change_descriptor.map(str::to_string)),
     };
-    let address = AddressInfoDef::from(address);
+    for descriptor in std::iter::once(&external_descriptor).chain(internal_descriptor.as_ref()) {
+        check_hardened_wildcard(descriptor)?;
+    }
 
-    // Serialize to JSON
-    let json = to_string(&address)?;
-    Ok(json)
+    Ok(Wallet::new_no_persist(external_descriptor.as_str(), internal_descriptor.as_deref(), network)?)
 }
 
+/// Inserts a known raw transaction in hex into the wallet, without a live Esplora,
+/// e.g. to seed a demo wallet or replay history.
+/// A confirmed transaction can't be above the wallet's chain tip, so a checkpoint must be inserted first.
+pub fn insert_external_tx(wallet: &mut Wallet, raw_hex: &str, confirmation: ConfirmationTime) -> Result<Txid> {
+    let bytes = Vec::<u8>::from_hex(raw_hex.trim()).map_err(|e| anyhow!("Invalid transaction hex: {}", e))?;
+    let tx: Transaction = deserialize(&bytes).map_err(|e| anyhow!("Invalid transaction: {}", e))?;
+    if tx.output.is_empty() {
+        bail!("Transaction {} has no outputs", tx.txid());
+    }
+    if let ConfirmationTime::Confirmed { height, .. } = confirmation {
+        match wallet.latest_checkpoint() {
+            Some(tip) if height <= tip.height => {}
+            Some(tip) => bail!("Confirmation height {} is above the chain tip {}", height, tip.height),
+            None => bail!("Wallet has no chain tip, insert a checkpoint first"),
+        }
+    }
 
-/// Returns a JSON string of the wallet's balance.
-#[server(PostSendTransaction, "/api", "Url", "send")]
-pub async fn post_send_transaction(mnemonic: String, network: String, address: String) -> Result<String, ServerFnError> {
-// Create the Esplora async client
-    let base_url = if network == "bitcoin" { DEFAULT_ESPLORA_BASE_URL_MAINNET } else { DEFAULT_ESPLORA_BASE_URL_TESTNET };
-    let esplora_client = Builder::new(base_url).build_async()?;
+    let txid = tx.txid();
+    wallet
+        .insert_tx(tx, confirmation)
+        .map_err(|e| anyhow!("Error inserting transaction {}: {:?}", txid, e))?;
+    Ok(txid)
+}
 
-    // Create the wallet
-    let mut wallet = create_wallet(mnemonic.as_str(), network.as_str(),
-            DEFAULT_DERIVATION_PATH_EXTERNAL,
-            DEFAULT_DERIVATION_PATH_INTERNAL,
-            ).unwrap();
+/// Reveals the next `count` receive addresses, so they're ready to be shown right away.
+pub fn reveal_addresses(wallet: &mut Wallet, count: usize) -> Vec<AddressInfo> {
+    (0..count).map(|_| wallet.get_address(AddressIndex::New)).collect()
+}
 
-    // Sync Wallet
-    let _ = sync_wallet(&mut wallet, &esplora_client).await;
+/// Creates a watch-only wallet from an account-level extended public key, e.g. exported at `m/84'/0'/0'`,
+/// watching its receive (`/0/*`) and change (`/1/*`) addresses.
+/// SLIP-0132 `zpub`/`vpub` keys are watched as native segwit and `ypub`/`upub` as nested segwit,
+/// while plain `xpub`/`tpub` keys default to taproot.
+pub fn create_watch_only_wallet(xpub: &str, network: &str) -> Result<Wallet> {
+    let (xpub, script_type) = normalize_slip132(xpub)?;
+    let descriptor = |keychain: u32| match script_type.unwrap_or(ScriptType::Taproot) {
+        ScriptType::Taproot => format!("tr({}/{}/*)", xpub, keychain),
+        ScriptType::NativeSegwit => format!("wpkh({}/{}/*)", xpub, keychain),
+        ScriptType::NestedSegwit => format!("sh(wpkh({}/{}/*))", xpub, keychain),
+    };
+    create_wallet_from_descriptor(&descriptor(0), Some(&descriptor(1)), network)
+}
 
-    // Create a Signed Transaction
-    // that drains all available coins to send to the given address
-    let psbt = create_signed_transaction(&mut wallet, address.as_str(), &esplora_client).await.unwrap();
+/// Whether the wallet can sign transactions, i.e. its descriptors contain secret keys,
+/// or is watch-only.
+pub fn can_sign(wallet: &Wallet) -> bool {
+    [KeychainKind::External, KeychainKind::Internal]
+        .into_iter()
+        .any(|keychain| !wallet.get_signers(keychain).signers().is_empty())
+}
 
-    // Broadcast the Signed Transaction
-    let tx = broadcast_signed_transaction(psbt, &esplora_client).await.unwrap();
+/// Renders a spending policy item as plain English, e.g. "2 of 3 signatures required".
+fn render_policy(item: &SatisfiableItem) -> String {
+    match item {
+        SatisfiableItem::EcdsaSignature(_) | SatisfiableItem::SchnorrSignature(_) => "single signature".to_string(),
+        SatisfiableItem::Multisig { keys, threshold } => format!("{} of {} signatures required", threshold, keys.len()),
+        SatisfiableItem::AbsoluteTimelock { value } => format!("after block height or time {}", value.to_consensus_u32()),
+        SatisfiableItem::RelativeTimelock { value } => format!("after a relative timelock of {}", value.0),
+        SatisfiableItem::Sha256Preimage { .. }
+        | SatisfiableItem::Hash256Preimage { .. }
+        | SatisfiableItem::Ripemd160Preimage { .. }
+        | SatisfiableItem::Hash160Preimage { .. } => "a hash preimage".to_string(),
+        SatisfiableItem::Thresh { items, threshold } => {
+            let items: Vec<String> = items.iter().map(|policy| render_policy(&policy.item)).collect();
+            let quantifier = match *threshold {
+                1 => "any".to_string(),
+                threshold if threshold == items.len() => "all".to_string(),
+                threshold => format!("{} of {}", threshold, items.len()),
+            };
+            format!("{} of: ({})", quantifier, items.join("; "))
+        }
+    }
+}
 
-    // Serialize to JSON
-    let json = to_string(&tx)?;
-    Ok(json)
+/// Describes the spending policy of the wallet's external descriptor in plain English,
+/// e.g. "single signature" or "2 of 3 signatures required" for a multisig.
+pub fn describe_policy(wallet: &Wallet) -> Result<String> {
+    match wallet.policies(KeychainKind::External)? {
+        Some(policy) => Ok(render_policy(&policy.item)),
+        None => bail!("The wallet's descriptor has no spending policy"),
+    }
 }
 
+/// Whether two wallets watch the same descriptors, e.g. the same wallet imported
+/// from a mnemonic and from its exported descriptors.
+/// Compares the public descriptors of both keychains, including their checksums.
+pub fn descriptors_equal(a: &Wallet, b: &Wallet) -> bool {
+    [KeychainKind::External, KeychainKind::Internal].into_iter().all(|keychain| {
+        a.get_descriptor_for_keychain(keychain).to_string() == b.get_descriptor_for_keychain(keychain).to_string()
+    })
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// A freshly generated wallet, for onboarding.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewWallet {
+    /// The backup mnemonic.
+    pub mnemonic: String,
+    pub first_address: String,
+    /// The external and internal public descriptors.
+    pub descriptors: Vec<String>,
+}
 
-    use std::any::TypeId;
+/// Generates a 12-word mnemonic and builds its wallet with the default script type,
+/// along with its first receive address and public descriptors.
+pub fn generate_new_wallet(network: &str) -> Result<NewWallet> {
+    let mnemonic: GeneratedKey<Mnemonic, Tap> = Mnemonic::generate((WordCount::Words12, Language::English))
+        .map_err(|_| anyhow!("Failed to generate a mnemonic"))?;
+    let mnemonic = mnemonic.into_key().to_string();
 
-    use bdk::wallet::{AddressIndex, Wallet};
-    use bdk::bitcoin::{
-        Txid, Transaction, PackedLockTime, BlockHash, TxOut,
-        hashes::Hash,
+    let mut wallet = create_default_wallet(&mnemonic, network)?;
+    let first_address = wallet.get_address(AddressIndex::Peek(0)).address.to_string();
+    let descriptors = [KeychainKind::External, KeychainKind::Internal]
+        .into_iter()
+        .map(|keychain| wallet.get_descriptor_for_keychain(keychain).to_string())
+        .collect();
+
+    Ok(NewWallet {
+        mnemonic,
+        first_address,
+        descriptors,
+    })
+}
+
+/// Number of addresses past the last revealed one that Bitcoin Core should watch.
+const CORE_IMPORT_RANGE_LOOKAHEAD: u32 = 1_000;
+
+/// Returns a Bitcoin Core `importdescriptors` request, as a JSON array, for the wallet's public descriptors.
+/// The rescan starts at the unix `timestamp` if given, otherwise at "now" (no rescan).
+/// A receive-only wallet exports only its external descriptor.
+pub fn export_core_import_request(wallet: &Wallet, timestamp: Option<u64>) -> String {
+    let timestamp = match timestamp {
+        Some(timestamp) => serde_json::json!(timestamp),
+        None => serde_json::json!("now"),
     };
-    use bdk_esplora::{esplora_client::{AsyncClient, Builder}, EsploraAsyncExt};
-    use bdk_chain::{BlockId, ConfirmationTime};
+    let external = wallet.get_descriptor_for_keychain(KeychainKind::External).to_string();
+    let internal = wallet.get_descriptor_for_keychain(KeychainKind::Internal).to_string();
 
-    fn is_wallet<T: ?Sized + 'static>(_s: &T) -> bool {
-       TypeId::of::<Wallet>() == TypeId::of::<T>()
+    let mut keychains = vec![(KeychainKind::External, external.clone())];
+    if internal != external {
+        keychains.push((KeychainKind::Internal, internal));
     }
+    let requests: Vec<serde_json::Value> = keychains
+        .into_iter()
+        .map(|(keychain, desc)| {
+            let next_index = wallet.spk_index().last_revealed_index(&keychain).map_or(0, |index| index + 1);
+            serde_json::json!({
+                "desc": desc,
+                "active": true,
+                "internal": keychain == KeychainKind::Internal,
+                "timestamp": timestamp,
+                "range": [0, next_index + CORE_IMPORT_RANGE_LOOKAHEAD - 1],
+            })
+        })
+        .collect();
+    serde_json::Value::Array(requests).to_string()
+}
 
-    fn is_psbt<T: ?Sized + 'static>(_s: &T) -> bool {
-        TypeId::of::<PartiallySignedTransaction>() == TypeId::of::<T>()
+/// Stop gaps for the external and internal keychains.
+/// Change addresses are only revealed when sending, so the internal keychain rarely needs a large gap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StopGaps {
+    pub external: usize,
+    pub internal: usize,
+}
+
+impl Default for StopGaps {
+    fn default() -> Self {
+        Self {
+            external: 5,
+            internal: 5,
+        }
     }
+}
 
-    /// Return a fake wallet that appears to be funded for testing.
-    pub fn get_funded_wallet_with_change(
-        mnemonic: &str,
-        derivation_path_external: &str,
-        derivation_path_internal: &str,
-    ) -> (Wallet, Txid) {
-        let mut wallet = create_wallet(
-            mnemonic,
-            "regtest",
-            derivation_path_external,
-            derivation_path_internal,
-        ).unwrap();
+impl StopGaps {
+    /// The stop gap of a keychain.
+    pub fn for_keychain(&self, keychain: KeychainKind) -> usize {
+        match keychain {
+            KeychainKind::External => self.external,
+            KeychainKind::Internal => self.internal,
+        }
+    }
+}
 
-        let address = wallet.get_address(AddressIndex::New).address;
+/// The outcome of a sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncResult {
+    /// Whether the wallet changed.
+    pub changed: bool,
+    /// Whether the sync replaced blocks of the wallet's previous chain, e.g. to warn the user
+    /// that some of their confirmed transactions are unconfirmed again.
+    pub reorg_detected: bool,
+}
 
-        let tx = Transaction {
-            version: 1,
-            lock_time: PackedLockTime(0),
-            input: vec![],
-            output: vec![TxOut {
-                value: 50_000,
-                script_pubkey: address.script_pubkey(),
-            }],
-        };
+/// The wallet's checkpoints and confirmed transactions at a point in time, to detect reorgs across a sync.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainSnapshot {
+    checkpoints: BTreeMap<u32, BlockHash>,
+    confirmed: HashSet<Txid>,
+}
 
-        wallet
-            .insert_checkpoint(BlockId {
-                height: 1_000,
-                hash: BlockHash::all_zeros(),
-            })
-            .unwrap();
-        wallet
-            .insert_tx(
-                tx.clone(),
-                ConfirmationTime::Confirmed {
-                    height: 1_000,
-                    time: 100,
-                },
-            )
-            .unwrap();
+impl ChainSnapshot {
+    /// Takes a snapshot of the wallet's chain.
+    pub fn of<D>(wallet: &Wallet<D>) -> Self {
+        let confirmed = wallet
+            .transactions()
+            .filter(|tx| tx.observed_as.is_confirmed())
+            .map(|tx| tx.node.txid)
+            .collect();
+        Self {
+            checkpoints: wallet.checkpoints().clone(),
+            confirmed,
+        }
+    }
 
-        (wallet, tx.txid())
+    /// Whether the chain was reorganized since this snapshot:
+    /// a checkpoint's block was replaced, or a confirmed transaction became unconfirmed.
+    pub fn reorg_since(&self, after: &ChainSnapshot) -> bool {
+        let replaced_block = self
+            .checkpoints
+            .iter()
+            .any(|(height, hash)| after.checkpoints.get(height).is_some_and(|after_hash| after_hash != hash));
+        replaced_block || !self.confirmed.is_subset(&after.confirmed)
     }
+}
 
-    #[test]
-    fn test_create_wallet(){
-        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon cactus";
+/// Default number of SPKs scanned per Esplora scan call, enough for the default stop gaps in one call.
+const DEFAULT_SCAN_BATCH_SIZE: usize = 100;
+
+/// How a sync scans the wallet's keychains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncConfig {
+    pub stop_gaps: StopGaps,
+    /// Number of SPKs scanned per Esplora scan call. Smaller batches keep less in memory at once
+    /// on constrained backends, at the cost of more calls.
+    pub batch_size: usize,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            stop_gaps: StopGaps::default(),
+            batch_size: DEFAULT_SCAN_BATCH_SIZE,
+        }
+    }
+}
+
+/// Scans the SPKs of keychains for their transactions, i.e. an Esplora client.
+/// Syncs are generic over it so that tests can stand in for Esplora and record the scans.
+pub trait Scanner {
+    /// The base URL of the Esplora server, to look up its concurrency.
+    fn url(&self) -> &str;
+
+    /// Scans the SPKs of each keychain, stopping a keychain's scan after `stop_gap` SPKs without transactions.
+    fn scan_keychains(
+        &self,
+        local_chain: &BTreeMap<u32, BlockHash>,
+        keychain_spks: BTreeMap<KeychainKind, Vec<(u32, Script)>>,
+        stop_gap: usize,
+        parallel_requests: usize,
+    ) -> impl Future<Output = Result<Update>> + Send;
+}
+
+impl Scanner for AsyncClient {
+    fn url(&self) -> &str {
+        AsyncClient::url(self)
+    }
+
+    async fn scan_keychains(
+        &self,
+        local_chain: &BTreeMap<u32, BlockHash>,
+        keychain_spks: BTreeMap<KeychainKind, Vec<(u32, Script)>>,
+        stop_gap: usize,
+        parallel_requests: usize,
+    ) -> Result<Update> {
+        let update = RateLimitedClient::new(self, rate_limiter())
+            .scan(local_chain, keychain_spks, stop_gap, parallel_requests)
+            .await
+            .map_err(WalletError::from)?;
+        Ok(update)
+    }
+}
+
+/// Sync a wallet with the Esplora client.
+pub async fn sync_wallet(wallet: &mut Wallet, client: &impl Scanner, config: SyncConfig) -> Result<SyncResult> {
+    sync_wallet_with_stop_gaps(wallet, client, config.stop_gaps, config.batch_size).await
+}
+
+/// Splits the scan of a keychain into batches of SPK indices, so a large stop gap isn't scanned in a single call.
+/// The scan ends once the batches reach the stop gap past the last used SPK.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanBatches {
+    next: u32,
+    batch_size: u32,
+    stop_gap: u32,
+    started: bool,
+}
+
+impl ScanBatches {
+    /// Batches of `batch_size` SPKs from index `start`.
+    pub fn new(start: u32, batch_size: usize, stop_gap: usize) -> Self {
+        Self {
+            next: start,
+            batch_size: batch_size.max(1) as u32,
+            stop_gap: stop_gap as u32,
+            started: false,
+        }
+    }
+
+    /// The next batch of SPK indices to scan, given the index after the last used SPK so far (0 if none),
+    /// or `None` once the scan reached the stop gap.
+    pub fn next_batch(&mut self, next_unused: u32) -> Option<Range<u32>> {
+        if self.started && self.next >= next_unused.saturating_add(self.stop_gap) {
+            return None;
+        }
+        self.started = true;
+        let batch = self.next..self.next.saturating_add(self.batch_size);
+        self.next = batch.end;
+        Some(batch)
+    }
+}
+
+/// A planned scan of one keychain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanRequest {
+    pub keychain: KeychainKind,
+    pub stop_gap: usize,
+    pub parallel_requests: usize,
+    /// Number of SPKs scanned per call.
+    pub batch_size: usize,
+}
+
+/// Plans the scan of each of the wallet's keychains against the Esplora server at `base_url`,
+/// with the server's configured concurrency, in batches of `batch_size` SPKs.
+pub fn scan_requests<D>(wallet: &Wallet<D>, base_url: &str, stop_gaps: StopGaps, batch_size: usize) -> Vec<ScanRequest> {
+    let parallel_requests = concurrency_config().for_url(base_url);
+    wallet
+        .spks_of_all_keychains()
+        .into_keys()
+        .map(|keychain| ScanRequest {
+            keychain,
+            stop_gap: stop_gaps.for_keychain(keychain),
+            parallel_requests,
+            batch_size,
+        })
+        .collect()
+}
+
+/// The index after a keychain's last revealed SPK, which after applying a scan update is the last used one.
+fn next_unused_index<D>(wallet: &Wallet<D>, keychain: KeychainKind) -> u32 {
+    wallet.spk_index().last_revealed_index(&keychain).map_or(0, |index| index + 1)
+}
+
+/// Sync a wallet with the Esplora client, using a separate stop gap for each keychain.
+/// Each keychain is scanned on its own with its own SPK iterator, in batches of `batch_size` SPKs.
+pub async fn sync_wallet_with_stop_gaps(wallet: &mut Wallet, client: &impl Scanner, stop_gaps: StopGaps, batch_size: usize) -> Result<SyncResult> {
+    let before = ChainSnapshot::of(wallet);
+    for request in scan_requests(wallet, client.url(), stop_gaps, batch_size) {
+        let keychain = request.keychain;
+        let mut batches = ScanBatches::new(0, request.batch_size, request.stop_gap);
+        while let Some(batch) = batches.next_batch(next_unused_index(wallet, keychain)) {
+            let local_chain = wallet.checkpoints();
+            let keychain_spks: BTreeMap<_, Vec<_>> = wallet
+                .spks_of_all_keychains()
+                .into_iter()
+                .filter(|(k, _)| *k == keychain)
+                .map(|(k, spks)| (k, spks.skip(batch.start as usize).take(batch.len()).collect()))
+                .collect();
+
+            let update = client
+                .scan_keychains(local_chain, keychain_spks, request.stop_gap, request.parallel_requests)
+                .await?;
+            wallet.apply_update(update)?;
+        }
+    }
+    let reorg_detected = before.reorg_since(&ChainSnapshot::of(wallet));
+    if reorg_detected {
+        log::warn!("Reorg detected while syncing, some confirmed transactions may be unconfirmed again");
+    }
+    Ok(SyncResult {
+        changed: wallet.commit()?,
+        reorg_detected,
+    })
+}
+
+/// Number of SPKs a resumable sync scans between two saves of its progress.
+const SYNC_PROGRESS_BATCH: usize = 50;
+
+/// The index a resumable sync of a keychain starts scanning from:
+/// where an interrupted sync left off, or 0 if there is none.
+pub fn resume_index(progress: &SyncProgress, keychain: KeychainKind) -> u32 {
+    progress.get(&keychain).copied().unwrap_or(0)
+}
+
+/// Sync a wallet backed by a store, saving the sync's progress in the store after each batch of SPKs.
+/// A long first sync that's interrupted resumes from the last saved index instead of rescanning from zero,
+/// as the transactions found so far are committed to the store with the progress.
+pub async fn resumable_sync_wallet(
+    wallet: &mut Wallet<StoreBackend<'_>>,
+    client: &impl Scanner,
+    store: &dyn WalletStore,
+    stop_gaps: StopGaps,
+) -> Result<SyncResult> {
+    // NOTE: keyed like the wallet's changesets, by the public external descriptor
+    let descriptor = wallet.get_descriptor_for_keychain(KeychainKind::External).to_string();
+    let mut progress = store.load_sync_progress(&descriptor).unwrap_or_default();
+    let before = ChainSnapshot::of(wallet);
+    let mut changed = false;
+    for request in scan_requests(wallet, client.url(), stop_gaps, SYNC_PROGRESS_BATCH) {
+        let keychain = request.keychain;
+        let mut batches = ScanBatches::new(resume_index(&progress, keychain), request.batch_size, request.stop_gap);
+        while let Some(batch) = batches.next_batch(next_unused_index(wallet, keychain)) {
+            let keychain_spks: BTreeMap<_, Vec<_>> = wallet
+                .spks_of_all_keychains()
+                .into_iter()
+                .filter(|(k, _)| *k == keychain)
+                .map(|(k, spks)| (k, spks.skip(batch.start as usize).take(batch.len()).collect()))
+                .collect();
+
+            let update = client
+                .scan_keychains(wallet.checkpoints(), keychain_spks, request.stop_gap, request.parallel_requests)
+                .await?;
+            wallet.apply_update(update)?;
+            changed |= wallet.commit()?;
+
+            progress.insert(keychain, batch.end);
+            store.persist_sync_progress(&descriptor, Some(progress.clone()))?;
+        }
+    }
+    store.persist_sync_progress(&descriptor, None)?;
+
+    let reorg_detected = before.reorg_since(&ChainSnapshot::of(wallet));
+    if reorg_detected {
+        log::warn!("Reorg detected while syncing, some confirmed transactions may be unconfirmed again");
+    }
+    Ok(SyncResult { changed, reorg_detected })
+}
+
+/// The SPKs of each keychain up to its last revealed index, skipping keychains with no revealed SPKs.
+pub fn revealed_spks(wallet: &Wallet) -> BTreeMap<KeychainKind, Vec<(u32, Script)>> {
+    wallet
+        .spks_of_all_keychains()
+        .into_iter()
+        .filter_map(|(keychain, spks)| {
+            let revealed = wallet.spk_index().last_revealed_index(&keychain)? as usize + 1;
+            Some((keychain, spks.take(revealed).collect()))
+        })
+        .collect()
+}
+
+/// Quickly refresh a wallet's balance and confirmations with the Esplora client.
+/// Unlike a full sync, only the already revealed SPKs are scanned, without discovering new addresses
+/// past them, which trades completeness for speed on an established wallet.
+pub async fn refresh_wallet(wallet: &mut Wallet, client: &impl Scanner) -> Result<SyncResult> {
+    let before = ChainSnapshot::of(wallet);
+    let keychain_spks = revealed_spks(wallet);
+    // NOTE: a stop gap of the revealed count never scans past the revealed SPKs
+    let stop_gap = keychain_spks.values().map(Vec::len).max().unwrap_or(0);
+    if stop_gap > 0 {
+        let update = client
+            .scan_keychains(wallet.checkpoints(), keychain_spks, stop_gap, concurrency_config().for_url(client.url()))
+            .await?;
+        wallet.apply_update(update)?;
+    }
+    Ok(SyncResult {
+        reorg_detected: before.reorg_since(&ChainSnapshot::of(wallet)),
+        changed: wallet.commit()?,
+    })
+}
+
+/// Roughly estimates how long a full sync takes: one lookup per SPK up to each keychain's stop gap,
+/// made `parallel_requests` at a time, each taking `latency`.
+/// This is a best-effort lower bound, as used addresses extend the scan.
+pub fn estimate_sync_time(keychains: &[KeychainKind], stop_gaps: StopGaps, latency: Duration, parallel_requests: usize) -> Duration {
+    let batches: usize = keychains
+        .iter()
+        .map(|keychain| stop_gaps.for_keychain(*keychain).div_ceil(parallel_requests.max(1)))
+        .sum();
+    // NOTE: plus one request for the chain tip
+    latency * (batches as u32 + 1)
+}
+
+/// Measures the latency of a request to Esplora with a quick probe.
+pub async fn measure_latency(client: &AsyncClient) -> Result<Duration> {
+    rate_limiter().acquire().await;
+    let start = Instant::now();
+    client.get_height().await.map_err(WalletError::from)?;
+    Ok(start.elapsed())
+}
+
+/// Reset the wallet's checkpoint to a given block.
+fn reset_checkpoint(wallet: &mut Wallet, block: BlockId) -> Result<bool> {
+    Ok(wallet.insert_checkpoint(block)?)
+}
+
+/// Fully rescans a wallet, anchoring its chain at the block at `height` first,
+/// with the block hash fetched from Esplora.
+/// The height doesn't bound the scan: Esplora looks up each script's whole history,
+/// so transactions confirmed below `height` are found as well.
+pub async fn rescan_wallet_at_height(wallet: &mut Wallet, client: &AsyncClient, height: u32) -> Result<SyncResult> {
+    rate_limiter().acquire().await;
+    let hash = client.get_block_hash(height).await?;
+    rescan_wallet_at_block(wallet, client, BlockId { height, hash }).await
+}
+
+/// Inserts `block` as a checkpoint and then fully rescans the wallet.
+/// NOTE: the server fns create a fresh, unpersisted wallet per request, so there are no stale
+/// checkpoints or transactions to reset: the scan rediscovers the wallet's transactions from scratch.
+async fn rescan_wallet_at_block(wallet: &mut Wallet, client: &impl Scanner, block: BlockId) -> Result<SyncResult> {
+    reset_checkpoint(wallet, block)?;
+    sync_wallet(wallet, client, SyncConfig::default()).await
+}
+
+/// Returns the wallet's latest checkpoint, if any.
+pub fn get_chain_tip(wallet: &Wallet) -> Option<ChainTip> {
+    wallet.latest_checkpoint().map(|block| ChainTip {
+        height: block.height,
+        hash: block.hash.to_string(),
+    })
+}
+
+/// Get the fee rates by confirmation target in blocks from the Esplora server.
+pub async fn get_fee_map(client: &AsyncClient) -> Result<HashMap<String, f64>> {
+    // NOTE: cached for a short time, fee estimates change at most every block
+    fee_cache()
+        .get_or_fetch(client.url(), || async {
+            rate_limiter().acquire().await;
+            let fee_estimates: HashMap<String, f64> = client.get_fee_estimates().await.map_err(WalletError::from)?;
+            Ok(fee_estimates)
+        })
+        .await
+}
+
+/// Get the fee estimates from the Esplora server.
+/// The default block is 1, which is the next block.
+pub async fn get_fee_estimates(client: &AsyncClient, block: Option<usize>) -> Result<f32> {
+    let fee_estimates = get_fee_map(client).await?;
+
+    // NOTE: if block is not specified, use the next block
+    fee_rate_for_target(&fee_estimates, block.unwrap_or(1))
+}
+
+/// Picks the fee rate for a confirmation target in blocks from Esplora's fee estimates.
+/// The estimates are sparse, so a missing target falls back to the closest lower target,
+/// which confirms at least as fast.
+pub fn fee_rate_for_target(fee_estimates: &HashMap<String, f64>, target: usize) -> Result<f32> {
+    let estimates = fee_estimates
+        .iter()
+        .filter_map(|(block, fee_rate)| block.parse::<usize>().ok().map(|block| (block, *fee_rate)));
+    let estimate = estimates
+        .clone()
+        .filter(|(block, _)| *block <= target)
+        .max_by_key(|(block, _)| *block)
+        .or_else(|| estimates.min_by_key(|(block, _)| *block));
+    match estimate {
+        Some((_, fee_rate)) => Ok(fee_rate as f32),
+        None => bail!("No fee estimates available"),
+    }
+}
+
+/// Named fee rates in sat/vB for the send screen.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeePresets {
+    /// About a day, 144 blocks.
+    pub slow: f32,
+    /// About an hour, 6 blocks.
+    pub normal: f32,
+    /// The next block.
+    pub fast: f32,
+}
+
+/// Derives the slow, normal and fast presets from a fee map, using the nearest available target for missing ones.
+/// The presets are kept monotonic (slow <= normal <= fast) even if the estimates aren't.
+pub fn fee_presets(fee_estimates: &HashMap<String, f64>) -> Result<FeePresets> {
+    let fast = fee_rate_for_target(fee_estimates, 1)?;
+    let normal = fee_rate_for_target(fee_estimates, 6)?.min(fast);
+    let slow = fee_rate_for_target(fee_estimates, 144)?.min(normal);
+    Ok(FeePresets { slow, normal, fast })
+}
+
+/// The fee presets, with the fee previews of a send at each of them if one was given.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeePreview {
+    #[serde(flatten)]
+    pub presets: FeePresets,
+    pub estimates: Option<FeePresetEstimates>,
+}
+
+/// The fee previews of a send at the slow, normal and fast presets.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeePresetEstimates {
+    pub slow: FeeEstimate,
+    pub normal: FeeEstimate,
+    pub fast: FeeEstimate,
+}
+
+/// Previews the fees of sending `amount` to `address`, or all available coins if no amount is given,
+/// at each of the presets, from the wallet's actual coin selection.
+pub fn preview_fees(wallet: &mut Wallet, presets: FeePresets, address: &str, amount: Option<u64>) -> Result<FeePreview> {
+    let estimates = FeePresetEstimates {
+        slow: estimate_transaction_fee(wallet, address, amount, presets.slow)?,
+        normal: estimate_transaction_fee(wallet, address, amount, presets.normal)?,
+        fast: estimate_transaction_fee(wallet, address, amount, presets.fast)?,
+    };
+    Ok(FeePreview { presets, estimates: Some(estimates) })
+}
+
+/// Fee to pay for a transaction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fee {
+    /// Fee rate in sat/vB.
+    Rate(f32),
+    /// Absolute fee in sats.
+    Absolute(u64),
+}
+
+/// Where to place the change output among the transaction's outputs.
+/// The default leaves it to the output ordering rather than randomizing it: a BIP69-sorted change output
+/// is already placed by its amount and script alone, while moving it elsewhere would give away
+/// that the transaction wasn't sorted and single out the moved output as the change.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangePosition {
+    /// Leave the placement to the output ordering: sorted per BIP69 by default, or shuffled with BIP69 ordering off.
+    #[default]
+    Ordered,
+    /// Move the change output to a random index.
+    Random,
+    First,
+    Last,
+}
+
+/// Options for building a transaction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TxOptions {
+    /// Set the nLockTime to the current chain tip height (anti-fee-sniping).
+    pub anti_fee_sniping: bool,
+    /// Fee rate in sat/vB, estimated from Esplora if neither this nor `absolute_fee_sat` is set.
+    pub fee_rate: Option<f32>,
+    /// Absolute fee in sats, mutually exclusive with `fee_rate`.
+    pub absolute_fee_sat: Option<u64>,
+    /// Grind for low-R ECDSA signatures, saving a byte per signature about half of the time.
+    /// This has no effect on taproot's fixed-size Schnorr signatures.
+    pub allow_grinding: bool,
+    /// Sort inputs and outputs lexicographically (BIP69) instead of shuffling them.
+    pub bip69_ordering: bool,
+    /// Confirmation target in blocks for the estimated fee rate, the next block if not set.
+    pub confirmation_target: Option<usize>,
+    /// Where to place the change output, applied after the output ordering.
+    pub change_position: ChangePosition,
+    /// Don't spend the wallet's own unconfirmed change, avoiding chains of unconfirmed transactions.
+    /// Confirmed change and unconfirmed incoming coins can still be spent.
+    pub avoid_unconfirmed_change: bool,
+    /// The exact nSequence of every input, e.g. to satisfy a relative timelock (CSV).
+    /// It must still signal RBF, the default RBF sequence is used if not set.
+    pub sequence: Option<u32>,
+    /// Attach a P2A anchor output, so that a child can fee bump the transaction with CPFP.
+    /// Requires the `p2a-anchors` feature.
+    pub anchor_output: bool,
+}
+
+impl TxOptions {
+    /// Default options for a given network.
+    /// Anti-fee-sniping is only on by default for mainnet.
+    pub fn for_network(network: Network) -> Self {
+        Self {
+            anti_fee_sniping: network == Network::Bitcoin,
+            fee_rate: None,
+            absolute_fee_sat: None,
+            allow_grinding: true,
+            bip69_ordering: true,
+            confirmation_target: None,
+            change_position: ChangePosition::default(),
+            avoid_unconfirmed_change: false,
+            sequence: None,
+            anchor_output: false,
+        }
+    }
+
+    /// The input/output ordering to build the transaction with.
+    pub fn ordering(&self) -> TxOrdering {
+        if self.bip69_ordering {
+            TxOrdering::Bip69Lexicographic
+        } else {
+            TxOrdering::Shuffle
+        }
+    }
+
+    /// The options to sign the transaction with.
+    pub fn sign_options(&self) -> SignOptions {
+        SignOptions {
+            allow_grinding: self.allow_grinding,
+            ..SignOptions::default()
+        }
+    }
+
+    /// The fee set in the options, if any.
+    /// Returns an error if both a fee rate and an absolute fee are set.
+    pub fn fee(&self) -> Result<Option<Fee>> {
+        match (self.fee_rate, self.absolute_fee_sat) {
+            (Some(_), Some(_)) => bail!("Fee rate and absolute fee are mutually exclusive"),
+            (Some(fee_rate), None) => Ok(Some(Fee::Rate(fee_rate))),
+            (None, Some(fee)) => Ok(Some(Fee::Absolute(fee))),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Lists the wallet's coinbase outputs with less than 100 confirmations at the wallet's chain tip,
+/// which can't be spent yet and are counted in the balance as immature.
+pub fn immature_coinbase_outpoints(wallet: &Wallet) -> Vec<OutPoint> {
+    let tip_height = match wallet.latest_checkpoint() {
+        Some(block) => block.height,
+        None => return Vec::new(),
+    };
+    wallet
+        .list_unspent()
+        .filter(|utxo| match utxo.confirmation_time {
+            // NOTE: an unconfirmed coinbase is never valid, it can't be spent either
+            ConfirmationTime::Confirmed { height, .. } => tip_height.saturating_sub(height) + 1 < COINBASE_MATURITY,
+            ConfirmationTime::Unconfirmed { .. } => true,
+        })
+        .filter(|utxo| {
+            wallet
+                .get_tx(utxo.outpoint.txid, true)
+                .and_then(|details| details.transaction)
+                .is_some_and(|tx| tx.is_coin_base())
+        })
+        .map(|utxo| utxo.outpoint)
+        .collect()
+}
+
+/// The UTXOs worth less than the fee of spending them as an input at `fee_rate` (sat/vB),
+/// which are effectively dust, e.g. to suggest cleaning them up while fees are low.
+/// The input is sized by the script type of the wallet's descriptor.
+pub fn find_uneconomical_utxos(wallet: &Wallet, fee_rate: f32) -> Result<Vec<OutPoint>> {
+    let input_weight = input_weight(ScriptType::of_wallet(wallet)?);
+    let input_fee = (weight_to_vbytes(input_weight) as f32 * fee_rate).ceil() as u64;
+    Ok(wallet
+        .list_unspent()
+        .filter(|utxo| utxo.txout.value < input_fee)
+        .map(|utxo| utxo.outpoint)
+        .collect())
+}
+
+/// Build an unsigned transaction from a wallet to send to a given address.
+/// If `amount` is `None` all available coins are sent, otherwise the change goes back to the wallet.
+/// If `current_height` is given the nLockTime is set to it, otherwise it is set to 0.
+/// A nLockTime equal to the chain tip is already final for the next block,
+/// so the transaction remains spendable immediately.
+/// Inputs and outputs are ordered before signing, according to `options`.
+pub fn build_transaction(
+    wallet: &mut Wallet,
+    address: &str,
+    amount: Option<u64>,
+    fee: Fee,
+    current_height: Option<u32>,
+    options: &TxOptions,
+) -> Result<PartiallySignedTransaction> {
+    build_transaction_excluding(wallet, address, amount, fee, current_height, options, Vec::new())
+}
+
+/// Build an unsigned transaction like `build_transaction`, skipping the UTXOs reserved in `locks`.
+/// The UTXOs it spends are then reserved while the returned guard lives, or past it if kept,
+/// until released on broadcast or until the lock times out.
+pub fn build_locked_transaction<'l>(
+    wallet: &mut Wallet,
+    address: &str,
+    amount: Option<u64>,
+    fee: Fee,
+    current_height: Option<u32>,
+    options: &TxOptions,
+    locks: &'l UtxoLocks,
+) -> Result<(PartiallySignedTransaction, UtxoLockGuard<'l>)> {
+    for _ in 0..MAX_LOCK_ATTEMPTS {
+        let psbt = build_transaction_excluding(wallet, address, amount, fee, current_height, options, locks.locked())?;
+        // NOTE: a concurrent transaction may have reserved some of the UTXOs since, then select again
+        if let Some(guard) = locks.try_lock(psbt.unsigned_tx.input.iter().map(|input| input.previous_output)) {
+            return Ok((psbt, guard));
+        }
+    }
+    bail!("The UTXOs kept being reserved by concurrent transactions, try again")
+}
+
+fn build_transaction_excluding(
+    wallet: &mut Wallet,
+    address: &str,
+    amount: Option<u64>,
+    fee: Fee,
+    current_height: Option<u32>,
+    options: &TxOptions,
+    mut unspendable: Vec<OutPoint>,
+) -> Result<PartiallySignedTransaction> {
+    let address = Address::from_str(address)?;
+
+    if options.avoid_unconfirmed_change {
+        unspendable.extend(
+            wallet
+                .list_unspent()
+                .filter(|utxo| utxo.keychain == KeychainKind::Internal)
+                .filter(|utxo| matches!(utxo.confirmation_time, ConfirmationTime::Unconfirmed { .. }))
+                .map(|utxo| utxo.outpoint),
+        );
+    }
+
+    // Immature coinbase outputs can't be spent yet
+    let immature = immature_coinbase_outpoints(wallet);
+    if !immature.is_empty() && wallet.list_unspent().all(|utxo| immature.contains(&utxo.outpoint) || unspendable.contains(&utxo.outpoint)) {
+        bail!("The only available coins are coinbase outputs with less than {} confirmations", COINBASE_MATURITY);
+    }
+    unspendable.extend(immature);
+
+    // A drain pays its fee from the balance, check upfront that it covers the fee
+    if amount.is_none() {
+        let spendable: Vec<u64> = wallet
+            .list_unspent()
+            .filter(|utxo| !unspendable.contains(&utxo.outpoint))
+            .map(|utxo| utxo.txout.value)
+            .collect();
+        let balance: u64 = spendable.iter().sum();
+        let estimated_fee = match fee {
+            // NOTE: sized by the wallet's script type, for the output as well
+            Fee::Rate(fee_rate) => {
+                let vbytes = estimate_vsize(ScriptType::of_wallet(wallet)?, spendable.len(), 1);
+                (vbytes as f32 * fee_rate).ceil() as u64
+            }
+            Fee::Absolute(fee) => fee,
+        };
+        if balance <= estimated_fee {
+            bail!(
+                "Balance too low to cover fee: the balance is {} sats, the fee is {} sats, short by {} sats",
+                balance, estimated_fee, estimated_fee - balance
+            );
+        }
+    }
+
+    let anchor = if options.anchor_output { Some(p2a_anchor_output()?) } else { None };
+
+    let mut tx_builder = wallet.build_tx();
+    tx_builder.unspendable(unspendable);
+    if let Some(anchor) = anchor {
+        tx_builder.add_recipient(anchor.script_pubkey, anchor.value);
+    }
+    match amount {
+        Some(amount) => tx_builder.add_recipient(address.script_pubkey(), amount),
+        // Spend all outputs in this wallet,
+        // sending the excess (which is all the coins minus the fee) to this address.
+        None => tx_builder.drain_wallet().drain_to(address.script_pubkey()),
+    };
+    match options.sequence {
+        Some(sequence) => tx_builder.enable_rbf_with_sequence(validate_rbf_sequence(sequence)?),
+        None => tx_builder.enable_rbf(),
+    };
+    tx_builder.ordering(options.ordering());
+    match fee {
+        Fee::Rate(fee_rate) => tx_builder.fee_rate(FeeRate::from_sat_per_vb(fee_rate)),
+        Fee::Absolute(fee) => tx_builder.fee_absolute(fee),
+    };
+    match current_height {
+        Some(height) => tx_builder.current_height(height).nlocktime(LockTime::from_height(height)?),
+        None => tx_builder.nlocktime(LockTime::ZERO),
+    };
+
+    let (mut psbt, _) = tx_builder.finish().map_err(|e| anyhow!("Error creating transaction: {}", e))?;
+    place_change_output(wallet, &mut psbt, options.change_position);
+    Ok(psbt)
+}
+
+/// Moves the change output of an unsigned transaction to the given position.
+/// The PSBT outputs are moved along with the transaction outputs, so it must be done before signing.
+fn place_change_output(wallet: &Wallet, psbt: &mut PartiallySignedTransaction, position: ChangePosition) {
+    let change_index = match identify_change_output(wallet, psbt) {
+        Some(index) => index,
+        None => return,
+    };
+    let target_index = match position {
+        ChangePosition::Ordered => return,
+        ChangePosition::Random => OsRng.next_u32() as usize % psbt.unsigned_tx.output.len(),
+        ChangePosition::First => 0,
+        ChangePosition::Last => psbt.unsigned_tx.output.len() - 1,
+    };
+
+    let output = psbt.unsigned_tx.output.remove(change_index);
+    psbt.unsigned_tx.output.insert(target_index, output);
+    let output = psbt.outputs.remove(change_index);
+    psbt.outputs.insert(target_index, output);
+}
+
+/// How to handle a recipient listed more than once in a batch send.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DuplicateRecipients {
+    /// Merge them into a single output, summing their amounts.
+    #[default]
+    Merge,
+    /// Reject the batch, since it's likely a user error.
+    Reject,
+}
+
+/// Parses recipient addresses for a network and deduplicates them by script,
+/// so that differently written addresses of the same script count as duplicates.
+/// Recipients keep the order of their first appearance.
+pub fn canonicalize_recipients(
+    recipients: &[(String, u64)],
+    network: Network,
+    duplicates: DuplicateRecipients,
+) -> Result<Vec<(Script, u64)>> {
+    let mut canonical: Vec<(Script, u64)> = Vec::with_capacity(recipients.len());
+    for (address, amount) in recipients {
+        let script_pubkey = parse_address_for_network(address, network)?.script_pubkey();
+        match canonical.iter_mut().find(|(script, _)| *script == script_pubkey) {
+            Some(_) if duplicates == DuplicateRecipients::Reject => bail!("Duplicate recipient {}", address.trim()),
+            Some((_, total)) => {
+                *total = total.checked_add(*amount).ok_or_else(|| anyhow!("Amount overflow for recipient {}", address.trim()))?
+            }
+            None => canonical.push((script_pubkey, *amount)),
+        }
+    }
+    Ok(canonical)
+}
+
+/// Build an unsigned batch transaction paying several recipients at `fee_rate` (sat/vB),
+/// with recipients listed more than once merged or rejected according to `duplicates`.
+pub fn build_batch_transaction(
+    wallet: &mut Wallet,
+    recipients: &[(String, u64)],
+    fee_rate: f32,
+    duplicates: DuplicateRecipients,
+) -> Result<PartiallySignedTransaction> {
+    let recipients = canonicalize_recipients(recipients, wallet.network(), duplicates)?;
+    build_multi_recipient_transaction(wallet, &recipients, fee_rate, Vec::new())
+}
+
+/// Build an unsigned transaction paying several recipients at `fee_rate` (sat/vB), without spending the `unspendable` UTXOs.
+fn build_multi_recipient_transaction(
+    wallet: &mut Wallet,
+    recipients: &[(Script, u64)],
+    fee_rate: f32,
+    unspendable: Vec<OutPoint>,
+) -> Result<PartiallySignedTransaction> {
+    let mut tx_builder = wallet.build_tx();
+    tx_builder
+        .set_recipients(recipients.to_vec())
+        .unspendable(unspendable)
+        .enable_rbf()
+        .fee_rate(FeeRate::from_sat_per_vb(fee_rate));
+    let (psbt, _) = tx_builder.finish().map_err(|e| anyhow!("Error creating transaction: {}", e))?;
+    Ok(psbt)
+}
+
+/// Splits a send to many recipients into several unsigned transactions, each at most `max_vsize` vB,
+/// e.g. for payouts that would exceed the standard transaction size. Recipients are kept in order,
+/// and each transaction spends different UTXOs, so that they can all confirm.
+/// A recipient listed more than once is merged into a single output.
+/// The transactions' vsizes are estimated by the script type of the wallet's descriptor.
+pub fn split_transaction(
+    wallet: &mut Wallet,
+    recipients: &[(String, u64)],
+    fee_rate: f32,
+    max_vsize: usize,
+) -> Result<Vec<PartiallySignedTransaction>> {
+    let recipients = canonicalize_recipients(recipients, wallet.network(), DuplicateRecipients::Merge)?;
+    let script_type = ScriptType::of_wallet(wallet)?;
+
+    let mut psbts = Vec::new();
+    let mut spent: Vec<OutPoint> = Vec::new();
+    let mut remaining = recipients.as_slice();
+    while !remaining.is_empty() {
+        // shrink the batch of recipients until the transaction fits under the cap
+        let mut count = remaining.len();
+        loop {
+            let psbt = build_multi_recipient_transaction(wallet, &remaining[..count], fee_rate, spent.clone())?;
+            let vsize = estimate_vsize(script_type, psbt.unsigned_tx.input.len(), psbt.unsigned_tx.output.len());
+            if vsize <= max_vsize {
+                spent.extend(psbt.unsigned_tx.input.iter().map(|input| input.previous_output));
+                psbts.push(psbt);
+                remaining = &remaining[count..];
+                break;
+            }
+            if count == 1 {
+                bail!("A transaction paying a single recipient is {} vB, above the cap of {} vB", vsize, max_vsize);
+            }
+            count = (count * max_vsize / vsize).clamp(1, count - 1);
+        }
+    }
+    Ok(psbts)
+}
+
+/// Validates that an input sequence signals RBF, which the wallet's transactions always do.
+fn validate_rbf_sequence(sequence: u32) -> Result<Sequence> {
+    if sequence > MAX_RBF_SEQUENCE {
+        bail!("Sequence {:#x} doesn't signal RBF, it must be at most {:#x}", sequence, MAX_RBF_SEQUENCE);
+    }
+    Ok(Sequence(sequence))
+}
+
+/// Sets the exact nSequence of each input of an unsigned transaction, in order, e.g. to satisfy
+/// relative timelocks (CSV) per input. Each sequence must still signal RBF.
+pub fn set_exact_sequence(psbt: &mut PartiallySignedTransaction, sequences: &[u32]) -> Result<()> {
+    if sequences.len() != psbt.unsigned_tx.input.len() {
+        bail!("Expected {} sequences, one per input, got {}", psbt.unsigned_tx.input.len(), sequences.len());
+    }
+    for (input, sequence) in psbt.unsigned_tx.input.iter_mut().zip(sequences) {
+        input.sequence = validate_rbf_sequence(*sequence)?;
+    }
+    Ok(())
+}
+
+/// Build an unsigned transaction from a wallet using all available coins to send to a given address.
+/// Uses the default options for the wallet's network.
+pub fn build_drain_transaction(
+    wallet: &mut Wallet,
+    address: &str,
+    fee: Fee,
+    current_height: Option<u32>,
+) -> Result<PartiallySignedTransaction> {
+    let options = TxOptions::for_network(wallet.network());
+    build_transaction(wallet, address, None, fee, current_height, &options)
+}
+
+/// How a transaction's inputs are split between the recipient, the change back to the wallet, and the fee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentBreakdown {
+    pub total_input: u64,
+    pub recipient: u64,
+    pub change: u64,
+    pub fee: u64,
+}
+
+/// Breaks down a PSBT paying `address` into the amount it receives, the wallet's change and the fee.
+/// Returns an error if an input's amount is missing from the PSBT.
+pub fn payment_breakdown(wallet: &Wallet, psbt: &PartiallySignedTransaction, address: &str) -> Result<PaymentBreakdown> {
+    let recipient_script = Address::from_str(address)?.script_pubkey();
+
+    let mut total_input: u64 = 0;
+    for (index, input) in psbt.inputs.iter().enumerate() {
+        match &input.witness_utxo {
+            Some(txout) => total_input += txout.value,
+            None => bail!("Missing amount of input {}", index),
+        }
+    }
+
+    let outputs = &psbt.unsigned_tx.output;
+    let recipient = outputs.iter().filter(|output| output.script_pubkey == recipient_script).map(|output| output.value).sum();
+    let change = outputs
+        .iter()
+        .filter(|output| output.script_pubkey != recipient_script && wallet.is_mine(&output.script_pubkey))
+        .map(|output| output.value)
+        .sum();
+    let total_output: u64 = outputs.iter().map(|output| output.value).sum();
+    let fee = match total_input.checked_sub(total_output) {
+        Some(fee) => fee,
+        None => bail!("Outputs ({} sats) exceed inputs ({} sats)", total_output, total_input),
+    };
+
+    Ok(PaymentBreakdown { total_input, recipient, change, fee })
+}
+
+/// A fee preview from the wallet's actual coin selection.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    /// Number of inputs coin selection picked.
+    pub inputs: usize,
+    pub outputs: usize,
+    /// Estimated vsize once signed.
+    pub vsize: usize,
+    pub fee: u64,
+    /// Fee rate in sat/vB.
+    pub fee_rate: f32,
+}
+
+/// Previews the fee of sending `amount` to `address`, or all available coins if no amount is given,
+/// by running the real coin selection without signing, so the input count matches the final transaction.
+pub fn estimate_transaction_fee(wallet: &mut Wallet, address: &str, amount: Option<u64>, fee_rate: f32) -> Result<FeeEstimate> {
+    let options = TxOptions::for_network(wallet.network());
+    let psbt = build_transaction(wallet, address, amount, Fee::Rate(fee_rate), None, &options)?;
+    let breakdown = payment_breakdown(wallet, &psbt, address)?;
+
+    let inputs = psbt.unsigned_tx.input.len();
+    let outputs = psbt.unsigned_tx.output.len();
+    // NOTE: the unsigned transaction has no witnesses yet
+    let vsize = estimate_vsize(ScriptType::of_wallet(wallet)?, inputs, outputs);
+    Ok(FeeEstimate {
+        inputs,
+        outputs,
+        vsize,
+        fee: breakdown.fee,
+        fee_rate: breakdown.fee as f32 / vsize as f32,
+    })
+}
+
+/// Create a Signed Transaction from a wallet to send an amount to a given address,
+/// or all available coins if no amount is given.
+/// Estimate the fee using the Esplora client.
+/// Unless a fee is given in the options,
+/// tries to use fee rate such that it will be included in the next block.
+/// By default, the transaction is marked as RBF.
+pub async fn create_signed_transaction(
+    wallet: &mut Wallet,
+    address: &str,
+    amount: Option<u64>,
+    client: &AsyncClient,
+    options: TxOptions,
+) -> Result<PartiallySignedTransaction> {
+    let fee = match options.fee()? {
+        Some(fee) => fee,
+        None => Fee::Rate(get_fee_estimates(client, options.confirmation_target).await?),
+    };
+    let current_height = if options.anti_fee_sniping {
+        rate_limiter().acquire().await;
+        Some(client.get_height().await?)
+    } else {
+        None
+    };
+
+    let (mut psbt, guard) = build_locked_transaction(wallet, address, amount, fee, current_height, &options, utxo_locks())?;
+    wallet
+        .sign(&mut psbt, options.sign_options())
+        .map_err(|e| anyhow!("Error signing transaction: {}", e))?;
+    // NOTE: reserved until broadcast
+    guard.keep();
+    Ok(psbt)
+}
+
+/// A P2A (pay-to-anchor) output, `OP_1 <0x4e73>`, that anyone can spend to fee bump its transaction with CPFP.
+/// NOTE: ephemeral zero-value anchors need v3 transactions, which the pinned BDK can't build,
+/// so the anchor carries its dust threshold instead.
+#[cfg(feature = "p2a-anchors")]
+pub fn p2a_anchor_output() -> Result<TxOut> {
+    let script_pubkey = bdk::bitcoin::blockdata::script::Builder::new()
+        .push_opcode(bdk::bitcoin::blockdata::opcodes::all::OP_PUSHNUM_1)
+        .push_slice(&[0x4e, 0x73])
+        .into_script();
+    Ok(TxOut {
+        value: script_pubkey.dust_value().to_sat(),
+        script_pubkey,
+    })
+}
+
+/// A P2A (pay-to-anchor) output.
+/// Always returns an `UnsupportedFeature` error since the `p2a-anchors` feature is off.
+#[cfg(not(feature = "p2a-anchors"))]
+pub fn p2a_anchor_output() -> Result<TxOut> {
+    Err(WalletError::UnsupportedFeature("the p2a-anchors feature is disabled".to_string()).into())
+}
+
+/// Send an amount to a BIP352 silent payment address.
+/// NOTE: the pinned BDK has no silent payments support yet,
+/// so this only sets the API shape and always returns an `UnsupportedFeature` error.
+#[cfg(feature = "silent-payments")]
+pub fn send_to_silent_payment(
+    _wallet: &mut Wallet,
+    sp_address: &str,
+    _amount: u64,
+) -> Result<PartiallySignedTransaction> {
+    if !(sp_address.starts_with("sp1") || sp_address.starts_with("tsp1")) {
+        bail!("Invalid silent payment address: {}", sp_address);
+    }
+    Err(WalletError::UnsupportedFeature("silent payments are not supported by this BDK version".to_string()).into())
+}
+
+/// Send an amount to a BIP352 silent payment address.
+/// Always returns an `UnsupportedFeature` error since the `silent-payments` feature is off.
+#[cfg(not(feature = "silent-payments"))]
+pub fn send_to_silent_payment(
+    _wallet: &mut Wallet,
+    _sp_address: &str,
+    _amount: u64,
+) -> Result<PartiallySignedTransaction> {
+    Err(WalletError::UnsupportedFeature("the silent-payments feature is disabled".to_string()).into())
+}
+
+/// Creates a single-key wallet from a WIF private key, for sweeping its coins.
+/// Compressed keys are watched as P2WPKH, uncompressed keys as P2PKH.
+/// Returns an error if the key is not for the given network.
+pub fn create_wif_wallet(wif: &str, network: Network) -> Result<Wallet> {
+    let key = PrivateKey::from_wif(wif.trim())?;
+    // NOTE: WIF keys only distinguish mainnet from the test networks
+    if (key.network == Network::Bitcoin) != (network == Network::Bitcoin) {
+        bail!("WIF key is for {} but the wallet is for {}", key.network, network);
+    }
+    let descriptor = if key.compressed {
+        format!("wpkh({})", key)
+    } else {
+        format!("pkh({})", key)
+    };
+    Ok(Wallet::new_no_persist(descriptor.as_str(), None, network)?)
+}
+
+/// Build and sign a transaction sending all of a WIF wallet's coins to a destination address.
+fn build_sweep_transaction(wallet: &mut Wallet, destination_address: &str, fee_rate: f32) -> Result<PartiallySignedTransaction> {
+    if wallet.get_balance().total() == 0 {
+        bail!("No coins to sweep");
+    }
+    let options = TxOptions::for_network(wallet.network());
+    let mut psbt = build_transaction(wallet, destination_address, None, Fee::Rate(fee_rate), None, &options)?;
+    wallet.sign(&mut psbt, options.sign_options())?;
+    Ok(psbt)
+}
+
+/// Sweep all coins of a WIF private key to a destination address, e.g. one of the wallet's own addresses.
+/// The key's network must match the destination's.
+/// Returns the signed PSBT, ready to broadcast.
+pub async fn sweep_wif(
+    wif: &str,
+    destination_address: &str,
+    fee_rate: f32,
+    client: &AsyncClient,
+) -> Result<PartiallySignedTransaction> {
+    if fee_rate < MIN_RELAY_FEE_RATE {
+        bail!("Fee rate {} sat/vB is below the minimum relay fee rate", fee_rate);
+    }
+    let destination = Address::from_str(destination_address)?;
+    let mut wallet = create_wif_wallet(wif, destination.network)?;
+    sync_wallet(&mut wallet, client, SyncConfig::default()).await?;
+    build_sweep_transaction(&mut wallet, destination_address, fee_rate)
+}
+
+/// The wallet's total balance against the sum of its UTXOs, which should always match.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceIntegrity {
+    pub balance: u64,
+    pub utxo_total: u64,
+    pub consistent: bool,
+}
+
+/// Compares the wallet's total balance with the sum of its UTXOs.
+pub fn balance_integrity(wallet: &Wallet) -> Result<BalanceIntegrity> {
+    let balance = wallet.get_balance().total();
+    let utxo_total = wallet
+        .list_unspent()
+        .try_fold(0u64, |total, utxo| total.checked_add(utxo.txout.value))
+        .ok_or_else(|| anyhow!("UTXO total overflows"))?;
+    Ok(BalanceIntegrity {
+        balance,
+        utxo_total,
+        consistent: balance == utxo_total,
+    })
+}
+
+/// Whether the wallet's total balance equals the sum of its UTXOs.
+/// A mismatch points to a sync bug.
+pub fn verify_balance_integrity(wallet: &Wallet) -> Result<bool> {
+    Ok(balance_integrity(wallet)?.consistent)
+}
+
+/// Whether a wallet's transaction can be fee bumped with RBF.
+/// The transaction must be in the wallet, still unconfirmed, RBF-signalling,
+/// and sent from the wallet.
+pub fn can_bump_fee(wallet: &Wallet, txid: Txid) -> Result<bool> {
+    let details = match wallet.get_tx(txid, true) {
+        Some(details) => details,
+        None => bail!("Transaction {} not found in the wallet", txid),
+    };
+    let tx = match details.transaction {
+        Some(tx) => tx,
+        None => bail!("Transaction {} not found in the wallet", txid),
+    };
+
+    let is_unconfirmed = !matches!(details.confirmation_time, ConfirmationTime::Confirmed { .. });
+    let is_rbf = tx.input.iter().any(|input| input.sequence.is_rbf());
+    let is_sent = details.sent > 0;
+    Ok(is_unconfirmed && is_rbf && is_sent)
+}
+
+/// The minimum fee rate in sat/vB, rounded up to the hundredth, to replace a transaction of `vsize` vB paying `original_fee` sats.
+/// BIP125 requires a higher absolute fee that also pays for the replacement's own relay
+/// at the incremental relay fee rate, assuming the replacement is the same size.
+pub fn min_bump_fee_rate(original_fee: u64, vsize: usize) -> f32 {
+    let vsize = vsize.max(1) as f32;
+    let min_fee_rate = (original_fee as f32 + vsize * MIN_RELAY_FEE_RATE) / vsize;
+    (min_fee_rate * 100.0).ceil() / 100.0
+}
+
+/// Build a replacement for an unconfirmed RBF transaction at a higher fee rate, paid from its change.
+/// The fee rate is validated upfront, so that a bump too small to replace the original
+/// fails with its minimum fee rate instead of BDK's error.
+pub fn bump_fee(wallet: &mut Wallet, txid: Txid, fee_rate: f32) -> Result<PartiallySignedTransaction> {
+    if !can_bump_fee(wallet, txid)? {
+        bail!("Transaction {} can't be fee bumped, it must be unconfirmed, RBF-signalling and sent from the wallet", txid);
+    }
+    let original = wallet.get_tx(txid, true).unwrap();
+    let vsize = original.transaction.as_ref().map_or(0, |tx| tx.vsize());
+    let min_fee_rate = min_bump_fee_rate(original.fee.unwrap_or(0), vsize);
+    if fee_rate < min_fee_rate {
+        bail!("Fee bump too small, minimum is {:.2} sat/vB", min_fee_rate);
+    }
+
+    let mut tx_builder = wallet.build_fee_bump(txid)?;
+    tx_builder.fee_rate(FeeRate::from_sat_per_vb(fee_rate));
+    let (psbt, _) = tx_builder.finish()?;
+    Ok(psbt)
+}
+
+/// Build a replacement for an unconfirmed RBF transaction, spending its inputs back to the wallet
+/// in a single output at a higher fee rate, which cancels the original once it confirms.
+fn build_cancel_transaction(wallet: &mut Wallet, txid: Txid, fee_rate: f32) -> Result<PartiallySignedTransaction> {
+    if !can_bump_fee(wallet, txid)? {
+        bail!("Transaction {} can't be cancelled, it must be unconfirmed, RBF-signalling and sent from the wallet", txid);
+    }
+    let original = wallet.get_tx(txid, true).unwrap();
+    let original_fee = original.fee.unwrap_or(0);
+    let largest_output = match original.transaction.and_then(|tx| tx.output.into_iter().max_by_key(|output| output.value)) {
+        Some(output) => output.script_pubkey,
+        None => bail!("Transaction {} has no outputs", txid),
+    };
+
+    // NOTE: the fee bump fills in the inputs' signing data, its outputs are then replaced
+    let mut tx_builder = wallet.build_fee_bump(txid)?;
+    tx_builder.fee_rate(FeeRate::from_sat_per_vb(fee_rate)).allow_shrinking(largest_output)?;
+    let (mut psbt, _) = tx_builder.finish()?;
+
+    let mut total_input: u64 = 0;
+    for input in &psbt.inputs {
+        match &input.witness_utxo {
+            Some(txout) => total_input += txout.value,
+            None => bail!("Missing input amount"),
+        }
+    }
+    // NOTE: the inputs and the change output are all the wallet's own script type
+    let vbytes = estimate_vsize(ScriptType::of_wallet(wallet)?, psbt.inputs.len(), 1);
+    let fee = (vbytes as f32 * fee_rate).ceil() as u64;
+    // NOTE: BIP125 requires paying for the replacement's own relay on top of the original fee
+    let min_fee = original_fee + (vbytes as f32 * MIN_RELAY_FEE_RATE).ceil() as u64;
+    if fee < min_fee {
+        bail!("Fee rate {} sat/vB is too low to replace transaction {}, the fee must be at least {} sats", fee_rate, txid, min_fee);
+    }
+    let value = match total_input.checked_sub(fee) {
+        Some(value) => value,
+        None => bail!("Fee of {} sats exceeds the inputs of {} sats", fee, total_input),
+    };
+
+    let address = wallet.get_internal_address(AddressIndex::New).address;
+    psbt.unsigned_tx.output = vec![TxOut {
+        value,
+        script_pubkey: address.script_pubkey(),
+    }];
+    psbt.outputs = vec![Default::default()];
+    Ok(psbt)
+}
+
+/// Cancel an unconfirmed RBF transaction by double-spending its inputs back to the wallet at a higher fee rate.
+/// Returns the broadcast replacement transaction.
+pub async fn cancel_transaction(
+    wallet: &mut Wallet,
+    txid: Txid,
+    fee_rate: f32,
+    client: &AsyncClient,
+) -> Result<Transaction> {
+    let mut psbt = build_cancel_transaction(wallet, txid, fee_rate)?;
+    wallet.sign(&mut psbt, SignOptions::default())?;
+    broadcast_signed_transaction(psbt, std::slice::from_ref(client)).await
+}
+
+/// A child transaction paying for its unconfirmed parent (CPFP).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cpfp {
+    pub child: Transaction,
+    /// Fee rate in sat/vB of the parent and child together.
+    pub package_fee_rate: f32,
+}
+
+/// Build a child spending the wallet's outputs of an unconfirmed parent back to the wallet,
+/// with a fee bringing the parent and child package up to `fee_rate` (sat/vB).
+/// Returns the unsigned child and the package fee rate.
+fn build_cpfp_transaction(wallet: &mut Wallet, parent_txid: Txid, parent_fee: u64, fee_rate: f32) -> Result<(PartiallySignedTransaction, f32)> {
+    let parent = match wallet.get_tx(parent_txid, true) {
+        Some(details) => details,
+        None => bail!("Transaction {} not found in the wallet", parent_txid),
+    };
+    if matches!(parent.confirmation_time, ConfirmationTime::Confirmed { .. }) {
+        bail!("Transaction {} is already confirmed", parent_txid);
+    }
+    let parent_vsize = match parent.transaction {
+        Some(tx) => tx.vsize() as u64,
+        None => bail!("Transaction {} not found in the wallet", parent_txid),
+    };
+    let outpoints: Vec<OutPoint> = wallet
+        .list_unspent()
+        .filter(|utxo| utxo.outpoint.txid == parent_txid)
+        .map(|utxo| utxo.outpoint)
+        .collect();
+    if outpoints.is_empty() {
+        bail!("Transaction {} has no unspent outputs to the wallet", parent_txid);
+    }
+
+    // NOTE: the parent's outputs and the child's change output are all the wallet's own script type
+    let child_vsize = estimate_vsize(ScriptType::of_wallet(wallet)?, outpoints.len(), 1) as u64;
+    let package_fee = ((parent_vsize + child_vsize) as f32 * fee_rate).ceil() as u64;
+    // NOTE: the child pays at least its own relay fee
+    let min_child_fee = (child_vsize as f32 * MIN_RELAY_FEE_RATE).ceil() as u64;
+    let child_fee = package_fee.saturating_sub(parent_fee).max(min_child_fee);
+
+    let address = wallet.get_internal_address(AddressIndex::New).address;
+    let mut tx_builder = wallet.build_tx();
+    tx_builder
+        .add_utxos(&outpoints)?
+        .manually_selected_only()
+        .drain_to(address.script_pubkey())
+        .fee_absolute(child_fee)
+        .enable_rbf();
+    let (psbt, _) = tx_builder.finish()?;
+
+    let package_fee_rate = (parent_fee + child_fee) as f32 / (parent_vsize + child_vsize) as f32;
+    Ok((psbt, package_fee_rate))
+}
+
+/// Computes a transaction's fee by looking up the outputs it spends on Esplora,
+/// e.g. for an incoming transaction whose inputs aren't the wallet's.
+async fn fetch_transaction_fee(tx: &Transaction, client: &AsyncClient) -> Result<u64> {
+    let mut total_input: u64 = 0;
+    for input in &tx.input {
+        rate_limiter().acquire().await;
+        let previous = client.get_tx(&input.previous_output.txid).await.map_err(WalletError::from)?;
+        let value = previous
+            .and_then(|previous| previous.output.get(input.previous_output.vout as usize).map(|output| output.value))
+            .ok_or_else(|| anyhow!("Output {} not found", input.previous_output))?;
+        total_input += value;
+    }
+    let total_output: u64 = tx.output.iter().map(|output| output.value).sum();
+    total_input
+        .checked_sub(total_output)
+        .ok_or_else(|| anyhow!("Transaction {} spends more than its inputs", tx.txid()))
+}
+
+/// Bump a stuck unconfirmed parent, incoming or sent, by broadcasting a child that pays for it (CPFP),
+/// bringing the package up to `fee_rate` (sat/vB).
+/// NOTE: Esplora can't submit packages, so the parent must already be in the mempool.
+pub async fn create_cpfp(wallet: &mut Wallet, parent_txid: Txid, fee_rate: f32, client: &AsyncClient) -> Result<Cpfp> {
+    let parent = match wallet.get_tx(parent_txid, true) {
+        Some(details) => details,
+        None => bail!("Transaction {} not found in the wallet", parent_txid),
+    };
+    let parent_fee = match (parent.fee, parent.transaction) {
+        // NOTE: the fee is only known locally if the wallet paid it
+        (Some(fee), _) if parent.sent > 0 => fee,
+        (_, Some(tx)) => fetch_transaction_fee(&tx, client).await?,
+        (_, None) => bail!("Transaction {} not found in the wallet", parent_txid),
+    };
+
+    let (mut psbt, package_fee_rate) = build_cpfp_transaction(wallet, parent_txid, parent_fee, fee_rate)?;
+    wallet.sign(&mut psbt, SignOptions::default())?;
+    let child = broadcast_signed_transaction(psbt, std::slice::from_ref(client)).await?;
+    Ok(Cpfp { child, package_fee_rate })
+}
+
+/// Returns the mempool status of each of the wallet's unconfirmed transactions,
+/// flagging the ones dropped from the mempool.
+pub async fn unconfirmed_mempool_status(wallet: &Wallet, base_url: &str, client: &reqwest::Client) -> Result<Vec<MempoolStatus>> {
+    let unconfirmed: Vec<Txid> = wallet
+        .transactions()
+        .filter(|tx| !tx.observed_as.is_confirmed())
+        .map(|tx| tx.node.txid)
+        .collect();
+
+    let mut statuses = Vec::with_capacity(unconfirmed.len());
+    for txid in unconfirmed {
+        statuses.push(get_mempool_status(&txid, base_url, client).await?);
+    }
+    Ok(statuses)
+}
+
+/// Result of testing whether a transaction would be accepted by the network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AcceptResult {
+    pub accepted: bool,
+    pub reject_reason: Option<String>,
+}
+
+impl AcceptResult {
+    fn accepted() -> Self {
+        Self { accepted: true, reject_reason: None }
+    }
+
+    fn rejected(reason: String) -> Self {
+        Self { accepted: false, reject_reason: Some(reason) }
+    }
+}
+
+/// Checks locally that a signed transaction pays at least the minimum relay fee and is standard.
+/// The PSBT must be finalized, so that the fee rate is computed from the transaction's actual vsize.
+pub fn check_standardness(psbt: &PartiallySignedTransaction) -> Result<AcceptResult> {
+    if let Some(index) = psbt
+        .inputs
+        .iter()
+        .position(|input| input.final_script_witness.is_none() && input.final_script_sig.is_none())
+    {
+        bail!("PSBT is not finalized: input {} has no final script", index);
+    }
+    let tx = psbt.clone().extract_tx();
+
+    let mut input_value = 0;
+    for (index, input) in psbt.inputs.iter().enumerate() {
+        match &input.witness_utxo {
+            Some(utxo) => input_value += utxo.value,
+            None => return Ok(AcceptResult::rejected(format!("missing-inputs: input {} has no witness utxo", index))),
+        }
+    }
+    let output_value: u64 = tx.output.iter().map(|output| output.value).sum();
+    if output_value > input_value {
+        return Ok(AcceptResult::rejected("bad-txns-in-belowout".to_string()));
+    }
+
+    if tx.weight() > MAX_STANDARD_TX_WEIGHT {
+        return Ok(AcceptResult::rejected(format!("tx-size: weight {} is over {}", tx.weight(), MAX_STANDARD_TX_WEIGHT)));
+    }
+    if let Some(output) = tx.output.iter().find(|output| output.value < output.script_pubkey.dust_value().to_sat()) {
+        return Ok(AcceptResult::rejected(format!("dust: output of {} sats", output.value)));
+    }
+
+    let fee = input_value - output_value;
+    let fee_rate = fee as f32 / tx.vsize() as f32;
+    if fee_rate < MIN_RELAY_FEE_RATE {
+        return Ok(AcceptResult::rejected(format!(
+            "min relay fee not met: {:.2} sat/vB < {:.2} sat/vB",
+            fee_rate, MIN_RELAY_FEE_RATE
+        )));
+    }
+
+    Ok(AcceptResult::accepted())
+}
+
+/// Tests whether a signed transaction would be accepted by the network without broadcasting it.
+/// Esplora has no `testmempoolaccept`, so the fee rate and standardness are checked locally,
+/// and the inputs are checked to still be unspent with the Esplora client.
+pub async fn test_broadcast(psbt: &PartiallySignedTransaction, client: &AsyncClient) -> Result<AcceptResult> {
+    let result = check_standardness(psbt)?;
+    if !result.accepted {
+        return Ok(result);
+    }
+
+    for input in &psbt.unsigned_tx.input {
+        let outpoint = input.previous_output;
+        rate_limiter().acquire().await;
+        if let Some(status) = client.get_output_status(&outpoint.txid, outpoint.vout as u64).await? {
+            if status.spent {
+                return Ok(AcceptResult::rejected(format!("txn-mempool-conflict: {} is already spent", outpoint)));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Try each client in order until one of them succeeds.
+/// Returns the index of the client that succeeded, or all the errors aggregated if they all failed.
+async fn try_in_order<'a, C, F, Fut, E>(clients: &'a [C], mut f: F) -> Result<usize>
+where
+    F: FnMut(&'a C) -> Fut,
+    Fut: Future<Output = std::result::Result<(), E>>,
+    E: Display,
+{
+    let mut errors = Vec::new();
+    for (index, client) in clients.iter().enumerate() {
+        match f(client).await {
+            Ok(()) => return Ok(index),
+            Err(e) => errors.push(format!("client {}: {}", index, e)),
+        }
+    }
+    Err(anyhow!("Error broadcasting transaction: {}", errors.join("; ")))
+}
+
+/// Broadcast a signed transaction to the network using the given Esplora clients.
+/// The clients are tried in order, falling back to the next one if a broadcast fails.
+/// The transaction's UTXOs are released whether the broadcast succeeds or not.
+pub async fn broadcast_signed_transaction(psbt: PartiallySignedTransaction, clients: &[AsyncClient]) -> Result<Transaction> {
+    let tx = psbt.extract_tx();
+    let tx_ref = &tx;
+    let result = try_in_order(clients, |client| async move {
+        rate_limiter().acquire().await;
+        client.broadcast(tx_ref).await
+    })
+    .await;
+    // NOTE: a rejected transaction doesn't spend its UTXOs, so they can be spent again right away
+    utxo_locks().release(tx.input.iter().map(|input| input.previous_output));
+    result?;
+    Ok(tx)
+}
+
+/// The Esplora base URL of a network, mainnet's for bitcoin and testnet's otherwise.
+fn esplora_base_url(network: &str) -> Result<&'static str, ServerFnError> {
+    match parse_network(network).map_err(|e| ServerFnError::ServerError(e.to_string()))? {
+        Network::Bitcoin => Ok(DEFAULT_ESPLORA_BASE_URL_MAINNET),
+        _ => Ok(DEFAULT_ESPLORA_BASE_URL_TESTNET),
+    }
+}
+
+/// The fallback Esplora base URL of a network, mainnet's for bitcoin and testnet's otherwise.
+fn fallback_esplora_base_url(network: &str) -> Result<&'static str, ServerFnError> {
+    match parse_network(network).map_err(|e| ServerFnError::ServerError(e.to_string()))? {
+        Network::Bitcoin => Ok(FALLBACK_ESPLORA_BASE_URL_MAINNET),
+        _ => Ok(FALLBACK_ESPLORA_BASE_URL_TESTNET),
+    }
+}
+
+/// Create the Esplora async client, sending a bearer token if one is given.
+fn authenticated_client(base_url: &str, auth_token: Option<&str>) -> Result<AsyncClient, ServerFnError> {
+    auth_headers(auth_token)
+        .and_then(|headers| create_client_with_headers(base_url, headers))
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))
+}
+
+/// Returns a JSON string of the wallet's utxos.
+#[server(GetUtxo, "/api", "GetJson", "utxo")] // GetJson is a GET and will be cached
+pub async fn get_utxo(mnemonic: String, network: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Get UTXOs
+    let utxo = wallet
+        .list_unspent()
+        .map(|utxo| UtxoDef::from(&wallet, utxo))
+        .collect::<Vec<UtxoDef>>();
+
+    // Serialize to JSON
+    let json = to_string(&utxo)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the wallet's balance.
+#[server(GetBalance, "/api", "GetJson", "balance")] // GetJson is a GET and will be cached
+pub async fn get_balance(mnemonic: String, network: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Get Balance
+    let balance = wallet.get_balance();
+
+    // Serialize to JSON
+    let json = to_string(&balance)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the wallet's address for a given address type and index.
+/// Address type can be "receive" or "change".
+/// Script type can be "taproot" or "segwit".
+#[server(GetAddress, "/api", "GetJson", "address")] // GetJson is a GET and will be cached
+pub async fn get_address(
+    mnemonic: String,
+    network: String,
+    script_type: String,
+    address_type: String,
+    index_mode: String,
+    index: Option<usize>,
+    auth_token: Option<String>,
+) -> Result<String, ServerFnError> {
+    // Address wrangling
+    let address_type = address_type.as_str();
+    let address_type: AddressType = match address_type {
+        "receive" => AddressType::Receive,
+        "change" => AddressType::Change,
+        &_ => AddressType::Receive, // NOTE: a good default
+    };
+    let address_index = parse_address_index(index_mode.as_str(), index).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    let script_type = ScriptType::parse(script_type.as_str()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Create the wallet
+    let (derivation_path_external, derivation_path_internal) = script_type.derivation_paths();
+    let mut wallet = create_wallet_with_script_type(mnemonic.as_str(), network.as_str(),
+            script_type,
+            derivation_path_external,
+            derivation_path_internal,
+            ).unwrap();
+
+    // Sync Wallet, the next address depends on the wallet's history
+    if !matches!(address_index, AddressIndex::Peek(_)) {
+        let base_url = esplora_base_url(network.as_str())?;
+        let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+        let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+    }
+
+    // Get the address
+    let address = match (address_type, address_index) {
+        // NOTE: derived directly, a large index would otherwise derive every address below it
+        (AddressType::Receive, AddressIndex::Peek(index)) => peek_address(&wallet, KeychainKind::External, index).map_err(|e| ServerFnError::ServerError(e.to_string()))?,
+        (AddressType::Change, AddressIndex::Peek(index)) => peek_address(&wallet, KeychainKind::Internal, index).map_err(|e| ServerFnError::ServerError(e.to_string()))?,
+        (AddressType::Receive, address_index) => wallet.get_address(address_index),
+        (AddressType::Change, address_index) => wallet.get_internal_address(address_index),
+    };
+    validate_address_prefix(&address.address, wallet.network(), script_type).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    let address = AddressInfoDef::from(address);
+
+    // Serialize to JSON
+    let json = to_string(&address)?;
+    Ok(json)
+}
+
+
+/// Returns a JSON string of the broadcast transaction, with its split between the recipient, change and fee.
+/// Sends `amount` sats if given, otherwise all available coins.
+/// An optional absolute fee in sats can be given instead of the estimated fee rate.
+#[server(PostSendTransaction, "/api", "Url", "send")]
+pub async fn post_send_transaction(mnemonic: String, network: String, address: String, amount: Option<u64>, absolute_fee_sat: Option<u64>, confirmation_target: Option<usize>, auth_token: Option<String>) -> Result<String, ServerFnError> {
+// Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Create a Signed Transaction
+    // that sends the amount, or drains all available coins, to the given address
+    let options = TxOptions {
+        absolute_fee_sat,
+        confirmation_target,
+        ..TxOptions::for_network(wallet.network())
+    };
+    let psbt = create_signed_transaction(&mut wallet, address.as_str(), amount, &esplora_client, options)
+        .await
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    let breakdown = payment_breakdown(&wallet, &psbt, address.as_str()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Broadcast the Signed Transaction
+    // falling back to a secondary Esplora server
+    // NOTE: the auth token is only meant for the primary server, so it isn't sent to the fallback
+    let fallback_url = fallback_esplora_base_url(network.as_str())?;
+    let fallback_client = Builder::new(fallback_url).build_async()?;
+    let tx = broadcast_signed_transaction(psbt, &[esplora_client, fallback_client])
+        .await
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = serde_json::json!({ "tx": tx, "breakdown": breakdown }).to_string();
+    Ok(json)
+}
+
+
+/// Formats a unix timestamp as an ISO 8601 UTC date-time.
+fn format_unix_time(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let seconds = timestamp % 86_400;
+
+    // NOTE: days to civil date from <http://howardhinnant.github.io/date_algorithms.html>
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3_600,
+        (seconds % 3_600) / 60,
+        seconds % 60
+    )
+}
+
+/// Escapes a CSV field, quoting it if it contains a comma, a quote or a newline.
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// User metadata for coin control: labels and frozen coins.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoinControl {
+    pub labels: HashMap<OutPoint, String>,
+    pub frozen: HashSet<OutPoint>,
+}
+
+/// Which coins count as spendable, on top of excluding frozen coins and immature coinbase outputs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpendablePolicy {
+    /// Count unconfirmed coins as spendable.
+    pub include_unconfirmed: bool,
+}
+
+/// The balance actually available to spend: the wallet's UTXOs minus frozen coins, immature coinbase outputs
+/// and, unless the policy includes them, unconfirmed coins. Complements `get_balance`'s raw figures.
+pub fn spendable_balance(wallet: &Wallet, coin_control: &CoinControl, policy: SpendablePolicy) -> u64 {
+    let immature = immature_coinbase_outpoints(wallet);
+    wallet
+        .list_unspent()
+        .filter(|utxo| !coin_control.frozen.contains(&utxo.outpoint) && !immature.contains(&utxo.outpoint))
+        .filter(|utxo| policy.include_unconfirmed || matches!(utxo.confirmation_time, ConfirmationTime::Confirmed { .. }))
+        .map(|utxo| utxo.txout.value)
+        .sum()
+}
+
+/// A wallet UTXO with its coin control metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Coin {
+    pub outpoint: String,
+    pub value: u64,
+    /// 0 if unconfirmed.
+    pub confirmations: u32,
+    pub address: Option<String>,
+    pub keychain: String,
+    pub label: Option<String>,
+    pub frozen: bool,
+}
+
+/// The number of confirmations at the wallet's chain tip, `tip_height - confirmation_height + 1`,
+/// and 0 for unconfirmed transactions or without a tip.
+pub fn confirmations(confirmation_time: ConfirmationTime, tip_height: Option<u32>) -> u32 {
+    match confirmation_time {
+        ConfirmationTime::Confirmed { height, .. } => tip_height.map_or(0, |tip| tip.saturating_sub(height) + 1),
+        ConfirmationTime::Unconfirmed { .. } => 0,
+    }
+}
+
+/// Lists the wallet's UTXOs with their coin control metadata.
+pub fn list_coins(wallet: &Wallet, coin_control: &CoinControl) -> Vec<Coin> {
+    let tip_height = wallet.latest_checkpoint().map(|block| block.height);
+
+    wallet
+        .list_unspent()
+        .map(|utxo| {
+            Coin {
+                outpoint: utxo.outpoint.to_string(),
+                value: utxo.txout.value,
+                confirmations: confirmations(utxo.confirmation_time, tip_height),
+                address: Address::from_script(&utxo.txout.script_pubkey, wallet.network())
+                    .ok()
+                    .map(|address| address.to_string()),
+                keychain: format!("{:?}", utxo.keychain),
+                label: coin_control.labels.get(&utxo.outpoint).cloned(),
+                frozen: coin_control.frozen.contains(&utxo.outpoint),
+            }
+        })
+        .collect()
+}
+
+/// Age of a UTXO since its confirmation, assuming 10 minute blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AgeBucket {
+    /// Less than a day old, including unconfirmed UTXOs.
+    Day,
+    Week,
+    Month,
+    Older,
+}
+
+impl AgeBucket {
+    /// Buckets a UTXO by its age in blocks.
+    pub fn from_blocks(blocks: u32) -> Self {
+        match blocks {
+            0..=143 => AgeBucket::Day,
+            144..=1_007 => AgeBucket::Week,
+            1_008..=4_319 => AgeBucket::Month,
+            _ => AgeBucket::Older,
+        }
+    }
+}
+
+/// Counts the wallet's UTXOs and their total value in sats by age bucket,
+/// relative to the wallet's chain tip. Empty buckets are included.
+pub fn utxo_age_histogram(wallet: &Wallet) -> Result<Vec<(AgeBucket, usize, u64)>> {
+    let tip_height = match wallet.latest_checkpoint() {
+        Some(block) => block.height,
+        None => bail!("Wallet has no chain tip, sync it first"),
+    };
+
+    let mut histogram: Vec<(AgeBucket, usize, u64)> = [AgeBucket::Day, AgeBucket::Week, AgeBucket::Month, AgeBucket::Older]
+        .into_iter()
+        .map(|bucket| (bucket, 0, 0))
+        .collect();
+    for utxo in wallet.list_unspent() {
+        let age = match utxo.confirmation_time {
+            ConfirmationTime::Confirmed { height, .. } => tip_height.saturating_sub(height),
+            ConfirmationTime::Unconfirmed { .. } => 0,
+        };
+        let bucket = AgeBucket::from_blocks(age);
+        if let Some(entry) = histogram.iter_mut().find(|(b, _, _)| *b == bucket) {
+            entry.1 += 1;
+            entry.2 += utxo.txout.value;
+        }
+    }
+    Ok(histogram)
+}
+
+/// Total amount received on one of the wallet's receive addresses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressReceived {
+    pub index: u32,
+    pub address: String,
+    pub total_received: u64,
+}
+
+/// Lists the wallet's receive addresses that have received funds, with the total received on each,
+/// summed over the outputs of the wallet's transactions and ordered by derivation index.
+pub fn list_received_addresses(wallet: &Wallet) -> Vec<AddressReceived> {
+    let mut received: HashMap<u32, (String, u64)> = HashMap::new();
+    for tx in wallet.transactions() {
+        for output in &tx.node.tx.output {
+            let index = match wallet.spk_index().index_of_spk(&output.script_pubkey) {
+                Some((KeychainKind::External, index)) => *index,
+                _ => continue,
+            };
+            let address = match Address::from_script(&output.script_pubkey, wallet.network()) {
+                Ok(address) => address.to_string(),
+                Err(_) => continue,
+            };
+            received.entry(index).or_insert((address, 0)).1 += output.value;
+        }
+    }
+
+    let mut received: Vec<AddressReceived> = received
+        .into_iter()
+        .filter(|(_, (_, total_received))| *total_received > 0)
+        .map(|(index, (address, total_received))| AddressReceived { index, address, total_received })
+        .collect();
+    received.sort_by_key(|address| address.index);
+    received
+}
+
+/// How far a keychain's revealed addresses run ahead of its used ones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GapInfo {
+    pub keychain: String,
+    /// Highest index that received funds, if any.
+    pub highest_used: Option<u32>,
+    /// Index after the highest used one.
+    pub next_unused: u32,
+    /// Revealed addresses after the highest used one, to compare against the stop gap.
+    pub gap: u32,
+}
+
+/// Returns the gap info of the external and internal keychains, from the wallet's synced transactions.
+pub fn gap_info(wallet: &Wallet) -> Vec<GapInfo> {
+    [KeychainKind::External, KeychainKind::Internal]
+        .into_iter()
+        .map(|keychain| {
+            let highest_used = wallet
+                .transactions()
+                .flat_map(|tx| tx.node.tx.output.iter())
+                .filter_map(|output| match wallet.spk_index().index_of_spk(&output.script_pubkey) {
+                    Some((output_keychain, index)) if *output_keychain == keychain => Some(*index),
+                    _ => None,
+                })
+                .max();
+            let next_unused = highest_used.map_or(0, |index| index + 1);
+            let revealed = wallet.spk_index().last_revealed_index(&keychain).map_or(0, |index| index + 1);
+            GapInfo {
+                keychain: format!("{:?}", keychain),
+                highest_used,
+                next_unused,
+                gap: revealed.saturating_sub(next_unused),
+            }
+        })
+        .collect()
+}
+
+/// Whether the receive address at `index` already has history in the synced wallet,
+/// i.e. any of the wallet's transactions pays to it.
+pub fn address_has_history(wallet: &Wallet, index: u32) -> Result<bool> {
+    let script = peek_address(wallet, KeychainKind::External, index)?.script_pubkey();
+    Ok(wallet
+        .transactions()
+        .any(|tx| tx.node.tx.output.iter().any(|output| output.script_pubkey == script)))
+}
+
+/// Syncs the wallet and checks whether the receive address at `index` was already used,
+/// to warn before reusing it.
+pub async fn is_address_reused(wallet: &mut Wallet, index: u32, client: &AsyncClient) -> Result<bool> {
+    sync_wallet(wallet, client, SyncConfig::default()).await?;
+    address_has_history(wallet, index)
+}
+
+/// A transaction in the wallet's history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub txid: String,
+    pub received: u64,
+    pub sent: u64,
+    /// Only known when the wallet paid the fee, i.e. it owns all the inputs.
+    pub fee: Option<u64>,
+    /// Fee rate in sat/vB, `None` if the wallet didn't pay the fee.
+    pub fee_rate: Option<f32>,
+    pub confirmation_time: ConfirmationTime,
+    /// Confirmations at the synced chain tip, 0 if unconfirmed.
+    pub confirmations: u32,
+}
+
+/// Lists the wallet's transactions, with the fee rate of those the wallet paid the fee of.
+pub fn list_transactions(wallet: &Wallet) -> Vec<HistoryEntry> {
+    let tip_height = wallet.latest_checkpoint().map(|block| block.height);
+    wallet
+        .transactions()
+        .filter_map(|tx| {
+            let confirmation_time = ConfirmationTime::from(tx.observed_as.cloned());
+            let details = wallet.get_tx(tx.node.txid, false)?;
+            // NOTE: incoming transactions spend foreign inputs, the sender paid the fee
+            let fee = if details.sent > 0 { details.fee } else { None };
+            let fee_rate = fee.map(|fee| fee as f32 / tx.node.tx.vsize() as f32);
+            Some(HistoryEntry {
+                txid: details.txid.to_string(),
+                received: details.received,
+                sent: details.sent,
+                fee,
+                fee_rate,
+                confirmation_time,
+                confirmations: confirmations(confirmation_time, tip_height),
+            })
+        })
+        .collect()
+}
+
+/// Everything the dashboard shows, from a single sync of the wallet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletSnapshot {
+    pub balance: Balance,
+    pub utxos: Vec<UtxoDef>,
+    pub transactions: Vec<HistoryEntry>,
+    pub next_receive_address: String,
+    pub tip: Option<ChainTip>,
+}
+
+/// Takes a snapshot of a synced wallet's balance, UTXOs, transactions, next unused receive address and chain tip.
+pub fn wallet_snapshot(wallet: &mut Wallet) -> WalletSnapshot {
+    WalletSnapshot {
+        balance: wallet.get_balance(),
+        utxos: wallet.list_unspent().map(|utxo| UtxoDef::from(wallet, utxo)).collect(),
+        transactions: list_transactions(wallet),
+        next_receive_address: wallet.get_address(AddressIndex::LastUnused).address.to_string(),
+        tip: get_chain_tip(wallet),
+    }
+}
+
+/// What changed in a wallet since the UI last checked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffReport {
+    /// Transactions the UI hasn't seen yet.
+    pub new_txids: Vec<String>,
+    /// Transactions the UI saw unconfirmed that are now confirmed.
+    pub newly_confirmed_txids: Vec<String>,
+    /// Net change of the balance in sats from the new transactions.
+    pub balance_delta: i64,
+}
+
+/// Diffs a synced wallet against the txids the UI already knows, `since_txids`,
+/// of which `unconfirmed_txids` were still unconfirmed, e.g. for notifications.
+pub fn wallet_diff(wallet: &Wallet, since_txids: &[String], unconfirmed_txids: &[String]) -> Result<DiffReport> {
+    let parse_txids = |txids: &[String]| {
+        txids
+            .iter()
+            .map(|txid| Txid::from_str(txid.trim()).map_err(|e| anyhow!("Invalid txid {}: {}", txid, e)))
+            .collect::<Result<HashSet<Txid>>>()
+    };
+    let known = parse_txids(since_txids)?;
+    let unconfirmed = parse_txids(unconfirmed_txids)?;
+
+    let mut report = DiffReport {
+        new_txids: Vec::new(),
+        newly_confirmed_txids: Vec::new(),
+        balance_delta: 0,
+    };
+    for tx in wallet.transactions() {
+        let txid = tx.node.txid;
+        let is_confirmed = tx.observed_as.is_confirmed();
+        if !known.contains(&txid) {
+            if let Some(details) = wallet.get_tx(txid, false) {
+                report.balance_delta += details.received as i64 - details.sent as i64;
+            }
+            report.new_txids.push(txid.to_string());
+        } else if is_confirmed && unconfirmed.contains(&txid) {
+            report.newly_confirmed_txids.push(txid.to_string());
+        }
+    }
+    Ok(report)
+}
+
+/// Returns the wallet's transaction history as a CSV string.
+/// Transactions are ordered chronologically, with unconfirmed transactions last,
+/// and a running balance is computed after each transaction.
+pub fn export_history_csv(wallet: &Wallet) -> String {
+    let tip_height = wallet.latest_checkpoint().map(|block| block.height);
+
+    let mut csv = String::from("date,txid,type,amount_sat,fee_sat,confirmations,balance_after\n");
+    // The running balance needs the rows in chain order: by confirmation, unconfirmed last
+    let mut txs: Vec<(ConfirmationTime, Txid)> = wallet
+        .transactions()
+        .map(|tx| (ConfirmationTime::from(tx.observed_as.cloned()), tx.node.txid))
+        .collect();
+    txs.sort_by_key(|(confirmation_time, txid)| match *confirmation_time {
+        ConfirmationTime::Confirmed { height, time } => (false, height, time, *txid),
+        ConfirmationTime::Unconfirmed { last_seen } => (true, u32::MAX, last_seen, *txid),
+    });
+
+    let mut balance: i64 = 0;
+    for (confirmation_time, txid) in txs {
+        let details = match wallet.get_tx(txid, false) {
+            Some(details) => details,
+            None => continue,
+        };
+        balance += details.received as i64 - details.sent as i64;
+
+        let fee = details.fee.unwrap_or(0);
+        let (tx_type, amount) = if details.sent > details.received {
+            ("send", details.sent - details.received - fee)
+        } else {
+            ("receive", details.received - details.sent)
+        };
+        let date = match confirmation_time {
+            ConfirmationTime::Confirmed { time, .. } => format_unix_time(time),
+            ConfirmationTime::Unconfirmed { .. } => String::new(),
+        };
+        let confirmations = confirmations(confirmation_time, tip_height);
+
+        let fields = [
+            date,
+            details.txid.to_string(),
+            tx_type.to_string(),
+            amount.to_string(),
+            fee.to_string(),
+            confirmations.to_string(),
+            balance.to_string(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| escape_csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Returns a CSV string of the wallet's transaction history.
+#[server(ExportHistoryCsv, "/api", "GetJson", "history-csv")] // GetJson is a GET and will be cached
+pub async fn export_history(mnemonic: String, network: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    Ok(export_history_csv(&wallet))
+}
+
+/// Fully rescans the wallet, with its chain anchored at a given block height, and returns a JSON string of the wallet's balance.
+/// Transactions below the height are rescanned as well, the height only adds a checkpoint.
+#[server(RescanFromHeight, "/api", "Url", "rescan")]
+pub async fn rescan_from_height(mnemonic: String, network: String, height: u32, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Rescan Wallet
+    rescan_wallet_at_height(&mut wallet, &esplora_client, height).await.map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Get Balance
+    let balance = wallet.get_balance();
+
+    // Serialize to JSON
+    let json = to_string(&balance)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the wallet's chain tip after a sync,
+/// along with Esplora's tip height so that a stale tip can be detected,
+/// and whether the sync detected a reorg.
+#[server(GetChainTip, "/api", "GetJson", "tip")] // GetJson is a GET and will be cached
+pub async fn get_tip(mnemonic: String, network: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let reorg_detected = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default())
+        .await
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?
+        .reorg_detected;
+
+    // Get the tips
+    let tip = get_chain_tip(&wallet);
+    rate_limiter().acquire().await;
+    let esplora_height = esplora_client.get_height().await?;
+
+    // Serialize to JSON
+    let json = serde_json::json!({ "tip": tip, "esplora_height": esplora_height, "reorg_detected": reorg_detected }).to_string();
+    Ok(json)
+}
+
+/// Returns a JSON string of the estimated fee savings of consolidating the wallet's UTXOs now
+/// at the next-block fee rate versus spending them separately later at `future_fee_rate` (sat/vB).
+/// This is only an estimate, nothing is broadcast.
+#[server(GetConsolidationSavings, "/api", "GetJson", "consolidation-savings")] // GetJson is a GET and will be cached
+pub async fn get_consolidation_savings(mnemonic: String, network: String, future_fee_rate: f32, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Estimate the savings
+    let num_utxos = wallet.list_unspent().count();
+    let fee_rate = get_fee_estimates(&esplora_client, None).await.map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    let estimate = estimate_consolidation_savings(num_utxos, fee_rate, future_fee_rate);
+
+    // Serialize to JSON
+    let json = to_string(&estimate)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the wallet's UTXOs for coin control,
+/// with their value, confirmations, address, keychain, label and frozen state.
+#[server(GetCoins, "/api", "GetJson", "coins")] // GetJson is a GET and will be cached
+pub async fn get_coins(mnemonic: String, network: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // List the coins
+    // NOTE: labels and frozen coins aren't persisted yet, so no coin has any
+    let coins = list_coins(&wallet, &CoinControl::default());
+
+    // Serialize to JSON
+    let json = to_string(&coins)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the rough number of seconds a full sync of the wallet will take.
+#[server(EstimateSyncTime, "/api", "GetJson", "sync-estimate")] // GetJson is a GET and will be cached
+pub async fn get_sync_estimate(mnemonic: String, network: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Probe Esplora and estimate
+    let latency = measure_latency(&esplora_client).await.map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    let keychains: Vec<KeychainKind> = wallet.spks_of_all_keychains().into_keys().collect();
+    let parallel_requests = concurrency_config().for_url(esplora_client.url());
+    let estimate = estimate_sync_time(&keychains, StopGaps::default(), latency, parallel_requests);
+
+    // Serialize to JSON
+    let json = serde_json::json!({ "seconds": estimate.as_secs_f64() }).to_string();
+    Ok(json)
+}
+
+/// Returns a JSON string of the wallet's receive addresses that have received funds, with the total received on each.
+#[server(GetReceivedAddresses, "/api", "GetJson", "received-addresses")] // GetJson is a GET and will be cached
+pub async fn get_received_addresses(mnemonic: String, network: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Sum the received amounts
+    let received = list_received_addresses(&wallet);
+
+    // Serialize to JSON
+    let json = to_string(&received)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of whether a descriptor wallet can sign transactions or is watch-only,
+/// e.g. for a descriptor with `xprv` keys or one with only `xpub` keys.
+#[server(CanSign, "/api", "GetJson", "can-sign")] // GetJson is a GET and will be cached
+pub async fn get_can_sign(descriptor: String, change_descriptor: Option<String>, network: String) -> Result<String, ServerFnError> {
+    // Create the wallet
+    let wallet = create_wallet_from_descriptor(descriptor.as_str(), change_descriptor.as_deref(), network.as_str())
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = serde_json::json!({ "can_sign": can_sign(&wallet) }).to_string();
+    Ok(json)
+}
+
+/// Returns a JSON string of the transaction replacing and cancelling an unconfirmed RBF transaction,
+/// which sends its inputs back to the wallet at a higher fee rate in sat/vB.
+#[server(CancelTransaction, "/api", "Url", "cancel")]
+pub async fn post_cancel_transaction(mnemonic: String, network: String, txid: String, fee_rate: f32, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Cancel the transaction
+    let txid = Txid::from_str(txid.as_str())?;
+    let tx = cancel_transaction(&mut wallet, txid, fee_rate, &esplora_client)
+        .await
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = to_string(&tx)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the wallet's balance, the sum of its UTXOs, and whether they match.
+#[server(VerifyBalanceIntegrity, "/api", "GetJson", "balance-integrity")] // GetJson is a GET and will be cached
+pub async fn get_balance_integrity(mnemonic: String, network: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Compare the balance with the UTXOs
+    let integrity = balance_integrity(&wallet).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = to_string(&integrity)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of whether a wallet's transaction can be fee bumped with RBF.
+#[server(CanBumpFee, "/api", "GetJson", "can-bump-fee")] // GetJson is a GET and will be cached
+pub async fn get_can_bump_fee(mnemonic: String, network: String, txid: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Check the transaction
+    let txid = Txid::from_str(txid.as_str())?;
+    let can_bump_fee = can_bump_fee(&wallet, txid).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = serde_json::json!({ "can_bump_fee": can_bump_fee }).to_string();
+    Ok(json)
+}
+
+/// Returns a JSON string of the wallet's total balance in sats and its fiat value in a currency.
+/// If the price source fails, only the balance in sats is returned with `fiat_available` set to false.
+#[server(GetFiatBalance, "/api", "GetJson", "fiat-balance")] // GetJson is a GET and will be cached
+pub async fn get_fiat_balance(mnemonic: String, network: String, currency: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Get Balance and its fiat value
+    let balance = wallet.get_balance().total();
+    let fiat = get_fiat_value(balance, currency.as_str(), &reqwest::Client::new()).await.ok();
+
+    // Serialize to JSON
+    let json = serde_json::json!({
+        "balance": balance,
+        "currency": currency.to_uppercase(),
+        "fiat": fiat,
+        "fiat_available": fiat.is_some(),
+    })
+    .to_string();
+    Ok(json)
+}
+
+/// Returns a JSON string summarizing a base64 PSBT's inputs, outputs and fee.
+/// Inputs and outputs owned by the wallet are flagged, guessing the change outputs.
+#[server(DescribePsbt, "/api", "Url", "describe-psbt")]
+pub async fn get_psbt_summary(mnemonic: String, network: String, psbt: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet to reveal the wallet's scripts
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Describe the PSBT
+    let summary = describe_psbt(&wallet, psbt.as_str(), wallet.network())
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = to_string(&summary)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of how the wallet is derived: script type, derivation paths, network and master fingerprint.
+#[server(GetDerivationInfo, "/api", "GetJson", "derivation")] // GetJson is a GET and will be cached
+pub async fn get_derivation(mnemonic: String, network: String) -> Result<String, ServerFnError> {
+    let info = get_derivation_info(mnemonic.as_str(), network.as_str(), default_script_type())
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = to_string(&info)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the combination of base64 or hex PSBTs, and whether it can be finalized.
+/// The combined PSBT is serialized as "base64" (the default) or "hex".
+#[server(CombinePsbts, "/api", "Url", "combine-psbts")]
+pub async fn post_combine_psbts(psbts: Vec<String>, format: Option<String>) -> Result<String, ServerFnError> {
+    let format = match format {
+        Some(format) => PsbtFormat::parse(format.as_str()).map_err(|e| ServerFnError::ServerError(e.to_string()))?,
+        None => PsbtFormat::default(),
+    };
+
+    // Combine the PSBTs
+    let combined = combine_psbts(psbts, format).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = to_string(&combined)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the txid of a fully signed raw transaction in hex, broadcast through Esplora.
+/// The reject reason is returned as the error if the transaction is rejected.
+#[server(BroadcastRaw, "/api", "Url", "broadcast-raw")]
+pub async fn post_broadcast_raw(network: String, tx_hex: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    let base_url = esplora_base_url(network.as_str())?;
+    let headers = auth_headers(auth_token.as_deref()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    let client = reqwest::Client::builder().default_headers(headers).build()?;
+
+    // Broadcast the transaction
+    let txid = broadcast_raw_transaction(tx_hex.as_str(), base_url, &client)
+        .await
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = serde_json::json!({ "txid": txid }).to_string();
+    Ok(json)
+}
+
+/// Returns a JSON string of whether a signed base64 or hex PSBT would be accepted by the network,
+/// with the reject reason if not, without broadcasting it.
+#[server(TestBroadcast, "/api", "Url", "test-broadcast")]
+pub async fn post_test_broadcast(network: String, psbt: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Test the transaction
+    let psbt = parse_psbt(psbt.as_str()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    let result = test_broadcast(&psbt, &esplora_client)
+        .await
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = to_string(&result)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the wallet's transaction history, with each transaction's fee rate.
+#[server(GetTransactions, "/api", "GetJson", "transactions")] // GetJson is a GET and will be cached
+pub async fn get_transactions(mnemonic: String, network: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Get Transactions
+    let transactions = list_transactions(&wallet);
+
+    // Serialize to JSON
+    let json = to_string(&transactions)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the timestamps of blocks by height, e.g. of the confirmation heights of the wallet's transactions,
+/// so that the history can show real dates. Each height is only fetched once, then cached.
+#[server(GetBlockTimes, "/api", "GetJson", "block-times")] // GetJson is a GET and will be cached
+pub async fn get_block_times(network: String, heights: Vec<u32>, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Get the block times
+    let times = block_time_cache()
+        .get_block_times(&heights, &esplora_client)
+        .await
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = to_string(&times)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the wallet's UTXO count and value by age bucket.
+#[server(GetUtxoAgeHistogram, "/api", "GetJson", "utxo-age")] // GetJson is a GET and will be cached
+pub async fn get_utxo_age_histogram(mnemonic: String, network: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Get the UTXO age histogram
+    let histogram = utxo_age_histogram(&wallet).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = to_string(&histogram)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of an address's funded, spent and balance amounts in sats, queried directly from Esplora.
+/// The address doesn't need to belong to a wallet.
+#[server(GetAddressBalance, "/api", "GetJson", "address-balance")] // GetJson is a GET and will be cached
+pub async fn get_address_balance_by_address(network: String, address: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    let address = parse_network(network.as_str()).and_then(|network| parse_address_for_network(address.as_str(), network)).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    let base_url = esplora_base_url(network.as_str())?;
+    let headers = auth_headers(auth_token.as_deref()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    let client = reqwest::Client::builder().default_headers(headers).build()?;
+
+    // Get the address balance
+    let balance = get_address_balance(&address, base_url, &client)
+        .await
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = to_string(&balance)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the slow, normal and fast fee rate presets in sat/vB.
+/// Given a wallet's mnemonic and a send to `address` of `amount`, or all available coins if no amount is given,
+/// the fees of the send at each preset are previewed from the wallet's actual coin selection.
+#[server(GetFeePresets, "/api", "GetJson", "fee-presets")] // GetJson is a GET and will be cached
+pub async fn get_fee_presets(
+    network: String,
+    mnemonic: Option<String>,
+    address: Option<String>,
+    amount: Option<u64>,
+    auth_token: Option<String>,
+) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Get the fee presets
+    let fee_estimates = get_fee_map(&esplora_client).await.map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    let presets = fee_presets(&fee_estimates).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Preview the send's fees
+    let preview = match (mnemonic, address) {
+        (Some(mnemonic), Some(address)) => {
+            let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+            let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+            preview_fees(&mut wallet, presets, address.as_str(), amount).map_err(|e| ServerFnError::ServerError(e.to_string()))?
+        }
+        _ => FeePreview { presets, estimates: None },
+    };
+
+    // Serialize to JSON
+    let json = to_string(&preview)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the gap between the highest used and the revealed addresses of each keychain.
+#[server(GetGapInfo, "/api", "GetJson", "gap-info")] // GetJson is a GET and will be cached
+pub async fn get_gap_info(mnemonic: String, network: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Get the gap info
+    let gap_info = gap_info(&wallet);
+
+    // Serialize to JSON
+    let json = to_string(&gap_info)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the mempool status of the wallet's unconfirmed transactions.
+#[server(GetMempoolStatus, "/api", "GetJson", "mempool-status")] // GetJson is a GET and will be cached
+pub async fn get_unconfirmed_status(mnemonic: String, network: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+    let headers = auth_headers(auth_token.as_deref()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    let client = reqwest::Client::builder().default_headers(headers).build()?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Get the mempool status
+    let statuses = unconfirmed_mempool_status(&wallet, base_url, &client)
+        .await
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = to_string(&statuses)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of a freshly generated wallet: its backup mnemonic, first receive address and public descriptors.
+/// NOTE: this is the only place the mnemonic is returned, and it must never be logged.
+#[server(CreateNewWallet, "/api", "Url", "new-wallet")]
+pub async fn create_new_wallet(network: String) -> Result<String, ServerFnError> {
+    // Generate the wallet
+    let new_wallet = generate_new_wallet(network.as_str()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = to_string(&new_wallet)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the wallet's UTXOs that cost more to spend than they're worth at `fee_rate` (sat/vB).
+#[server(GetUneconomicalUtxos, "/api", "GetJson", "uneconomical-utxos")] // GetJson is a GET and will be cached
+pub async fn get_uneconomical_utxos(mnemonic: String, network: String, fee_rate: f32, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Get the uneconomical UTXOs
+    let outpoints: Vec<String> = find_uneconomical_utxos(&wallet, fee_rate)
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?
+        .iter()
+        .map(|outpoint| outpoint.to_string())
+        .collect();
+
+    // Serialize to JSON
+    let json = to_string(&outpoints)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the master fingerprint of a mnemonic, to match against hardware wallets.
+#[server(GetFingerprint, "/api", "GetJson", "fingerprint")] // GetJson is a GET and will be cached
+pub async fn get_fingerprint(mnemonic: String) -> Result<String, ServerFnError> {
+    let fingerprint = get_master_fingerprint(mnemonic.as_str()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = serde_json::json!({ "fingerprint": fingerprint }).to_string();
+    Ok(json)
+}
+
+/// Returns a JSON string of the dust threshold in sats of an address, the minimum amount it can be sent.
+#[server(GetDustLimit, "/api", "GetJson", "dust-limit")] // GetJson is a GET and will be cached
+pub async fn get_dust_limit(address: String, network: String) -> Result<String, ServerFnError> {
+    let dust_limit = dust_limit_for_address(address.as_str(), network.as_str()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = serde_json::json!({ "dust_limit": dust_limit }).to_string();
+    Ok(json)
+}
+
+/// Returns a JSON string of the fee rate in sat/vB to be included within `target_vsize_depth` vbytes of the mempool,
+/// from a user-supplied JSON fee histogram of `[fee_rate, vsize]` pairs, e.g. from their own node.
+#[server(GetFeeRateFromHistogram, "/api", "GetJson", "fee-from-histogram")] // GetJson is a GET and will be cached
+pub async fn get_fee_rate_from_histogram(histogram: String, target_vsize_depth: u64) -> Result<String, ServerFnError> {
+    let histogram: Vec<(f32, u64)> = serde_json::from_str(histogram.as_str())?;
+
+    // NOTE: never below the minimum relay fee rate
+    let fee_rate = fee_rate_from_histogram(histogram, target_vsize_depth).max(MIN_RELAY_FEE_RATE);
+
+    // Serialize to JSON
+    let json = serde_json::json!({ "fee_rate": fee_rate }).to_string();
+    Ok(json)
+}
+
+/// Returns a JSON string of the wallet's balance, UTXOs, transactions, next receive address and chain tip,
+/// syncing the wallet once instead of once per resource.
+#[server(GetWalletSnapshot, "/api", "GetJson", "snapshot")] // GetJson is a GET and will be cached
+pub async fn get_wallet_snapshot(mnemonic: String, network: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Get the snapshot
+    let snapshot = wallet_snapshot(&mut wallet);
+
+    // Serialize to JSON
+    let json = to_string(&snapshot)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the spending policy of a descriptor in plain English.
+#[server(DescribePolicy, "/api", "GetJson", "policy")] // GetJson is a GET and will be cached
+pub async fn get_policy(descriptor: String, network: String) -> Result<String, ServerFnError> {
+    // Create the wallet
+    let wallet = create_wallet_from_descriptor(descriptor.as_str(), None, network.as_str()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Describe the policy
+    let policy = describe_policy(&wallet).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = serde_json::json!({ "policy": policy }).to_string();
+    Ok(json)
+}
+
+/// Returns a JSON string of the wallet's new transactions, newly confirmed transactions and balance delta
+/// since the UI last checked, given the txids it already knows and which of them were unconfirmed.
+#[server(GetWalletDiff, "/api", "GetJson", "diff")] // GetJson is a GET and will be cached
+pub async fn get_wallet_diff(
+    mnemonic: String,
+    network: String,
+    since_txids: Vec<String>,
+    unconfirmed_txids: Vec<String>,
+    auth_token: Option<String>,
+) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Diff against the known transactions
+    let diff = wallet_diff(&wallet, &since_txids, &unconfirmed_txids).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = to_string(&diff)?;
+    Ok(json)
+}
+
+/// Returns a JSON string of the balance available to spend, excluding frozen coins, immature coinbase outputs
+/// and unconfirmed coins unless `include_unconfirmed` is set.
+#[server(GetSpendableBalance, "/api", "GetJson", "spendable-balance")] // GetJson is a GET and will be cached
+pub async fn get_spendable_balance(
+    mnemonic: String,
+    network: String,
+    include_unconfirmed: Option<bool>,
+    auth_token: Option<String>,
+) -> Result<String, ServerFnError> {
+    // Create the Esplora async client
+    let base_url = esplora_base_url(network.as_str())?;
+    let esplora_client = authenticated_client(base_url, auth_token.as_deref())?;
+
+    // Create the wallet
+    let mut wallet = create_default_wallet(mnemonic.as_str(), network.as_str()).unwrap();
+
+    // Sync Wallet
+    let _ = sync_wallet(&mut wallet, &esplora_client, SyncConfig::default()).await;
+
+    // Get the spendable balance
+    // NOTE: frozen coins aren't persisted yet, so no coin is frozen
+    let policy = SpendablePolicy { include_unconfirmed: include_unconfirmed.unwrap_or(false) };
+    let spendable = spendable_balance(&wallet, &CoinControl::default(), policy);
+
+    // Serialize to JSON
+    let json = serde_json::json!({ "spendable": spendable, "total": wallet.get_balance().total() }).to_string();
+    Ok(json)
+}
+
+/// Returns a JSON string of whether an English mnemonic is valid, with the reason if it isn't.
+#[server(ValidateMnemonic, "/api", "GetJson", "validate-mnemonic")] // GetJson is a GET and will be cached
+pub async fn check_mnemonic(mnemonic: String) -> Result<String, ServerFnError> {
+    let json = match validate_mnemonic(mnemonic.as_str(), Language::English) {
+        Ok(valid) => serde_json::json!({ "valid": valid, "error": null }),
+        Err(e) => serde_json::json!({ "valid": false, "error": e.to_string() }),
+    };
+    Ok(json.to_string())
+}
+
+/// Returns a JSON string of a confirmed transaction's merkle inclusion proof, queried directly from Esplora,
+/// to verify the transaction against its block header.
+#[server(GetMerkleProof, "/api", "GetJson", "merkle-proof")] // GetJson is a GET and will be cached
+pub async fn get_transaction_merkle_proof(network: String, txid: String, auth_token: Option<String>) -> Result<String, ServerFnError> {
+    let txid = Txid::from_str(txid.as_str()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    let base_url = esplora_base_url(network.as_str())?;
+    let headers = auth_headers(auth_token.as_deref()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    let client = reqwest::Client::builder().default_headers(headers).build()?;
+
+    // Get the merkle proof
+    let proof = get_merkle_proof(&txid, base_url, &client)
+        .await
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    // Serialize to JSON
+    let json = to_string(&proof)?;
+    Ok(json)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::api::script_type::{DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL, SEGWIT_DERIVATION_PATH_EXTERNAL, SEGWIT_DERIVATION_PATH_INTERNAL};
+    use bdk::descriptor::calc_checksum;
+
+    use std::any::TypeId;
+
+    use bdk::wallet::{AddressIndex, Wallet};
+    use bdk::bitcoin::{
+        Txid, Transaction, PackedLockTime, BlockHash, TxOut,
+        hashes::{hex::FromHex, Hash},
+    };
+    use bdk_esplora::esplora_client::Builder;
+    use crate::api::{mock::MockEsplora, store::MemoryStore};
+    use bdk_chain::{local_chain::LocalChain, BlockId, ConfirmationTime, ConfirmationTimeAnchor};
+    use std::sync::Mutex;
+
+    /// A scan recorded by a `MockScanner`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub(crate) struct RecordedScan {
+        pub keychain: KeychainKind,
+        /// The indices of the SPKs scanned.
+        pub indices: Vec<u32>,
+        pub stop_gap: usize,
+    }
+
+    /// Stands in for Esplora in a sync, answering each scan with `respond` and recording it.
+    pub(crate) struct MockScanner<F> {
+        respond: F,
+        scans: Mutex<Vec<RecordedScan>>,
+    }
+
+    impl<F> MockScanner<F>
+    where
+        F: Fn(&BTreeMap<u32, BlockHash>, &BTreeMap<KeychainKind, Vec<(u32, Script)>>) -> Result<Update> + Sync,
+    {
+        pub(crate) fn new(respond: F) -> Self {
+            Self {
+                respond,
+                scans: Mutex::new(Vec::new()),
+            }
+        }
+
+        /// The scans made so far, in order.
+        pub(crate) fn scans(&self) -> Vec<RecordedScan> {
+            self.scans.lock().unwrap().clone()
+        }
+    }
+
+    impl<F> Scanner for MockScanner<F>
+    where
+        F: Fn(&BTreeMap<u32, BlockHash>, &BTreeMap<KeychainKind, Vec<(u32, Script)>>) -> Result<Update> + Sync,
+    {
+        fn url(&self) -> &str {
+            "http://localhost:3002"
+        }
+
+        async fn scan_keychains(
+            &self,
+            local_chain: &BTreeMap<u32, BlockHash>,
+            keychain_spks: BTreeMap<KeychainKind, Vec<(u32, Script)>>,
+            stop_gap: usize,
+            _parallel_requests: usize,
+        ) -> Result<Update> {
+            self.scans.lock().unwrap().extend(keychain_spks.iter().map(|(keychain, spks)| RecordedScan {
+                keychain: *keychain,
+                indices: spks.iter().map(|(index, _)| *index).collect(),
+                stop_gap,
+            }));
+            (self.respond)(local_chain, &keychain_spks)
+        }
+    }
+
+    /// A scanner shared by the wallets synced in parallel.
+    impl<S: Scanner + Send + Sync> Scanner for std::sync::Arc<S> {
+        fn url(&self) -> &str {
+            self.as_ref().url()
+        }
+
+        fn scan_keychains(
+            &self,
+            local_chain: &BTreeMap<u32, BlockHash>,
+            keychain_spks: BTreeMap<KeychainKind, Vec<(u32, Script)>>,
+            stop_gap: usize,
+            parallel_requests: usize,
+        ) -> impl Future<Output = Result<Update>> + Send {
+            self.as_ref().scan_keychains(local_chain, keychain_spks, stop_gap, parallel_requests)
+        }
+    }
+
+    fn is_wallet<T: ?Sized + 'static>(_s: &T) -> bool {
+       TypeId::of::<Wallet>() == TypeId::of::<T>()
+    }
+
+    fn is_psbt<T: ?Sized + 'static>(_s: &T) -> bool {
+        TypeId::of::<PartiallySignedTransaction>() == TypeId::of::<T>()
+    }
+
+    /// Return a fake wallet that appears to be funded for testing.
+    pub fn get_funded_wallet_with_change(
+        mnemonic: &str,
+        derivation_path_external: &str,
+        derivation_path_internal: &str,
+    ) -> (Wallet, Txid) {
+        let mut wallet = create_wallet_with_script_type(
+            mnemonic,
+            "regtest",
+            ScriptType::Taproot,
+            derivation_path_external,
+            derivation_path_internal,
+        ).unwrap();
+
+        let txid = fund_wallet(&mut wallet, 50_000, 1_000);
+        (wallet, txid)
+    }
+
+    /// Fund a wallet with a fake transaction paying `value` to a new address, confirmed at `height`.
+    pub fn fund_wallet(wallet: &mut Wallet, value: u64, height: u32) -> Txid {
+        let address = wallet.get_address(AddressIndex::New).address;
+
+        let tx = Transaction {
+            version: 1,
+            lock_time: PackedLockTime(0),
+            input: vec![],
+            output: vec![TxOut {
+                value,
+                script_pubkey: address.script_pubkey(),
+            }],
+        };
+
+        wallet
+            .insert_checkpoint(BlockId {
+                height,
+                hash: BlockHash::all_zeros(),
+            })
+            .unwrap();
+        wallet
+            .insert_tx(
+                tx.clone(),
+                ConfirmationTime::Confirmed {
+                    height,
+                    time: 100,
+                },
+            )
+            .unwrap();
+
+        tx.txid()
+    }
+
+    /// Spend all of the wallet's coins in a signed unconfirmed RBF transaction to the given address.
+    pub fn insert_unconfirmed_drain(wallet: &mut Wallet, address: &str, fee: Fee) -> Transaction {
+        let mut psbt = build_drain_transaction(wallet, address, fee, None).unwrap();
+        wallet.sign(&mut psbt, SignOptions::default()).unwrap();
+        let tx = psbt.extract_tx();
+        wallet.insert_tx(tx.clone(), ConfirmationTime::Unconfirmed { last_seen: 0 }).unwrap();
+        tx
+    }
+
+    #[test]
+    fn test_create_wallet(){
+        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon cactus";
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let wallet_mainnet_12 = create_wallet_with_script_type(
+            mnemonic_12,
+            "mainnet",
+            ScriptType::Taproot,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        ).unwrap();
+        let wallet_mainnet_24 = create_wallet_with_script_type(
+            mnemonic_24,
+            "mainnet",
+            ScriptType::Taproot,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        ).unwrap();
+        let wallet_testnet_12 = create_wallet_with_script_type(
+            mnemonic_12,
+            "testnet",
+            ScriptType::Taproot,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        ).unwrap();
+        let wallet_testnet_24 = create_wallet_with_script_type(
+            mnemonic_24,
+            "testnet",
+            ScriptType::Taproot,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        ).unwrap();
+
+        assert!(is_wallet(&wallet_mainnet_12));
+        assert!(is_wallet(&wallet_mainnet_24));
+        assert!(is_wallet(&wallet_testnet_12));
+        assert!(is_wallet(&wallet_testnet_24));
+        assert_eq!(wallet_mainnet_12.network(), Network::Bitcoin);
+        assert_eq!(wallet_mainnet_24.network(), Network::Bitcoin);
+        assert_eq!(wallet_testnet_12.network(), Network::Testnet);
+        assert_eq!(wallet_testnet_24.network(), Network::Testnet);
+    }
+    #[tokio::test]
+    async fn test_create_signed_transaction() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+ 
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        // NOTE: failing to fetch the fee estimates is an error, not a panic
+        let esplora = MockEsplora::respond_with("500 Internal Server Error", "unavailable").await;
+        let client = Builder::new(esplora.base_url()).build_async().unwrap();
+        let result =
+            create_signed_transaction(&mut wallet, address, None, &client, TxOptions::for_network(Network::Testnet)).await;
+        assert!(result.is_err());
+
+        let esplora = MockEsplora::respond_with("200 OK", r#"{"1": 2.0, "6": 1.0}"#).await;
+        let client = Builder::new(esplora.base_url()).build_async().unwrap();
+        let psbt =
+            create_signed_transaction(&mut wallet, address, None, &client, TxOptions::for_network(Network::Testnet)).await.unwrap();
+        assert!(is_psbt(&psbt));
+
+        utxo_locks().release(psbt.unsigned_tx.input.iter().map(|input| input.previous_output));
+    }
+
+    #[test]
+    fn test_build_drain_transaction_anti_fee_sniping() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        // NOTE: mocked chain tip height
+        let tip = 1_000;
+        let psbt = build_drain_transaction(&mut wallet, address, Fee::Rate(1.0), Some(tip)).unwrap();
+        assert_eq!(psbt.unsigned_tx.lock_time.0, tip);
+
+        let psbt = build_drain_transaction(&mut wallet, address, Fee::Rate(1.0), None).unwrap();
+        assert_eq!(psbt.unsigned_tx.lock_time.0, 0);
+    }
+
+    #[test]
+    fn test_export_history_csv() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        let (wallet, txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        let csv = export_history_csv(&wallet);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "date,txid,type,amount_sat,fee_sat,confirmations,balance_after");
+        assert_eq!(
+            lines[1],
+            format!("1970-01-01T00:01:40Z,{},receive,50000,0,1,50000", txid)
+        );
+    }
+
+    #[test]
+    fn test_export_history_csv_chain_order() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        // NOTE: inserted out of height order
+        let (mut wallet, txid_1000) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let txid_1100 = fund_wallet(&mut wallet, 30_000, 1_100);
+        let txid_900 = fund_wallet(&mut wallet, 20_000, 900);
+        let drain = insert_unconfirmed_drain(&mut wallet, address, Fee::Absolute(1_000));
+
+        let csv = export_history_csv(&wallet);
+        let rows: Vec<Vec<&str>> = csv.lines().skip(1).map(|line| line.split(',').collect()).collect();
+        assert_eq!(rows.len(), 4);
+
+        let expected = [
+            (txid_900.to_string(), "receive", "201", "20000"),
+            (txid_1000.to_string(), "receive", "101", "70000"),
+            (txid_1100.to_string(), "receive", "1", "100000"),
+            (drain.txid().to_string(), "send", "0", "0"),
+        ];
+        for (row, (txid, tx_type, confirmations, balance_after)) in rows.iter().zip(expected.iter()) {
+            assert_eq!(row[1], txid);
+            assert_eq!(row[2], *tx_type);
+            assert_eq!(row[5], *confirmations);
+            assert_eq!(row[6], *balance_after);
+        }
+    }
+
+    #[test]
+    fn test_escape_csv_field() {
+        assert_eq!(escape_csv_field("receive"), "receive");
+        assert_eq!(escape_csv_field("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv_field("a\"b"), "\"a\"\"b\"");
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_fallback() {
+        // NOTE: mocked clients, the primary is down and the secondary accepts the tx
+        let clients = ["primary", "secondary"];
+        let index = try_in_order(&clients, |client| {
+            let client = *client;
+            async move {
+                match client {
+                    "primary" => Err("connection refused"),
+                    _ => Ok(()),
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(clients[index], "secondary");
+
+        let err = try_in_order(&clients, |_client| async { Err::<(), _>("connection refused") })
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Error broadcasting transaction: client 0: connection refused; client 1: connection refused"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_failure_releases_utxos() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        // NOTE: funded with its own value, so that its UTXO isn't shared with other tests locking UTXOs
+        let mut wallet = create_wallet_with_script_type(
+            mnemonic_24,
+            "regtest",
+            ScriptType::Taproot,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        ).unwrap();
+        fund_wallet(&mut wallet, 45_000, 1_000);
+        let mut psbt = build_drain_transaction(&mut wallet, address, Fee::Rate(2.0), None).unwrap();
+        wallet.sign(&mut psbt, SignOptions::default()).unwrap();
+        let outpoints: Vec<OutPoint> = psbt.unsigned_tx.input.iter().map(|input| input.previous_output).collect();
+        utxo_locks().try_lock(outpoints.clone()).unwrap().keep();
+
+        let esplora = MockEsplora::respond_with("400 Bad Request", "min-relay-fee-not-met (code 66)").await;
+        let client = Builder::new(esplora.base_url()).build_async().unwrap();
+        assert!(broadcast_signed_transaction(psbt, &[client]).await.is_err());
+
+        let locked = utxo_locks().locked();
+        assert!(outpoints.iter().all(|outpoint| !locked.contains(outpoint)));
+    }
+
+    #[test]
+    fn test_reset_checkpoint_below_funding_height() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        // NOTE: mocked block below the funding height
+        let block = BlockId {
+            height: 900,
+            hash: BlockHash::all_zeros(),
+        };
+        assert!(reset_checkpoint(&mut wallet, block).unwrap());
+        assert!(wallet.checkpoints().contains_key(&900));
+        assert_eq!(wallet.get_balance().total(), 50_000);
+    }
+
+    #[tokio::test]
+    async fn test_rescan_rediscovers_transactions() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        // NOTE: like the server fns, a fresh wallet without any transactions
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+        let script_pubkey = wallet.get_address(AddressIndex::Peek(0)).address.script_pubkey();
+
+        // Esplora has a transaction funding the first address confirmed at height 1000, above the rescan block,
+        // and an older one at height 800, below it
+        let old_block = BlockId { height: 800, hash: BlockHash::hash(b"block 800") };
+        let rescan_block = BlockId { height: 900, hash: BlockHash::hash(b"block 900") };
+        let funding_block = BlockId { height: 1_000, hash: BlockHash::hash(b"block 1000") };
+        let fundings: Vec<(Transaction, BlockId)> = [(50_000, funding_block), (30_000, old_block)]
+            .into_iter()
+            .map(|(value, block)| {
+                let tx = Transaction {
+                    version: 1,
+                    lock_time: PackedLockTime(0),
+                    input: vec![],
+                    output: vec![TxOut { value, script_pubkey: script_pubkey.clone() }],
+                };
+                (tx, block)
+            })
+            .collect();
+        let scanned = fundings.clone();
+        let scanner = MockScanner::new(move |_, keychain_spks| {
+            let mut update = Update {
+                chain: LocalChain::from_blocks([old_block, rescan_block, funding_block]),
+                ..Update::default()
+            };
+            if keychain_spks.values().flatten().any(|(_, spk)| *spk == script_pubkey) {
+                for (tx, block) in &scanned {
+                    let _ = update.graph.insert_tx(tx.clone());
+                    let _ = update.graph.insert_anchor(
+                        tx.txid(),
+                        ConfirmationTimeAnchor { anchor_block: *block, confirmation_height: block.height, confirmation_time: 100 },
+                    );
+                }
+                update.keychain.insert(KeychainKind::External, 0);
+            }
+            Ok(update)
+        });
+
+        let result = rescan_wallet_at_block(&mut wallet, &scanner, rescan_block).await.unwrap();
+        assert!(result.changed);
+        assert!(wallet.checkpoints().contains_key(&900));
+        // NOTE: a full rescan, the transaction below the rescan block is found too
+        for (tx, _) in &fundings {
+            assert!(wallet.transactions().any(|canonical| canonical.node.txid == tx.txid() && canonical.observed_as.is_confirmed()));
+        }
+        assert_eq!(wallet.get_balance().confirmed, 80_000);
+    }
+
+    #[test]
+    fn test_get_chain_tip() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        let (wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        let tip = get_chain_tip(&wallet).unwrap();
+        assert_eq!(tip.height, 1_000);
+        assert_eq!(tip.hash, BlockHash::all_zeros().to_string());
+    }
+
+    #[test]
+    fn test_create_wallet_from_multipath_descriptor() {
+        // NOTE: BIP86 test vectors for the "abandon ... about" mnemonic
+        let descriptor = "tr(xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/<0;1>/*)";
+        let mut wallet = create_wallet_from_descriptor(descriptor, None, "mainnet").unwrap();
+
+        assert_eq!(
+            wallet.get_address(AddressIndex::Peek(0)).address.to_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+        assert_eq!(
+            wallet.get_internal_address(AddressIndex::Peek(0)).address.to_string(),
+            "bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7"
+        );
+    }
+
+    #[test]
+    fn test_can_sign() {
+        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let wallet = create_wallet_with_script_type(
+            mnemonic_12,
+            "mainnet",
+            ScriptType::Taproot,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        ).unwrap();
+        assert!(can_sign(&wallet));
+
+        let descriptor = "tr(xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/<0;1>/*)";
+        let wallet = create_wallet_from_descriptor(descriptor, None, "mainnet").unwrap();
+        assert!(!can_sign(&wallet));
+    }
+
+    #[test]
+    fn test_address_prefix_per_network_and_script_type() {
+        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let cases = [
+            ("mainnet", ScriptType::Taproot, "bc1p"),
+            ("mainnet", ScriptType::NativeSegwit, "bc1q"),
+            ("testnet", ScriptType::Taproot, "tb1p"),
+            ("testnet", ScriptType::NativeSegwit, "tb1q"),
+            ("regtest", ScriptType::Taproot, "bcrt1p"),
+        ];
+        for (network, script_type, prefix) in cases {
+            let (external, internal) = script_type.derivation_paths();
+            let mut wallet = create_wallet_with_script_type(mnemonic_12, network, script_type, external, internal).unwrap();
+            let address = wallet.get_address(AddressIndex::Peek(0)).address;
+
+            assert!(address.to_string().starts_with(prefix));
+            assert!(validate_address_prefix(&address, wallet.network(), script_type).is_ok());
+        }
+
+        let mut wallet = create_wallet_with_script_type(
+            mnemonic_12,
+            "mainnet",
+            ScriptType::Taproot,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        ).unwrap();
+        let address = wallet.get_address(AddressIndex::Peek(0)).address;
+        assert!(validate_address_prefix(&address, Network::Testnet, ScriptType::Taproot).is_err());
+        assert!(validate_address_prefix(&address, Network::Bitcoin, ScriptType::NativeSegwit).is_err());
+    }
+
+    #[test]
+    fn test_build_drain_transaction_absolute_fee() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        let psbt = build_drain_transaction(&mut wallet, address, Fee::Absolute(2_000), None).unwrap();
+        let input: u64 = psbt.inputs.iter().map(|input| input.witness_utxo.as_ref().unwrap().value).sum();
+        let output: u64 = psbt.unsigned_tx.output.iter().map(|output| output.value).sum();
+        assert_eq!(input - output, 2_000);
+    }
+
+    #[test]
+    fn test_tx_options_fee() {
+        let options = TxOptions::for_network(Network::Regtest);
+        assert_eq!(options.fee().unwrap(), None);
+
+        let options = TxOptions { absolute_fee_sat: Some(2_000), ..options };
+        assert_eq!(options.fee().unwrap(), Some(Fee::Absolute(2_000)));
+
+        let options = TxOptions { fee_rate: Some(1.0), ..options };
+        assert!(options.fee().is_err());
+    }
+
+    #[test]
+    fn test_can_bump_fee() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        let (mut wallet, funding_txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        let tx = insert_unconfirmed_drain(&mut wallet, address, Fee::Rate(1.0));
+        assert!(can_bump_fee(&wallet, tx.txid()).unwrap());
+        assert!(!can_bump_fee(&wallet, funding_txid).unwrap());
+        assert!(can_bump_fee(&wallet, Txid::all_zeros()).is_err());
+    }
+
+    #[test]
+    fn test_create_wallet_from_seed() {
+        // NOTE: BIP39 seed of the "abandon ... about" mnemonic
+        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let seed = Vec::<u8>::from_hex("5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc19a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4").unwrap();
+
+        let mut wallet_seed = create_wallet_from_seed(
+            &seed,
+            "mainnet",
+            ScriptType::Taproot,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        ).unwrap();
+        let mut wallet_mnemonic = create_wallet_with_script_type(
+            mnemonic_12,
+            "mainnet",
+            ScriptType::Taproot,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        ).unwrap();
+
+        assert_eq!(
+            wallet_seed.get_address(AddressIndex::Peek(0)).address,
+            wallet_mnemonic.get_address(AddressIndex::Peek(0)).address
+        );
+        assert_eq!(
+            wallet_seed.get_internal_address(AddressIndex::Peek(0)).address,
+            wallet_mnemonic.get_internal_address(AddressIndex::Peek(0)).address
+        );
+        assert_eq!(
+            wallet_seed.get_address(AddressIndex::Peek(0)).address.to_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+    }
+
+    #[test]
+    fn test_create_wallet_from_seed_invalid_length() {
+        for length in [15, 65] {
+            let seed = vec![0u8; length];
+            assert!(create_wallet_from_seed(
+                &seed,
+                "mainnet",
+                ScriptType::Taproot,
+                DEFAULT_DERIVATION_PATH_EXTERNAL,
+                DEFAULT_DERIVATION_PATH_INTERNAL,
+            ).is_err());
+        }
+    }
+
+    #[test]
+    fn test_create_wallet_invalid_derivation_path() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let store = MemoryStore::new();
+        let err = create_wallet(mnemonic_24, "mainnet", "m/86'/foo", DEFAULT_DERIVATION_PATH_INTERNAL, &store).err().unwrap();
+        assert!(err.to_string().starts_with("Invalid external derivation path m/86'/foo"));
+        let err = create_wallet(mnemonic_24, "mainnet", DEFAULT_DERIVATION_PATH_EXTERNAL, "86'/0'", &store).err().unwrap();
+        assert!(err.to_string().starts_with("Invalid internal derivation path 86'/0'"));
+    }
+
+    #[test]
+    fn test_check_standardness_min_relay_fee() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let mut psbt = build_drain_transaction(&mut wallet, address, Fee::Absolute(10), None).unwrap();
+        wallet.sign(&mut psbt, SignOptions::default()).unwrap();
+
+        let result = check_standardness(&psbt).unwrap();
+        assert!(!result.accepted);
+        assert!(result.reject_reason.unwrap().starts_with("min relay fee not met"));
+
+        // 100 sats would be over 1 sat/vB of the 94 vB unsigned transaction, but not of the 111 vB signed one
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let mut psbt = build_drain_transaction(&mut wallet, address, Fee::Absolute(100), None).unwrap();
+        assert!(check_standardness(&psbt).is_err());
+        wallet.sign(&mut psbt, SignOptions::default()).unwrap();
+        assert!(!check_standardness(&psbt).unwrap().accepted);
+
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let mut psbt = build_drain_transaction(&mut wallet, address, Fee::Rate(2.0), None).unwrap();
+        wallet.sign(&mut psbt, SignOptions::default()).unwrap();
+
+        assert_eq!(check_standardness(&psbt).unwrap(), AcceptResult::accepted());
+    }
+
+    #[test]
+    fn test_sign_with_low_r_grinding() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        // NOTE: low-R only applies to ECDSA signatures, so use a segwit wallet
+        let (external, internal) = ScriptType::NativeSegwit.derivation_paths();
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::NativeSegwit, external, internal).unwrap();
+        for value in [10_000, 20_000, 30_000, 40_000] {
+            fund_wallet(&mut wallet, value, 1_000);
+        }
+        let psbt = build_drain_transaction(&mut wallet, address, Fee::Absolute(1_000), None).unwrap();
+
+        let options = TxOptions::for_network(Network::Regtest);
+        let mut psbt_low_r = psbt.clone();
+        wallet.sign(&mut psbt_low_r, options.sign_options()).unwrap();
+        let options = TxOptions { allow_grinding: false, ..options };
+        let mut psbt_high_r = psbt;
+        wallet.sign(&mut psbt_high_r, options.sign_options()).unwrap();
+
+        assert!(psbt_low_r.extract_tx().vsize() <= psbt_high_r.extract_tx().vsize());
+    }
+
+    #[test]
+    fn test_parse_network_aliases() {
+        assert_eq!(parse_network("mainnet").unwrap(), Network::Bitcoin);
+        assert_eq!(parse_network("bitcoin").unwrap(), Network::Bitcoin);
+        assert_eq!(parse_network("testnet").unwrap(), Network::Testnet);
+        assert_eq!(parse_network("test").unwrap(), Network::Testnet);
+        assert_eq!(parse_network("signet").unwrap(), Network::Signet);
+        assert_eq!(parse_network("regtest").unwrap(), Network::Regtest);
+        assert!(parse_network("foo").is_err());
+    }
+
+    #[test]
+    fn test_esplora_base_url() {
+        assert_eq!(esplora_base_url("mainnet").unwrap(), DEFAULT_ESPLORA_BASE_URL_MAINNET);
+        assert_eq!(esplora_base_url("signet").unwrap(), DEFAULT_ESPLORA_BASE_URL_TESTNET);
+        assert_eq!(fallback_esplora_base_url("bitcoin").unwrap(), FALLBACK_ESPLORA_BASE_URL_MAINNET);
+        assert!(esplora_base_url("foo").is_err());
+        assert!(fallback_esplora_base_url("foo").is_err());
+
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        assert!(create_default_wallet(mnemonic_24, "foo").is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "silent-payments"))]
+    fn test_send_to_silent_payment_unsupported() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        let sp_address = "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc9pkqwv";
+        let err = send_to_silent_payment(&mut wallet, sp_address, 10_000).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<WalletError>(),
+            Some(WalletError::UnsupportedFeature(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stop_gaps_per_keychain() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+        let stop_gaps = StopGaps {
+            external: 20,
+            internal: 5,
+        };
+
+        let scanner = MockScanner::new(|_, _| Ok(Update::default()));
+        sync_wallet_with_stop_gaps(&mut wallet, &scanner, stop_gaps, 5).await.unwrap();
+
+        // each keychain is scanned on its own, with its own stop gap and up to it
+        let scanned = |keychain: KeychainKind| {
+            let scans: Vec<RecordedScan> = scanner.scans().into_iter().filter(|scan| scan.keychain == keychain).collect();
+            assert!(scans.iter().all(|scan| scan.stop_gap == stop_gaps.for_keychain(keychain)));
+            scans.into_iter().flat_map(|scan| scan.indices).collect::<Vec<u32>>()
+        };
+        assert_eq!(scanned(KeychainKind::External), (0..20).collect::<Vec<u32>>());
+        assert_eq!(scanned(KeychainKind::Internal), (0..5).collect::<Vec<u32>>());
+        assert_eq!(StopGaps::default().for_keychain(KeychainKind::External), 5);
+    }
+
+    #[test]
+    fn test_bip69_ordering() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        fund_wallet(&mut wallet, 30_000, 1_001);
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        let options = TxOptions::for_network(Network::Regtest);
+        assert_eq!(options.ordering(), TxOrdering::Bip69Lexicographic);
+
+        let mut psbt = build_transaction(&mut wallet, address, Some(60_000), Fee::Rate(1.0), None, &options).unwrap();
+        wallet.sign(&mut psbt, options.sign_options()).unwrap();
+        let tx = psbt.extract_tx();
+
+        // payment and change
+        assert_eq!(tx.output.len(), 2);
+        let outputs: Vec<_> = tx.output.iter().map(|o| (o.value, o.script_pubkey.clone())).collect();
+        let mut sorted_outputs = outputs.clone();
+        sorted_outputs.sort();
+        assert_eq!(outputs, sorted_outputs);
+
+        // both funding coins are needed
+        assert_eq!(tx.input.len(), 2);
+        let inputs: Vec<_> = tx.input.iter().map(|i| i.previous_output).collect();
+        let mut sorted_inputs = inputs.clone();
+        sorted_inputs.sort();
+        assert_eq!(inputs, sorted_inputs);
+    }
+
+    #[test]
+    fn test_sweep_wif() {
+        // private key 1, compressed, for the test networks
+        let wif = "cMahea7zqjxrtgAbB7LSGbcQUr1uX1ojuat9jZodMN87JcbXMTcA";
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        let mut wallet = create_wif_wallet(wif, Network::Testnet).unwrap();
+        assert!(build_sweep_transaction(&mut wallet, address, 1.0).is_err());
+        let funding_txid = fund_wallet(&mut wallet, 10_000, 1_000);
+        assert_eq!(
+            wallet.get_address(AddressIndex::Peek(0)).address.to_string(),
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"
+        );
+
+        let psbt = build_sweep_transaction(&mut wallet, address, 1.0).unwrap();
+        assert_eq!(psbt.unsigned_tx.input.len(), 1);
+        assert_eq!(psbt.unsigned_tx.input[0].previous_output.txid, funding_txid);
+        assert_eq!(psbt.unsigned_tx.output.len(), 1);
+        assert_eq!(psbt.unsigned_tx.output[0].script_pubkey, Address::from_str(address).unwrap().script_pubkey());
+        assert!(psbt.inputs[0].final_script_witness.is_some());
+
+        // mainnet key on a test network
+        assert!(create_wif_wallet("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn", Network::Testnet).is_err());
+        assert!(create_wif_wallet("not a wif", Network::Testnet).is_err());
+    }
+
+    #[test]
+    fn test_locked_utxos_are_not_reused() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        fund_wallet(&mut wallet, 30_000, 1_001);
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        let options = TxOptions::for_network(Network::Regtest);
+        let locks = UtxoLocks::default();
+
+        let inputs = |psbt: &PartiallySignedTransaction| psbt.unsigned_tx.input.iter().map(|input| input.previous_output).collect::<Vec<_>>();
+        let (first, first_guard) = build_locked_transaction(&mut wallet, address, Some(20_000), Fee::Rate(1.0), None, &options, &locks).unwrap();
+        let (second, second_guard) = build_locked_transaction(&mut wallet, address, Some(20_000), Fee::Rate(1.0), None, &options, &locks).unwrap();
+        assert!(inputs(&second).iter().all(|outpoint| !inputs(&first).contains(outpoint)));
+        assert_eq!(first_guard.outpoints(), inputs(&first).as_slice());
+
+        // both coins are reserved now
+        assert_eq!(locks.locked().len(), 2);
+        assert!(build_locked_transaction(&mut wallet, address, Some(20_000), Fee::Rate(1.0), None, &options, &locks).is_err());
+
+        // a dropped guard releases its coins, e.g. when signing fails
+        drop(first_guard);
+        let (third, third_guard) = build_locked_transaction(&mut wallet, address, Some(20_000), Fee::Rate(1.0), None, &options, &locks).unwrap();
+        assert_eq!(inputs(&third), inputs(&first));
+
+        // kept coins stay reserved until released on broadcast
+        second_guard.keep();
+        third_guard.keep();
+        assert_eq!(locks.locked().len(), 2);
+        locks.release(inputs(&first));
+        assert_eq!(locks.locked(), inputs(&second));
+    }
+
+    #[test]
+    fn test_get_derivation_info() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        let info = get_derivation_info(mnemonic_24, "testnet", ScriptType::Taproot).unwrap();
+        assert_eq!(info.script_type, ScriptType::Taproot);
+        assert_eq!(info.external_path, "m/86'/0'/0'/0");
+        assert_eq!(info.internal_path, "m/86'/0'/0'/1");
+        assert_eq!(info.network, "testnet");
+        assert_eq!(info.master_fingerprint, "5436d724");
+
+        // the fingerprint doesn't depend on the network or script type
+        let info = get_derivation_info(mnemonic_24, "mainnet", ScriptType::NativeSegwit).unwrap();
+        assert_eq!(info.external_path, "m/84'/0'/0'/0");
+        assert_eq!(info.master_fingerprint, "5436d724");
+    }
+
+    #[test]
+    fn test_verify_balance_integrity() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        assert!(verify_balance_integrity(&wallet).unwrap());
+
+        fund_wallet(&mut wallet, 30_000, 1_001);
+        let integrity = balance_integrity(&wallet).unwrap();
+        assert_eq!(integrity.balance, 80_000);
+        assert_eq!(integrity.utxo_total, 80_000);
+        assert!(integrity.consistent);
+    }
+
+    #[test]
+    fn test_fee_rate_for_target() {
+        let fee_estimates: HashMap<String, f64> = [("1", 20.5), ("2", 15.0), ("6", 8.25), ("144", 1.0)]
+            .into_iter()
+            .map(|(block, fee_rate)| (block.to_string(), fee_rate))
+            .collect();
+
+        let next_block = fee_rate_for_target(&fee_estimates, 1).unwrap();
+        let six_blocks = fee_rate_for_target(&fee_estimates, 6).unwrap();
+        assert_eq!(next_block, 20.5);
+        assert_eq!(six_blocks, 8.25);
+        assert!(six_blocks <= next_block);
+
+        // missing targets use the closest lower one
+        assert_eq!(fee_rate_for_target(&fee_estimates, 10).unwrap(), 8.25);
+        assert_eq!(fee_rate_for_target(&fee_estimates, 1008).unwrap(), 1.0);
+        assert_eq!(fee_rate_for_target(&fee_estimates, 0).unwrap(), 20.5);
+        assert!(fee_rate_for_target(&HashMap::new(), 1).is_err());
+    }
+
+    #[test]
+    fn test_list_coins() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        let coins = list_coins(&wallet, &CoinControl::default());
+        assert_eq!(coins.len(), 1);
+        let coin = &coins[0];
+        assert_eq!(coin.outpoint, format!("{}:0", txid));
+        assert_eq!(coin.value, 50_000);
+        assert_eq!(coin.confirmations, 1);
+        assert_eq!(coin.address, Some(wallet.get_address(AddressIndex::Peek(0)).address.to_string()));
+        assert_eq!(coin.keychain, "External");
+        assert_eq!(coin.label, None);
+        assert!(!coin.frozen);
+
+        let outpoint = OutPoint::new(txid, 0);
+        let coin_control = CoinControl {
+            labels: HashMap::from([(outpoint, "salary".to_string())]),
+            frozen: HashSet::from([outpoint]),
+        };
+        let coins = list_coins(&wallet, &coin_control);
+        assert_eq!(coins[0].label.as_deref(), Some("salary"));
+        assert!(coins[0].frozen);
+    }
+
+    #[test]
+    fn test_create_receive_only_wallet_from_descriptor() {
+        // NOTE: BIP86 "abandon ... about" account key, as a tpub for regtest
+        let descriptor = "tr(tpubDC3pD7UZXnsgh3EBjbtBQiB1FnLask7UHBSunZ1DPK4dCFFZoFRkgxHB8gt42FvLzx1DpxfHWxAsYaY6b643RVcGjDxXxns7wKKYnnfEcbB/0/*)";
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        let mut wallet = create_wallet_from_descriptor(descriptor, None, "regtest").unwrap();
+        fund_wallet(&mut wallet, 50_000, 1_000);
+        assert_eq!(wallet.get_balance().total(), 50_000);
+
+        // change goes back to the external keychain
+        let options = TxOptions::for_network(Network::Regtest);
+        let psbt = build_transaction(&mut wallet, address, Some(20_000), Fee::Rate(1.0), None, &options).unwrap();
+        let change = psbt.unsigned_tx.output.iter().find(|output| wallet.is_mine(&output.script_pubkey)).unwrap();
+        let external_scripts: Vec<_> = (0..5).map(|index| wallet.get_address(AddressIndex::Peek(index)).script_pubkey()).collect();
+        assert!(external_scripts.contains(&change.script_pubkey));
+
+        let psbt = build_drain_transaction(&mut wallet, address, Fee::Rate(1.0), None).unwrap();
+        assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    }
+
+    #[test]
+    fn test_payment_breakdown() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        let options = TxOptions::for_network(Network::Regtest);
+
+        let psbt = build_transaction(&mut wallet, address, Some(20_000), Fee::Rate(2.0), None, &options).unwrap();
+        let breakdown = payment_breakdown(&wallet, &psbt, address).unwrap();
+        assert_eq!(breakdown.total_input, 50_000);
+        assert_eq!(breakdown.recipient, 20_000);
+        assert!(breakdown.change > 0);
+        assert!(breakdown.fee > 0);
+        assert_eq!(breakdown.fee + breakdown.change + breakdown.recipient, breakdown.total_input);
+    }
+
+    #[test]
+    fn test_estimate_sync_time() {
+        let keychains = [KeychainKind::External, KeychainKind::Internal];
+        let latency = Duration::from_millis(200);
+
+        let small = estimate_sync_time(&keychains, StopGaps { external: 5, internal: 5 }, latency, 5);
+        let large = estimate_sync_time(&keychains, StopGaps { external: 50, internal: 5 }, latency, 5);
+        // one batch per keychain plus the chain tip
+        assert_eq!(small, Duration::from_millis(600));
+        assert_eq!(large, Duration::from_millis(2_400));
+        assert!(large > small);
+        assert!(estimate_sync_time(&keychains[..1], StopGaps::default(), latency, 5) < small);
+        // more parallel requests, fewer batches
+        assert!(estimate_sync_time(&keychains, StopGaps { external: 50, internal: 5 }, latency, 20) < large);
+    }
+
+    #[test]
+    fn test_list_received_addresses() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        // revealed but unused
+        wallet.get_address(AddressIndex::New);
+
+        let received = list_received_addresses(&wallet);
+        assert_eq!(
+            received,
+            vec![AddressReceived {
+                index: 0,
+                address: wallet.get_address(AddressIndex::Peek(0)).address.to_string(),
+                total_received: 50_000,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_cancel_transaction() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, funding_txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        let tx = insert_unconfirmed_drain(&mut wallet, address, Fee::Rate(1.0));
+        let original_fee = 50_000 - tx.output[0].value;
+
+        // not enough to replace
+        assert!(build_cancel_transaction(&mut wallet, tx.txid(), 1.0).is_err());
+
+        let mut psbt = build_cancel_transaction(&mut wallet, tx.txid(), 5.0).unwrap();
+        wallet.sign(&mut psbt, SignOptions::default()).unwrap();
+        let replacement = psbt.extract_tx();
+        assert_eq!(replacement.input.len(), 1);
+        assert_eq!(replacement.input[0].previous_output, tx.input[0].previous_output);
+        assert_eq!(replacement.output.len(), 1);
+        assert!(wallet.is_mine(&replacement.output[0].script_pubkey));
+        assert!(50_000 - replacement.output[0].value > original_fee);
+
+        // confirmed transactions can't be cancelled
+        assert!(build_cancel_transaction(&mut wallet, funding_txid, 5.0).is_err());
+
+        // a 110 vB P2WPKH replacement pays 550 sats at 5 sat/vB
+        let (external, internal) = ScriptType::NativeSegwit.derivation_paths();
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::NativeSegwit, external, internal).unwrap();
+        fund_wallet(&mut wallet, 50_000, 1_000);
+        let tx = insert_unconfirmed_drain(&mut wallet, address, Fee::Rate(1.0));
+        let psbt = build_cancel_transaction(&mut wallet, tx.txid(), 5.0).unwrap();
+        assert_eq!(50_000 - psbt.unsigned_tx.output[0].value, 550);
+    }
+
+    #[test]
+    fn test_reveal_addresses() {
+        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mut wallet = create_wallet_with_script_type(
+            mnemonic_12,
+            "mainnet",
+            ScriptType::Taproot,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        ).unwrap();
+
+        let addresses = reveal_addresses(&mut wallet, 5);
+        assert_eq!(addresses.iter().map(|address| address.index).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(addresses[0].address.to_string(), "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr");
+        assert_eq!(wallet.get_address(AddressIndex::New).index, 5);
+    }
+
+    #[test]
+    fn test_address_has_history() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        // the funded address is at index 0
+        assert!(address_has_history(&wallet, 0).unwrap());
+        assert!(!address_has_history(&wallet, 1).unwrap());
+    }
+
+    #[test]
+    fn test_create_watch_only_wallet_hardened_wildcard() {
+        let descriptor = "tr(xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/*h)";
+        let err = create_wallet_from_descriptor(descriptor, None, "mainnet").unwrap_err();
+        assert!(err.to_string().starts_with("Hardened derivation requires private keys"));
+
+        // also when only the change descriptor is hardened
+        let change = descriptor.replace("/0/*h", "/1/*h");
+        let err = create_wallet_from_descriptor(&descriptor.replace("/0/*h", "/0/*"), Some(&change), "mainnet").unwrap_err();
+        assert!(err.to_string().starts_with("Hardened derivation requires private keys"));
+    }
+
+    #[test]
+    fn test_create_watch_only_wallet_from_slip132() {
+        // NOTE: BIP84 and BIP49 test vectors for the "abandon ... about" mnemonic
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let mut wallet = create_watch_only_wallet(zpub, "mainnet").unwrap();
+        assert_eq!(
+            wallet.get_address(AddressIndex::Peek(0)).address.to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert_eq!(
+            wallet.get_internal_address(AddressIndex::Peek(0)).address.to_string(),
+            "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el"
+        );
+        assert!(!can_sign(&wallet));
+
+        let ypub = "ypub6Ww3ibxVfGzLrAH1PNcjyAWenMTbbAosGNB6VvmSEgytSER9azLDWCxoJwW7Ke7icmizBMXrzBx9979FfaHxHcrArf3zbeJJJUZPf663zsP";
+        let mut wallet = create_watch_only_wallet(ypub, "mainnet").unwrap();
+        let address = wallet.get_address(AddressIndex::Peek(0)).address;
+        assert_eq!(address.to_string(), "37VucYSaXLCAsxYyAPfbSi9eh4iEcbShgf");
+        assert!(validate_address_prefix(&address, Network::Bitcoin, ScriptType::NestedSegwit).is_ok());
+
+        // plain xpubs default to taproot
+        let xpub = "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ";
+        let mut wallet = create_watch_only_wallet(xpub, "mainnet").unwrap();
+        assert_eq!(
+            wallet.get_address(AddressIndex::Peek(0)).address.to_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+    }
+
+    #[test]
+    fn test_list_transactions_fee_rate() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, funding_txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        let tx = insert_unconfirmed_drain(&mut wallet, address, Fee::Rate(5.0));
+
+        let transactions = list_transactions(&wallet);
+        assert_eq!(transactions.len(), 2);
+
+        let incoming = transactions.iter().find(|entry| entry.txid == funding_txid.to_string()).unwrap();
+        assert_eq!(incoming.fee_rate, None);
+
+        let outgoing = transactions.iter().find(|entry| entry.txid == tx.txid().to_string()).unwrap();
+        let fee_rate = outgoing.fee_rate.unwrap();
+        assert!((4.9..=5.5).contains(&fee_rate), "unexpected fee rate {}", fee_rate);
+    }
+
+    #[test]
+    fn test_utxo_age_histogram() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        // the tip is the funding block
+        let histogram = utxo_age_histogram(&wallet).unwrap();
+        assert_eq!(histogram[0], (AgeBucket::Day, 1, 50_000));
+        assert!(histogram[1..].iter().all(|(_, count, value)| *count == 0 && *value == 0));
+
+        // 500 blocks later
+        wallet
+            .insert_checkpoint(BlockId {
+                height: 1_500,
+                hash: BlockHash::all_zeros(),
+            })
+            .unwrap();
+        let histogram = utxo_age_histogram(&wallet).unwrap();
+        assert_eq!(histogram[1], (AgeBucket::Week, 1, 50_000));
+        assert_eq!(histogram[0], (AgeBucket::Day, 0, 0));
+    }
+
+    #[test]
+    fn test_validate_address_index() {
+        assert_eq!(validate_address_index(0).unwrap(), 0);
+        assert_eq!(validate_address_index(MAX_NON_HARDENED_INDEX).unwrap(), 2_147_483_647);
+        assert!(validate_address_index(MAX_NON_HARDENED_INDEX + 1).is_err());
+        assert!(validate_address_index(u32::MAX as usize).is_err());
+
+        // a large valid index derives an address
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+        let index = validate_address_index(1_000).unwrap();
+        assert_eq!(wallet.get_address(AddressIndex::Peek(index)).index, index);
+    }
+
+    #[test]
+    fn test_peek_address() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+
+        // the same addresses as BDK's peek
+        for (keychain, index) in [(KeychainKind::External, 0), (KeychainKind::External, 1_000), (KeychainKind::Internal, 7)] {
+            let peeked = match keychain {
+                KeychainKind::External => wallet.get_address(AddressIndex::Peek(index)),
+                KeychainKind::Internal => wallet.get_internal_address(AddressIndex::Peek(index)),
+            };
+            assert_eq!(peek_address(&wallet, keychain, index).unwrap(), peeked);
+        }
+
+        // the largest index is derived directly, without the addresses below it
+        let start = Instant::now();
+        let address = peek_address(&wallet, KeychainKind::External, MAX_NON_HARDENED_INDEX as u32).unwrap();
+        assert_eq!(address.index, 2_147_483_647);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(peek_address(&wallet, KeychainKind::External, MAX_NON_HARDENED_INDEX as u32 + 1).is_err());
+    }
+
+    #[test]
+    fn test_descriptors_equal() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+
+        // import the exported public descriptors
+        let external = wallet.get_descriptor_for_keychain(KeychainKind::External).to_string();
+        let internal = wallet.get_descriptor_for_keychain(KeychainKind::Internal).to_string();
+        let twin = create_wallet_from_descriptor(&external, Some(&internal), "regtest").unwrap();
+        assert!(descriptors_equal(&wallet, &twin));
+
+        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon cactus";
+        let other = create_wallet_with_script_type(mnemonic_12, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+        assert!(!descriptors_equal(&wallet, &other));
+    }
+
+    #[test]
+    fn test_change_position() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        for (position, expected_index) in [(ChangePosition::First, 0), (ChangePosition::Last, 1)] {
+            let (mut wallet, _txid) = get_funded_wallet_with_change(
+                mnemonic_24,
+                DEFAULT_DERIVATION_PATH_EXTERNAL,
+                DEFAULT_DERIVATION_PATH_INTERNAL,
+            );
+            let options = TxOptions { change_position: position, ..TxOptions::for_network(Network::Regtest) };
+            let mut psbt = build_transaction(&mut wallet, address, Some(10_000), Fee::Rate(1.0), None, &options).unwrap();
+
+            let change = &psbt.unsigned_tx.output[expected_index];
+            assert!(matches!(
+                wallet.spk_index().index_of_spk(&change.script_pubkey),
+                Some((KeychainKind::Internal, _))
+            ));
+            assert_eq!(psbt.unsigned_tx.output.len(), psbt.outputs.len());
+
+            // the signatures commit to the final output order
+            assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+        }
+
+        // a random position still keeps every output, with the PSBT outputs moved along
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let options = TxOptions { change_position: ChangePosition::Random, ..TxOptions::for_network(Network::Regtest) };
+        let mut psbt = build_transaction(&mut wallet, address, Some(10_000), Fee::Rate(1.0), None, &options).unwrap();
+        assert_eq!(psbt.unsigned_tx.output.len(), 2);
+        assert_eq!(psbt.unsigned_tx.output.len(), psbt.outputs.len());
+        assert!(identify_change_output(&wallet, &psbt).is_some());
+        assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    }
+
+    #[test]
+    fn test_default_ordering() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        // by default the change output is where BIP69 sorts it
+        let options = TxOptions::for_network(Network::Regtest);
+        assert_eq!(options.ordering(), TxOrdering::Bip69Lexicographic);
+        assert_eq!(options.change_position, ChangePosition::Ordered);
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let psbt = build_transaction(&mut wallet, address, Some(10_000), Fee::Rate(1.0), None, &options).unwrap();
+        let outputs: Vec<_> = psbt.unsigned_tx.output.iter().map(|o| (o.value, o.script_pubkey.clone())).collect();
+        let mut sorted_outputs = outputs.clone();
+        sorted_outputs.sort();
+        assert_eq!(outputs, sorted_outputs);
+
+        // shuffled only with BIP69 ordering off
+        let options = TxOptions { bip69_ordering: false, ..options };
+        assert_eq!(options.ordering(), TxOrdering::Shuffle);
+    }
+
+    #[test]
+    fn test_parse_address_for_network() {
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        assert!(parse_address_for_network(address, Network::Testnet).is_ok());
+        assert!(parse_address_for_network(address, Network::Signet).is_ok());
+        assert!(parse_address_for_network(address, Network::Bitcoin).is_err());
+        assert!(parse_address_for_network("not an address", Network::Testnet).is_err());
+    }
+
+    #[test]
+    fn test_export_core_import_request() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+
+        let request = export_core_import_request(&wallet, Some(1_700_000_000));
+        let request: serde_json::Value = serde_json::from_str(&request).unwrap();
+        let request = request.as_array().unwrap();
+        assert_eq!(request.len(), 2);
+
+        let external = request.iter().find(|entry| entry["internal"] == false).unwrap();
+        let internal = request.iter().find(|entry| entry["internal"] == true).unwrap();
+        assert_eq!(external["desc"], wallet.get_descriptor_for_keychain(KeychainKind::External).to_string());
+        assert_eq!(internal["desc"], wallet.get_descriptor_for_keychain(KeychainKind::Internal).to_string());
+        for entry in [external, internal] {
+            let desc = entry["desc"].as_str().unwrap();
+            let (body, checksum) = desc.split_once('#').unwrap();
+            assert_eq!(checksum, calc_checksum(body).unwrap());
+            assert_eq!(entry["active"], true);
+            assert_eq!(entry["timestamp"], 1_700_000_000);
+            assert_eq!(entry["range"], serde_json::json!([0, 999]));
+        }
+
+        let request: serde_json::Value = serde_json::from_str(&export_core_import_request(&wallet, None)).unwrap();
+        assert_eq!(request[0]["timestamp"], "now");
+    }
+
+    #[test]
+    fn test_fee_presets() {
+        let fee_estimates: HashMap<String, f64> = [("1", 20.5), ("2", 15.0), ("6", 8.25), ("144", 1.0)]
+            .into_iter()
+            .map(|(block, fee_rate)| (block.to_string(), fee_rate))
+            .collect();
+        let presets = fee_presets(&fee_estimates).unwrap();
+        assert_eq!(presets, FeePresets { slow: 1.0, normal: 8.25, fast: 20.5 });
+
+        // sparse and non-monotonic estimates
+        let fee_estimates: HashMap<String, f64> = [("3", 10.0), ("25", 12.0)]
+            .into_iter()
+            .map(|(block, fee_rate)| (block.to_string(), fee_rate))
+            .collect();
+        let presets = fee_presets(&fee_estimates).unwrap();
+        assert_eq!(presets.fast, 10.0);
+        assert!(presets.slow <= presets.normal && presets.normal <= presets.fast);
+
+        assert!(fee_presets(&HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_immature_coinbase_excluded() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+
+        // a coinbase-like transaction mined at the tip
+        let coinbase = Transaction {
+            version: 1,
+            lock_time: PackedLockTime(0),
+            input: vec![bdk::bitcoin::TxIn::default()],
+            output: vec![TxOut {
+                value: 5_000_000_000,
+                script_pubkey: wallet.get_address(AddressIndex::New).script_pubkey(),
+            }],
+        };
+        assert!(coinbase.is_coin_base());
+        wallet.insert_checkpoint(BlockId { height: 1_000, hash: BlockHash::all_zeros() }).unwrap();
+        wallet.insert_tx(coinbase.clone(), ConfirmationTime::Confirmed { height: 1_000, time: 100 }).unwrap();
+
+        assert_eq!(wallet.get_balance().immature, 5_000_000_000);
+        assert_eq!(immature_coinbase_outpoints(&wallet), vec![OutPoint::new(coinbase.txid(), 0)]);
+        let err = build_drain_transaction(&mut wallet, address, Fee::Rate(1.0), None).unwrap_err();
+        assert!(err.to_string().contains("coinbase"));
+
+        // a regular UTXO is spent, the coinbase isn't
+        fund_wallet(&mut wallet, 50_000, 1_000);
+        let psbt = build_drain_transaction(&mut wallet, address, Fee::Rate(1.0), None).unwrap();
+        assert_eq!(psbt.unsigned_tx.input.len(), 1);
+        assert_ne!(psbt.unsigned_tx.input[0].previous_output.txid, coinbase.txid());
+    }
+
+    #[test]
+    fn test_build_cpfp_transaction() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, funding_txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        // a low-fee parent paying back to the wallet
+        let address = wallet.get_address(AddressIndex::New).address.to_string();
+        let parent = insert_unconfirmed_drain(&mut wallet, &address, Fee::Rate(1.0));
+        let parent_fee = 50_000 - parent.output[0].value;
+
+        let (mut psbt, package_fee_rate) = build_cpfp_transaction(&mut wallet, parent.txid(), parent_fee, 10.0).unwrap();
+        assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+        let child = psbt.extract_tx();
+        assert_eq!(child.input.len(), 1);
+        assert_eq!(child.input[0].previous_output, OutPoint::new(parent.txid(), 0));
+        assert!(wallet.is_mine(&child.output[0].script_pubkey));
+
+        let child_fee = parent.output[0].value - child.output[0].value;
+        assert!(child_fee > parent_fee);
+        let actual_rate = (parent_fee + child_fee) as f32 / (parent.vsize() + child.vsize()) as f32;
+        assert!(actual_rate >= 9.9, "package fee rate {} is too low", actual_rate);
+        assert!((package_fee_rate - actual_rate).abs() < 0.5);
+
+        // confirmed transactions don't need a child
+        assert!(build_cpfp_transaction(&mut wallet, funding_txid, 0, 10.0).is_err());
+
+        // a P2WPKH child is estimated at 110 vB
+        let (external, internal) = ScriptType::NativeSegwit.derivation_paths();
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::NativeSegwit, external, internal).unwrap();
+        fund_wallet(&mut wallet, 50_000, 1_000);
+        let address = wallet.get_address(AddressIndex::New).address.to_string();
+        let parent = insert_unconfirmed_drain(&mut wallet, &address, Fee::Rate(1.0));
+        let parent_fee = 50_000 - parent.output[0].value;
+        let (psbt, _) = build_cpfp_transaction(&mut wallet, parent.txid(), parent_fee, 10.0).unwrap();
+        let child_fee = parent.output[0].value - psbt.unsigned_tx.output[0].value;
+        assert_eq!(child_fee, (parent.vsize() as u64 + 110) * 10 - parent_fee);
+    }
+
+    #[test]
+    fn test_avoid_unconfirmed_change() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        // an unconfirmed payment leaves unconfirmed change
+        let options = TxOptions::for_network(Network::Regtest);
+        let mut psbt = build_transaction(&mut wallet, address, Some(10_000), Fee::Rate(1.0), None, &options).unwrap();
+        wallet.sign(&mut psbt, SignOptions::default()).unwrap();
+        wallet.insert_tx(psbt.extract_tx(), ConfirmationTime::Unconfirmed { last_seen: 0 }).unwrap();
+        // and a confirmed receive
+        let confirmed_txid = fund_wallet(&mut wallet, 30_000, 1_000);
+
+        let psbt = build_transaction(&mut wallet, address, None, Fee::Rate(1.0), None, &options).unwrap();
+        assert_eq!(psbt.unsigned_tx.input.len(), 2);
+
+        let options = TxOptions { avoid_unconfirmed_change: true, ..options };
+        let psbt = build_transaction(&mut wallet, address, None, Fee::Rate(1.0), None, &options).unwrap();
+        assert_eq!(psbt.unsigned_tx.input.len(), 1);
+        assert_eq!(psbt.unsigned_tx.input[0].previous_output.txid, confirmed_txid);
+    }
+
+    #[test]
+    fn test_gap_info() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        let info = gap_info(&wallet);
+        assert_eq!(info[0], GapInfo { keychain: "External".to_string(), highest_used: Some(0), next_unused: 1, gap: 0 });
+        assert_eq!(info[1], GapInfo { keychain: "Internal".to_string(), highest_used: None, next_unused: 0, gap: 0 });
+
+        // revealing unused addresses widens the gap
+        reveal_addresses(&mut wallet, 3);
+        assert_eq!(gap_info(&wallet)[0].gap, 3);
+    }
+
+    #[test]
+    fn test_insert_external_tx() {
+        use bdk::bitcoin::consensus::encode::serialize_hex;
+
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+        wallet.insert_checkpoint(BlockId { height: 1_000, hash: BlockHash::all_zeros() }).unwrap();
+
+        let tx = Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![bdk::bitcoin::TxIn::default()],
+            output: vec![TxOut {
+                value: 25_000,
+                script_pubkey: wallet.get_address(AddressIndex::New).script_pubkey(),
+            }],
+        };
+        let raw_hex = serialize_hex(&tx);
+
+        // above the tip
+        let confirmation = ConfirmationTime::Confirmed { height: 1_001, time: 100 };
+        assert!(insert_external_tx(&mut wallet, &raw_hex, confirmation).is_err());
+        assert!(insert_external_tx(&mut wallet, "zz", ConfirmationTime::Unconfirmed { last_seen: 0 }).is_err());
+
+        let confirmation = ConfirmationTime::Confirmed { height: 900, time: 100 };
+        assert_eq!(insert_external_tx(&mut wallet, &raw_hex, confirmation).unwrap(), tx.txid());
+        assert_eq!(wallet.get_balance().total(), 25_000);
+    }
+
+    #[test]
+    fn test_total_balance_across_accounts() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (external, internal) = account_derivation_paths(0);
+        assert_eq!((external.as_str(), internal.as_str()), (DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL));
+
+        let (first, _) = get_funded_wallet_with_change(mnemonic_24, &external, &internal);
+        let (external, internal) = account_derivation_paths(1);
+        let (mut second, _) = get_funded_wallet_with_change(mnemonic_24, &external, &internal);
+        fund_wallet(&mut second, 25_000, 1_000);
+        assert!(!descriptors_equal(&first, &second));
+
+        let individual = first.get_balance().total() + second.get_balance().total();
+        assert_eq!(total_balance(&[first, second]), individual);
+        assert_eq!(individual, 125_000);
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_balance() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        // Esplora knows of a payment to the first address of each account, of 10,000 sats more per account
+        let funded: HashMap<Script, u64> = (0..3)
+            .map(|account| {
+                let (external, internal) = account_derivation_paths(account);
+                let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, &external, &internal).unwrap();
+                let script_pubkey = wallet.get_address(AddressIndex::Peek(0)).address.script_pubkey();
+                (script_pubkey, 10_000 * (account as u64 + 1))
+            })
+            .collect();
+        let scanner = std::sync::Arc::new(MockScanner::new(move |_, keychain_spks| {
+            let mut update = Update::default();
+            for (keychain, spks) in keychain_spks {
+                for (index, script_pubkey) in spks {
+                    if let Some(value) = funded.get(script_pubkey) {
+                        let _ = update.graph.insert_tx(Transaction {
+                            version: 1,
+                            lock_time: PackedLockTime(0),
+                            input: vec![],
+                            output: vec![TxOut { value: *value, script_pubkey: script_pubkey.clone() }],
+                        });
+                        update.keychain.insert(*keychain, *index);
+                    }
+                }
+            }
+            Ok(update)
+        }));
+
+        // each account's wallet is synced on its own
+        let total = aggregate_balance(mnemonic_24, "regtest", vec![0, 1, 2], &scanner).await.unwrap();
+        assert_eq!(total, 60_000);
+        assert_eq!(scanner.scans().len(), 6);
+
+        assert_eq!(aggregate_balance(mnemonic_24, "regtest", vec![1], &scanner).await.unwrap(), 20_000);
+        assert_eq!(aggregate_balance(mnemonic_24, "regtest", vec![], &scanner).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_scan_requests_concurrency() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+
+        // the scan of every keychain uses the server's configured concurrency
+        let base_url = "https://mempool.space/api";
+        let requests = scan_requests(&wallet, base_url, StopGaps { external: 20, internal: 5 }, 10);
+        assert_eq!(requests.len(), 2);
+        for request in &requests {
+            assert_eq!(request.parallel_requests, concurrency_config().for_url(base_url));
+        }
+        let external = requests.iter().find(|request| request.keychain == KeychainKind::External).unwrap();
+        assert_eq!(external.stop_gap, 20);
+        assert_eq!(external.batch_size, 10);
+    }
+
+    #[test]
+    fn test_scan_batches() {
+        // NOTE: counts the scan calls and their sizes, as a sync would make them
+        let scan = |batch_size: usize, stop_gap: usize, next_unused: u32| {
+            let mut batches = ScanBatches::new(0, batch_size, stop_gap);
+            let mut sizes = Vec::new();
+            while let Some(batch) = batches.next_batch(next_unused) {
+                sizes.push(batch.len());
+            }
+            sizes
+        };
+
+        // a small batch size makes more, smaller calls
+        assert_eq!(scan(5, 20, 0), vec![5, 5, 5, 5]);
+        assert_eq!(scan(100, 20, 0), vec![100]);
+        // used SPKs extend the scan past them
+        assert_eq!(scan(5, 20, 12), vec![5; 7]);
+        // a resumed scan starts from its index
+        let mut batches = ScanBatches::new(50, 10, 5);
+        assert_eq!(batches.next_batch(0), Some(50..60));
+        assert_eq!(batches.next_batch(0), None);
+    }
+
+    #[tokio::test]
+    async fn test_sync_scan_batch_size() {
         let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
-        let wallet_mainnet_12 = create_wallet(
-            mnemonic_12,
-            "mainnet",
+        let stop_gaps = StopGaps { external: 20, internal: 5 };
+
+        // NOTE: counts the scans of a sync finding no transactions
+        let sync_scans = |batch_size: usize| async move {
+            let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+            let scanner = MockScanner::new(|_, _| Ok(Update::default()));
+            sync_wallet(&mut wallet, &scanner, SyncConfig { stop_gaps, batch_size }).await.unwrap();
+            scanner.scans()
+        };
+
+        // a small batch size makes more, smaller scans: 4 up to the external stop gap, 1 for the internal one
+        let small = sync_scans(5).await;
+        assert_eq!(small.len(), 5);
+        assert!(small.iter().all(|scan| scan.indices.len() == 5));
+        assert_eq!(small[3].indices, (15..20).collect::<Vec<u32>>());
+
+        let large = sync_scans(100).await;
+        assert_eq!(large.len(), 2);
+        assert!(large.iter().all(|scan| scan.indices.len() == 100));
+    }
+
+    #[test]
+    fn test_default_script_type() {
+        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let script_type = default_script_type();
+
+        // the default wallet's addresses match the configured default script type
+        let mut wallet = create_default_wallet(mnemonic_12, "mainnet").unwrap();
+        let address = wallet.get_address(AddressIndex::Peek(0)).address;
+        assert!(address.to_string().starts_with(&script_type.address_prefix(Network::Bitcoin)));
+
+        // taproot unless configured otherwise
+        assert_eq!(parse_default_script_type(None), ScriptType::Taproot);
+        assert_eq!(parse_default_script_type(Some("bogus")), ScriptType::Taproot);
+        assert_eq!(parse_default_script_type(Some("segwit")), ScriptType::NativeSegwit);
+
+        // a native segwit deployment's default wallet is a wpkh descriptor at m/84'
+        let script_type = parse_default_script_type(Some("segwit"));
+        let mut wallet = create_wallet_with_default_paths(mnemonic_12, "mainnet", script_type).unwrap();
+        assert_eq!(
+            wallet.get_address(AddressIndex::Peek(0)).address.to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+
+        // explicit derivation paths don't depend on the default script type
+        let mut wallet = create_wallet_with_script_type(mnemonic_12, "mainnet", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+        assert!(wallet.get_address(AddressIndex::Peek(0)).address.to_string().starts_with("bc1p"));
+    }
+
+    #[tokio::test]
+    async fn test_reorg_detected() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        // the wallet's transaction is confirmed in its checkpoint at height 1000, a block that gets orphaned
+        let (mut wallet, txid) = get_funded_wallet_with_change(mnemonic_24, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL);
+        assert!(wallet.transactions().any(|tx| tx.node.txid == txid && tx.observed_as.is_confirmed()));
+
+        // Esplora's chain has other blocks at the wallet's checkpoints, which didn't confirm the transaction
+        let scanner = MockScanner::new(|local_chain, _| {
+            let blocks = local_chain.keys().map(|height| BlockId {
+                height: *height,
+                hash: BlockHash::hash(format!("correct chain {}", height).as_bytes()),
+            });
+            Ok(Update {
+                chain: LocalChain::from_blocks(blocks),
+                ..Update::default()
+            })
+        });
+        let result = sync_wallet_with_stop_gaps(&mut wallet, &scanner, StopGaps::default(), 100).await.unwrap();
+        assert!(result.reorg_detected);
+        assert!(wallet.transactions().any(|tx| tx.node.txid == txid && !tx.observed_as.is_confirmed()));
+
+        // syncing again against the same chain isn't a reorg
+        let result = sync_wallet_with_stop_gaps(&mut wallet, &scanner, StopGaps::default(), 100).await.unwrap();
+        assert!(!result.reorg_detected);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_wallet() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+        let script_pubkey = wallet.get_address(AddressIndex::New).address.script_pubkey();
+        assert_eq!(wallet.get_balance().total(), 0);
+
+        // Esplora knows of a transaction funding the revealed address
+        let funding = Transaction {
+            version: 1,
+            lock_time: PackedLockTime(0),
+            input: vec![],
+            output: vec![TxOut { value: 20_000, script_pubkey: script_pubkey.clone() }],
+        };
+        let scanner = MockScanner::new(|_, keychain_spks| {
+            let mut update = Update::default();
+            if keychain_spks.values().flatten().any(|(_, spk)| *spk == script_pubkey) {
+                let _ = update.graph.insert_tx(funding.clone());
+            }
+            Ok(update)
+        });
+
+        let result = refresh_wallet(&mut wallet, &scanner).await.unwrap();
+        assert!(result.changed);
+        assert_eq!(wallet.get_balance().total(), 20_000);
+
+        // only the revealed SPK was scanned
+        let scans = scanner.scans();
+        assert_eq!(scans.len(), 1);
+        assert_eq!(scans[0].keychain, KeychainKind::External);
+        assert_eq!(scans[0].indices, vec![0]);
+    }
+
+    #[test]
+    fn test_generate_new_wallet() {
+        let new_wallet = generate_new_wallet("regtest").unwrap();
+        assert_eq!(new_wallet.mnemonic.split_whitespace().count(), 12);
+        assert_eq!(new_wallet.descriptors.len(), 2);
+
+        // the first address is index 0 of the returned mnemonic
+        let mut wallet = create_default_wallet(&new_wallet.mnemonic, "regtest").unwrap();
+        assert_eq!(wallet.get_address(AddressIndex::Peek(0)).address.to_string(), new_wallet.first_address);
+        assert_eq!(wallet.get_descriptor_for_keychain(KeychainKind::External).to_string(), new_wallet.descriptors[0]);
+
+        // every call generates a fresh mnemonic
+        assert_ne!(generate_new_wallet("regtest").unwrap().mnemonic, new_wallet.mnemonic);
+    }
+
+    #[test]
+    fn test_bump_fee_minimum() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _) = get_funded_wallet_with_change(
+            mnemonic_24,
             DEFAULT_DERIVATION_PATH_EXTERNAL,
             DEFAULT_DERIVATION_PATH_INTERNAL,
-        ).unwrap();
-        let wallet_mainnet_24 = create_wallet(
+        );
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        let mut psbt = build_transaction(&mut wallet, address, Some(20_000), Fee::Rate(1.0), None, &TxOptions::for_network(Network::Regtest)).unwrap();
+        wallet.sign(&mut psbt, SignOptions::default()).unwrap();
+        let tx = psbt.extract_tx();
+        wallet.insert_tx(tx.clone(), ConfirmationTime::Unconfirmed { last_seen: 0 }).unwrap();
+        let original_fee = wallet.get_tx(tx.txid(), false).unwrap().fee.unwrap();
+
+        // a higher fee rate that doesn't pay for the replacement's relay is rejected with the minimum
+        let min_fee_rate = min_bump_fee_rate(original_fee, tx.vsize());
+        assert!(min_fee_rate >= 2.0);
+        let err = bump_fee(&mut wallet, tx.txid(), 1.5).unwrap_err();
+        assert_eq!(err.to_string(), format!("Fee bump too small, minimum is {:.2} sat/vB", min_fee_rate));
+
+        let psbt = bump_fee(&mut wallet, tx.txid(), min_fee_rate).unwrap();
+        let fee = 50_000 - psbt.unsigned_tx.output.iter().map(|output| output.value).sum::<u64>();
+        assert!(fee >= original_fee + tx.vsize() as u64);
+    }
+
+    #[test]
+    fn test_get_balance_range() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        // NOTE: funded at external index 0
+        let (wallet, _) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        assert_eq!(get_balance_range(&wallet, KeychainKind::External, 0, 10), 50_000);
+        assert_eq!(get_balance_range(&wallet, KeychainKind::External, 1, 10), 0);
+        assert_eq!(get_balance_range(&wallet, KeychainKind::External, 0, 0), 0);
+        assert_eq!(get_balance_range(&wallet, KeychainKind::Internal, 0, 10), 0);
+    }
+
+    #[test]
+    fn test_find_uneconomical_utxos() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let tiny_txid = fund_wallet(&mut wallet, 1_000, 1_001);
+
+        // a 58 vB input costs 1,160 sats at 20 sat/vB, but only 58 sats at 1 sat/vB
+        assert_eq!(find_uneconomical_utxos(&wallet, 20.0).unwrap(), vec![OutPoint::new(tiny_txid, 0)]);
+        assert!(find_uneconomical_utxos(&wallet, 1.0).unwrap().is_empty());
+
+        // a 68 vB P2WPKH input costs 1,360 sats at 20 sat/vB, more than the 58 vB taproot one
+        let (external, internal) = ScriptType::NativeSegwit.derivation_paths();
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::NativeSegwit, external, internal).unwrap();
+        let small_txid = fund_wallet(&mut wallet, 1_200, 1_000);
+        assert_eq!(find_uneconomical_utxos(&wallet, 20.0).unwrap(), vec![OutPoint::new(small_txid, 0)]);
+    }
+
+    #[test]
+    fn test_drain_balance_too_low() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+        fund_wallet(&mut wallet, 600, 1_000);
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        // a 111 vB drain costs 2,220 sats at 20 sat/vB
+        let err = build_drain_transaction(&mut wallet, address, Fee::Rate(20.0), None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Balance too low to cover fee: the balance is 600 sats, the fee is 2220 sats, short by 1620 sats"
+        );
+        assert!(build_drain_transaction(&mut wallet, address, Fee::Absolute(600), None).is_err());
+
+        // a 110 vB P2WPKH drain costs 2,200 sats at 20 sat/vB
+        let (external, internal) = ScriptType::NativeSegwit.derivation_paths();
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::NativeSegwit, external, internal).unwrap();
+        fund_wallet(&mut wallet, 600, 1_000);
+        let err = build_drain_transaction(&mut wallet, address, Fee::Rate(20.0), None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Balance too low to cover fee: the balance is 600 sats, the fee is 2200 sats, short by 1600 sats"
+        );
+    }
+
+    #[test]
+    fn test_utxo_def() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (wallet, txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        let utxo = wallet.list_unspent().next().unwrap();
+        let script_pubkey = utxo.txout.script_pubkey.clone();
+        let utxo = UtxoDef::from(&wallet, utxo);
+        assert_eq!(utxo.utxo.outpoint, OutPoint::new(txid, 0));
+        assert_eq!(utxo.derivation_path.as_deref(), Some("m/86'/0'/0'/0/0"));
+        assert_eq!(utxo.script_pubkey_hex, script_pubkey.to_hex());
+        // NOTE: P2TR scripts are OP_1 followed by a 32-byte push
+        assert!(utxo.script_pubkey_hex.starts_with("5120"));
+
+        // the JSON keeps LocalUtxo's fields alongside the new ones
+        let json = serde_json::to_value(&utxo).unwrap();
+        for field in ["outpoint", "txout", "keychain", "is_spent", "derivation_index", "confirmation_time", "script_pubkey_hex", "derivation_path"] {
+            assert!(json.get(field).is_some(), "missing {}", field);
+        }
+        assert_eq!(json["outpoint"], OutPoint::new(txid, 0).to_string());
+        assert_eq!(json["keychain"], "External");
+        assert_eq!(serde_json::from_value::<UtxoDef>(json).unwrap(), utxo);
+    }
+
+    #[test]
+    fn test_get_master_fingerprint() {
+        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        assert_eq!(get_master_fingerprint(mnemonic_12).unwrap(), "73c5da0a");
+        assert_eq!(
+            get_master_fingerprint(mnemonic_12).unwrap(),
+            get_derivation_info(mnemonic_12, "testnet", ScriptType::Taproot).unwrap().master_fingerprint
+        );
+        assert!(get_master_fingerprint("abandon abandon").is_err());
+    }
+
+    #[test]
+    fn test_revealed_spks() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let mut wallet = create_wallet_with_script_type(mnemonic_24, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+        assert!(revealed_spks(&wallet).is_empty());
+
+        // funds a new address at index 0, then reveals index 1
+        fund_wallet(&mut wallet, 50_000, 1_000);
+        let funded = wallet.get_address(AddressIndex::Peek(0)).address.script_pubkey();
+        wallet.get_address(AddressIndex::New);
+
+        // only the revealed external SPKs are refreshed, nothing past them
+        let spks = revealed_spks(&wallet);
+        assert_eq!(spks.keys().collect::<Vec<_>>(), vec![&KeychainKind::External]);
+        let external = &spks[&KeychainKind::External];
+        assert_eq!(external.iter().map(|(index, _)| *index).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(external[0].1, funded);
+    }
+
+    #[test]
+    fn test_parse_address_index() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        // NOTE: funded at index 0
+        let (mut wallet, _) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        let peek = parse_address_index("peek", Some(5)).unwrap();
+        assert_eq!(wallet.get_address(peek).index, 5);
+        let new = parse_address_index("new", None).unwrap();
+        assert_eq!(wallet.get_address(new).index, 1);
+        // the new address at index 1 is still unused
+        let last_unused = parse_address_index("last_unused", None).unwrap();
+        assert_eq!(wallet.get_address(last_unused).index, 1);
+
+        assert!(parse_address_index("peek", None).is_err());
+        assert!(parse_address_index("new", Some(0)).is_err());
+        assert!(parse_address_index("last_unused", Some(0)).is_err());
+        assert!(parse_address_index("random", None).is_err());
+        assert!(parse_address_index("peek", Some(1 << 31)).is_err());
+    }
+
+    #[test]
+    fn test_dust_limit_for_address() {
+        let p2pkh = dust_limit_for_address("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn", "testnet").unwrap();
+        let p2wpkh = dust_limit_for_address("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx", "testnet").unwrap();
+        let p2tr = dust_limit_for_address("tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c", "testnet").unwrap();
+        assert_eq!((p2pkh, p2wpkh, p2tr), (546, 294, 330));
+
+        // the address must belong to the network
+        assert!(dust_limit_for_address("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx", "mainnet").is_err());
+        assert!(dust_limit_for_address("not an address", "testnet").is_err());
+    }
+
+    #[test]
+    fn test_list_transactions_confirmations() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        // NOTE: funded at height 1000, which is also the tip
+        let (mut wallet, funding_txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        let tx = insert_unconfirmed_drain(&mut wallet, address, Fee::Rate(1.0));
+
+        let transactions = list_transactions(&wallet);
+        let funding = transactions.iter().find(|entry| entry.txid == funding_txid.to_string()).unwrap();
+        assert_eq!(funding.confirmations, 1);
+        let unconfirmed = transactions.iter().find(|entry| entry.txid == tx.txid().to_string()).unwrap();
+        assert_eq!(unconfirmed.confirmations, 0);
+
+        assert_eq!(confirmations(ConfirmationTime::Confirmed { height: 1_000, time: 100 }, Some(1_005)), 6);
+        assert_eq!(confirmations(ConfirmationTime::Confirmed { height: 1_000, time: 100 }, None), 0);
+    }
+
+    #[test]
+    fn test_wallet_snapshot() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+
+        let snapshot = wallet_snapshot(&mut wallet);
+        assert_eq!(snapshot.balance.total(), 50_000);
+        assert_eq!(snapshot.utxos.len(), 1);
+        assert_eq!(snapshot.utxos[0].utxo.outpoint, OutPoint::new(txid, 0));
+        assert_eq!(snapshot.transactions.len(), 1);
+        assert_eq!(snapshot.transactions[0].txid, txid.to_string());
+        // the funded address at index 0 is used
+        assert_eq!(snapshot.next_receive_address, wallet.get_address(AddressIndex::Peek(1)).address.to_string());
+        assert_eq!(snapshot.tip.unwrap().height, 1_000);
+    }
+
+    #[test]
+    fn test_exact_sequence() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        fund_wallet(&mut wallet, 50_000, 1_001);
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        // a relative timelock of 144 blocks
+        let options = TxOptions { sequence: Some(144), ..TxOptions::for_network(Network::Regtest) };
+        let mut psbt = build_transaction(&mut wallet, address, Some(80_000), Fee::Rate(1.0), None, &options).unwrap();
+        assert_eq!(psbt.unsigned_tx.input.len(), 2);
+        assert!(psbt.unsigned_tx.input.iter().all(|input| input.sequence == Sequence(144)));
+
+        set_exact_sequence(&mut psbt, &[10, 20]).unwrap();
+        assert_eq!(psbt.unsigned_tx.input[0].sequence, Sequence(10));
+        assert_eq!(psbt.unsigned_tx.input[1].sequence, Sequence(20));
+        assert!(set_exact_sequence(&mut psbt, &[10]).is_err());
+        assert!(set_exact_sequence(&mut psbt, &[10, 0xfffffffe]).is_err());
+
+        // a final sequence doesn't signal RBF
+        let options = TxOptions { sequence: Some(0xffffffff), ..options };
+        assert!(build_transaction(&mut wallet, address, Some(20_000), Fee::Rate(1.0), None, &options).is_err());
+    }
+
+    #[test]
+    fn test_split_transaction() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _) = get_funded_wallet_with_change(
             mnemonic_24,
-            "mainnet",
             DEFAULT_DERIVATION_PATH_EXTERNAL,
             DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        for height in 1_001..1_005 {
+            fund_wallet(&mut wallet, 50_000, height);
+        }
+        // 20 recipients of another wallet
+        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mut recipient_wallet = create_wallet_with_script_type(mnemonic_12, "regtest", ScriptType::Taproot, DEFAULT_DERIVATION_PATH_EXTERNAL, DEFAULT_DERIVATION_PATH_INTERNAL).unwrap();
+        let recipient_scripts: HashSet<Script> = (0..20)
+            .map(|index| recipient_wallet.get_address(AddressIndex::Peek(index)).address.script_pubkey())
+            .collect();
+        let recipients: Vec<(String, u64)> = (0..20)
+            .map(|index| (recipient_wallet.get_address(AddressIndex::Peek(index)).address.to_string(), 5_000))
+            .collect();
+
+        // 20 P2TR outputs alone are over 800 vB
+        let psbts = split_transaction(&mut wallet, &recipients, 1.0, 500).unwrap();
+        assert!(psbts.len() > 1);
+        for psbt in &psbts {
+            let tx = &psbt.unsigned_tx;
+            assert!(estimate_vsize(ScriptType::Taproot, tx.input.len(), tx.output.len()) <= 500);
+        }
+
+        // every recipient is paid once, and no UTXO is spent twice
+        let paid = psbts
+            .iter()
+            .flat_map(|psbt| psbt.unsigned_tx.output.iter())
+            .filter(|output| recipient_scripts.contains(&output.script_pubkey))
+            .count();
+        assert_eq!(paid, 20);
+        let inputs: Vec<OutPoint> = psbts
+            .iter()
+            .flat_map(|psbt| psbt.unsigned_tx.input.iter().map(|input| input.previous_output))
+            .collect();
+        assert_eq!(inputs.iter().collect::<HashSet<_>>().len(), inputs.len());
+
+        // a cap too small for a single recipient
+        assert!(split_transaction(&mut wallet, &recipients, 1.0, 100).is_err());
+    }
+
+    #[test]
+    fn test_split_transaction_segwit() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let mut wallet = create_wallet_with_script_type(
+            mnemonic_24,
+            "regtest",
+            ScriptType::NativeSegwit,
+            SEGWIT_DERIVATION_PATH_EXTERNAL,
+            SEGWIT_DERIVATION_PATH_INTERNAL,
         ).unwrap();
-        let wallet_testnet_12 = create_wallet(
+        assert_eq!(ScriptType::of_wallet(&wallet).unwrap(), ScriptType::NativeSegwit);
+        for height in 1_000..1_005 {
+            fund_wallet(&mut wallet, 50_000, height);
+        }
+        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mut recipient_wallet = create_wallet_with_script_type(
             mnemonic_12,
-            "testnet",
+            "regtest",
+            ScriptType::NativeSegwit,
+            SEGWIT_DERIVATION_PATH_EXTERNAL,
+            SEGWIT_DERIVATION_PATH_INTERNAL,
+        ).unwrap();
+        let recipients: Vec<(String, u64)> = (0..20)
+            .map(|index| (recipient_wallet.get_address(AddressIndex::Peek(index)).address.to_string(), 5_000))
+            .collect();
+
+        // the batches are sized by P2WPKH weights, so more recipients fit than P2TR weights would allow
+        let psbts = split_transaction(&mut wallet, &recipients, 1.0, 500).unwrap();
+        for psbt in &psbts {
+            let tx = &psbt.unsigned_tx;
+            assert!(estimate_vsize(ScriptType::NativeSegwit, tx.input.len(), tx.output.len()) <= 500);
+        }
+        assert!(psbts.iter().any(|psbt| {
+            let tx = &psbt.unsigned_tx;
+            estimate_vsize(ScriptType::Taproot, tx.input.len(), tx.output.len()) > 500
+        }));
+    }
+
+    #[test]
+    fn test_describe_policy() {
+        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let wallet = create_default_wallet(mnemonic_12, "regtest").unwrap();
+        assert_eq!(describe_policy(&wallet).unwrap(), "single signature");
+
+        let multisig = "wsh(multi(2,\
+            xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/*,\
+            xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*,\
+            xpub6C6nQwHaWbSrzs5tZ1q7m5R9cPK9eYpNMFesiXsYrgc1P8bvLLAet9JfHjYXKjToD8cBRswJXXbbFpXgwsswVPAZzKMa1jUp2kVkGVUaJa7/0/*))";
+        let wallet = create_wallet_from_descriptor(multisig, None, "mainnet").unwrap();
+        assert_eq!(describe_policy(&wallet).unwrap(), "2 of 3 signatures required");
+    }
+
+    #[test]
+    fn test_estimate_transaction_fee() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _) = get_funded_wallet_with_change(
+            mnemonic_24,
             DEFAULT_DERIVATION_PATH_EXTERNAL,
             DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        fund_wallet(&mut wallet, 50_000, 1_001);
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        let small = estimate_transaction_fee(&mut wallet, address, Some(20_000), 2.0).unwrap();
+        assert_eq!(small.inputs, 1);
+
+        // more than a single 50,000 sats UTXO
+        let large = estimate_transaction_fee(&mut wallet, address, Some(70_000), 2.0).unwrap();
+        assert_eq!(large.inputs, 2);
+        assert_eq!(large.outputs, 2);
+        assert!(large.fee > small.fee);
+        assert!((1.9..=2.2).contains(&large.fee_rate), "unexpected fee rate {}", large.fee_rate);
+
+        // the presets' previews all use the actual coin selection
+        let presets = FeePresets { slow: 1.0, normal: 2.0, fast: 5.0 };
+        let preview = preview_fees(&mut wallet, presets, address, Some(70_000)).unwrap();
+        let estimates = preview.estimates.unwrap();
+        assert_eq!(estimates.normal, large);
+        assert_eq!(estimates.slow.inputs, 2);
+        assert!(estimates.slow.fee < estimates.normal.fee && estimates.normal.fee < estimates.fast.fee);
+        // NOTE: the presets keep their JSON fields
+        let json = serde_json::to_value(preview).unwrap();
+        assert_eq!(json["fast"], 5.0);
+    }
+
+    #[test]
+    fn test_estimate_transaction_fee_segwit() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let mut wallet = create_wallet_with_script_type(
+            mnemonic_24,
+            "regtest",
+            ScriptType::NativeSegwit,
+            SEGWIT_DERIVATION_PATH_EXTERNAL,
+            SEGWIT_DERIVATION_PATH_INTERNAL,
         ).unwrap();
-        let wallet_testnet_24 = create_wallet(
+        fund_wallet(&mut wallet, 50_000, 1_000);
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+
+        let estimate = estimate_transaction_fee(&mut wallet, address, Some(20_000), 2.0).unwrap();
+        assert_eq!(estimate.vsize, estimate_vsize(ScriptType::NativeSegwit, estimate.inputs, estimate.outputs));
+        assert!((1.9..=2.2).contains(&estimate.fee_rate), "unexpected fee rate {}", estimate.fee_rate);
+    }
+
+    #[test]
+    fn test_wallet_diff() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, funding_txid) = get_funded_wallet_with_change(
             mnemonic_24,
-            "testnet",
             DEFAULT_DERIVATION_PATH_EXTERNAL,
             DEFAULT_DERIVATION_PATH_INTERNAL,
-        ).unwrap();
+        );
 
-        assert!(is_wallet(&wallet_mainnet_12));
-        assert!(is_wallet(&wallet_mainnet_24));
-        assert!(is_wallet(&wallet_testnet_12));
-        assert!(is_wallet(&wallet_testnet_24));
-        assert_eq!(wallet_mainnet_12.network(), Network::Bitcoin);
-        assert_eq!(wallet_mainnet_24.network(), Network::Bitcoin);
-        assert_eq!(wallet_testnet_12.network(), Network::Testnet);
-        assert_eq!(wallet_testnet_24.network(), Network::Testnet);
+        // nothing known yet
+        let diff = wallet_diff(&wallet, &[], &[]).unwrap();
+        assert_eq!(diff.new_txids, vec![funding_txid.to_string()]);
+        assert!(diff.newly_confirmed_txids.is_empty());
+        assert_eq!(diff.balance_delta, 50_000);
+
+        // the funding transaction was seen unconfirmed, and has confirmed since
+        let known = vec![funding_txid.to_string()];
+        let diff = wallet_diff(&wallet, &known, &known).unwrap();
+        assert!(diff.new_txids.is_empty());
+        assert_eq!(diff.newly_confirmed_txids, known);
+        assert_eq!(diff.balance_delta, 0);
+
+        // a new outgoing transaction
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        let tx = insert_unconfirmed_drain(&mut wallet, address, Fee::Rate(1.0));
+        let diff = wallet_diff(&wallet, &known, &[]).unwrap();
+        assert_eq!(diff.new_txids, vec![tx.txid().to_string()]);
+        assert_eq!(diff.balance_delta, -50_000);
+
+        assert!(wallet_diff(&wallet, &["not a txid".to_string()], &[]).is_err());
     }
-    #[tokio::test]
-    async fn test_create_signed_transaction() {
+
+    #[test]
+    #[cfg(feature = "p2a-anchors")]
+    fn test_p2a_anchor_output() {
+        let anchor = p2a_anchor_output().unwrap();
+        assert_eq!(anchor.script_pubkey.to_hex(), "51024e73");
+        assert!(anchor.script_pubkey.is_witness_program());
+        assert_eq!(anchor.value, 240);
+
         let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _txid) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        let options = TxOptions { anchor_output: true, ..TxOptions::for_network(Network::Regtest) };
+        let psbt = build_transaction(&mut wallet, address, Some(20_000), Fee::Rate(1.0), None, &options).unwrap();
+        assert_eq!(psbt.unsigned_tx.output.len(), 3);
+        assert!(psbt.unsigned_tx.output.contains(&anchor));
+    }
 
+    #[test]
+    #[cfg(not(feature = "p2a-anchors"))]
+    fn test_p2a_anchor_output_unsupported() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
         let (mut wallet, _txid) = get_funded_wallet_with_change(
             mnemonic_24,
             DEFAULT_DERIVATION_PATH_EXTERNAL,
             DEFAULT_DERIVATION_PATH_INTERNAL,
         );
- 
-        let address_mainnet = "tb1prgvu88s0074nqgq8z95uq250lx4pken99yxerwz5mrcjhrzq642s6l247d";
-        let address_testnet = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
- 
-        let esplora_mainnet = Builder::new(DEFAULT_ESPLORA_BASE_URL_MAINNET).build_async().unwrap();
-        let esplora_testnet =Builder::new(DEFAULT_ESPLORA_BASE_URL_TESTNET).build_async().unwrap();
- 
-        let psbt_mainnet =
-            create_signed_transaction(&mut wallet, address_mainnet, &esplora_mainnet).await.unwrap();
-        let psbt_testnet =
-            create_signed_transaction(&mut wallet, address_testnet, &esplora_testnet).await.unwrap();
- 
-        assert!(is_psbt(&psbt_mainnet));
-        assert!(is_psbt(&psbt_testnet));
+        let address = "tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c";
+        let options = TxOptions { anchor_output: true, ..TxOptions::for_network(Network::Regtest) };
+        let err = build_transaction(&mut wallet, address, Some(20_000), Fee::Rate(1.0), None, &options).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<WalletError>(),
+            Some(WalletError::UnsupportedFeature(_))
+        ));
+    }
+
+    #[test]
+    fn test_batch_transaction_duplicate_recipients() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let script_pubkey = Address::from_str("tb1pce9rpv8x32r4y6xe0063kav2rpp8x9yquhvyjnfmzlk3zqn2rvuq5x7c7c").unwrap().script_pubkey();
+        let address = Address::from_script(&script_pubkey, Network::Regtest).unwrap().to_string();
+        // NOTE: bech32 addresses are case insensitive
+        let recipients = vec![(address.clone(), 10_000), (address.to_uppercase(), 15_000)];
+
+        let psbt = build_batch_transaction(&mut wallet, &recipients, 1.0, DuplicateRecipients::default()).unwrap();
+        let outputs: Vec<&TxOut> = psbt.unsigned_tx.output.iter().filter(|output| output.script_pubkey == script_pubkey).collect();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].value, 25_000);
+
+        let err = build_batch_transaction(&mut wallet, &recipients, 1.0, DuplicateRecipients::Reject).unwrap_err();
+        assert!(err.to_string().starts_with("Duplicate recipient"));
+    }
+
+    #[test]
+    fn test_spendable_balance() {
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let (mut wallet, _) = get_funded_wallet_with_change(
+            mnemonic_24,
+            DEFAULT_DERIVATION_PATH_EXTERNAL,
+            DEFAULT_DERIVATION_PATH_INTERNAL,
+        );
+        let frozen_txid = fund_wallet(&mut wallet, 25_000, 1_001);
+        let coin_control = CoinControl {
+            frozen: HashSet::from([OutPoint::new(frozen_txid, 0)]),
+            ..CoinControl::default()
+        };
+
+        // the frozen coin counts towards the total, but can't be spent
+        assert_eq!(wallet.get_balance().total(), 75_000);
+        assert_eq!(spendable_balance(&wallet, &coin_control, SpendablePolicy::default()), 50_000);
+        assert_eq!(spendable_balance(&wallet, &CoinControl::default(), SpendablePolicy::default()), 75_000);
+    }
+
+    #[tokio::test]
+    async fn test_resume_interrupted_sync() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mnemonic_24 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let store = MemoryStore::new();
+        let mut wallet = load_or_create_wallet(mnemonic_24, "regtest", &store).unwrap();
+        let descriptor = wallet.get_descriptor_for_keychain(KeychainKind::External).to_string();
+        // NOTE: a stop gap spanning three batches of the external keychain
+        let stop_gaps = StopGaps { external: 3 * SYNC_PROGRESS_BATCH, internal: 5 };
+
+        // the connection drops after the first batch
+        let scans = AtomicUsize::new(0);
+        let interrupted = MockScanner::new(|_, _| match scans.fetch_add(1, Ordering::SeqCst) {
+            0 => Ok(Update::default()),
+            _ => Err(anyhow!("Connection lost")),
+        });
+        assert!(resumable_sync_wallet(&mut wallet, &interrupted, &store, stop_gaps).await.is_err());
+        assert_eq!(interrupted.scans()[0].indices[0], 0);
+        let progress = store.load_sync_progress(&descriptor).unwrap();
+        assert_eq!(resume_index(&progress, KeychainKind::External), SYNC_PROGRESS_BATCH as u32);
+
+        // the resumed sync starts from where the interrupted one left off
+        let resumed = MockScanner::new(|_, _| Ok(Update::default()));
+        resumable_sync_wallet(&mut wallet, &resumed, &store, stop_gaps).await.unwrap();
+        let first = &resumed.scans()[0];
+        assert_eq!(first.keychain, KeychainKind::External);
+        assert_eq!(first.indices[0], SYNC_PROGRESS_BATCH as u32);
+
+        // a completed sync clears its progress
+        assert!(store.load_sync_progress(&descriptor).is_none());
+    }
+
+    #[test]
+    fn test_descriptors_key_origin() {
+        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let wallet = create_wallet_with_script_type(mnemonic_12, "regtest", ScriptType::Taproot, "m/86'/1'/0'/0", "m/86'/1'/0'/1").unwrap();
+        let fingerprint = get_master_fingerprint(mnemonic_12).unwrap();
+        assert_eq!(fingerprint.len(), 8);
+        assert!(fingerprint.chars().all(|c| c.is_ascii_hexdigit()));
+
+        for (keychain, index) in [(KeychainKind::External, 0), (KeychainKind::Internal, 1)] {
+            let descriptor = wallet.get_descriptor_for_keychain(keychain).to_string();
+            assert!(
+                descriptor.starts_with(&format!("tr([{}/86'/1'/0']tpub", fingerprint)),
+                "missing key origin in {}",
+                descriptor
+            );
+            assert!(descriptor.contains(&format!("/{}/*)", index)));
+        }
+    }
+
+    #[test]
+    fn test_validate_mnemonic() {
+        let mnemonic_12 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        assert!(validate_mnemonic(mnemonic_12, Language::English).unwrap());
+
+        // swapped words are all known, but break the checksum
+        let swapped = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about abandon";
+        let error = validate_mnemonic(swapped, Language::English).unwrap_err();
+        assert!(matches!(error.downcast_ref::<WalletError>(), Some(WalletError::InvalidMnemonicChecksum)));
+
+        // a typo isn't in the word list
+        let typo = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abuot";
+        let error = validate_mnemonic(typo, Language::English).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<WalletError>(),
+            Some(WalletError::UnknownMnemonicWord(word)) if word == "abuot"
+        ));
+
+        // a wrong length isn't a mnemonic
+        assert!(!validate_mnemonic("abandon abandon about", Language::English).unwrap());
     }
 }
@@ -1,7 +1,7 @@
 use app::*;
 use axum::{routing::{get, post}, Router};
 use fileserv::file_and_error_handler;
-use leptos::*;
+use leptos::{logging::log, *};
 use leptos_axum::{generate_route_list, LeptosRoutes, handle_server_fns};
 
 pub mod fileserv;